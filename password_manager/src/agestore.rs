@@ -0,0 +1,326 @@
+use crate::config::{self, create_private_dir};
+use crate::secret::SecretBytes;
+use crate::store::{write_atomic, Entry, PasswordStore};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Public keys every entry is encrypted to, one per line, in the format
+/// `age -R` reads. Lines starting with `#` are comments.
+pub const RECIPIENTS_FILE: &str = ".age-recipients";
+const EXTENSION: &str = "age";
+
+/// What each entry file held when this process last read or wrote it, so
+/// a save only rewrites entries that changed and the rest of the tree
+/// stays byte for byte the same in version control.
+struct Written {
+    recipients: Option<[u8; 32]>,
+    entries: BTreeMap<String, [u8; 32]>,
+}
+
+static WRITTEN: Mutex<Written> = Mutex::new(Written {
+    recipients: None,
+    entries: BTreeMap::new(),
+});
+
+/// The selected vault's identity file, required to open an `age` vault.
+fn identity() -> io::Result<&'static Path> {
+    let location = config::current();
+    location.identity.as_deref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "vault '{}' has no age identity; set `identity = \"<file>\"` under [vaults.{}] in {}",
+                location.name,
+                location.name,
+                config::CONFIG_FILE
+            ),
+        )
+    })
+}
+
+/// Reads every entry of the selected vault's directory.
+pub fn load() -> io::Result<PasswordStore> {
+    let dir = &config::current().path;
+    if !dir.exists() {
+        return Ok(PasswordStore::new());
+    }
+    let entries = read_tree(dir, identity()?)?;
+    let mut written = WRITTEN.lock().unwrap();
+    written.recipients = recipients_digest(dir)?;
+    written.entries = entries
+        .iter()
+        .map(|(service, entry)| Ok((service.clone(), entry_digest(entry)?)))
+        .collect::<io::Result<_>>()?;
+    Ok(entries)
+}
+
+/// Writes the entries that changed since the last load or save and
+/// removes the files of deleted ones. Everything is re-encrypted when the
+/// recipients file changed, so a new team member can open old entries.
+pub fn save(entries: &PasswordStore) -> io::Result<()> {
+    let dir = &config::current().path;
+    let recipients = read_recipients(dir)?;
+    let mut written = WRITTEN.lock().unwrap();
+    let recipients_changed = written.recipients != recipients_digest(dir)?;
+
+    let mut digests = BTreeMap::new();
+    for (service, entry) in entries {
+        let digest = entry_digest(entry)?;
+        if recipients_changed || written.entries.get(service) != Some(&digest) {
+            let plaintext = SecretBytes::new(serde_json::to_vec_pretty(entry)?);
+            write_entry(dir, service, &plaintext, &recipients)?;
+        }
+        digests.insert(service.clone(), digest);
+    }
+    for service in written.entries.keys() {
+        if !entries.contains_key(service) {
+            remove_entry(dir, service)?;
+        }
+    }
+    written.recipients = recipients_digest(dir)?;
+    written.entries = digests;
+    Ok(())
+}
+
+/// Writes all of `entries` into `dir`, a new or existing age store. With
+/// `recipients`, they also replace the directory's recipients file.
+/// Attachments are left out: their blobs stay with this vault.
+pub fn export(
+    dir: &Path,
+    entries: &BTreeMap<&str, &Entry>,
+    recipients: &[String],
+) -> io::Result<()> {
+    create_private_dir(dir)?;
+    if !recipients.is_empty() {
+        parse_recipients(&recipients.join("\n"))?;
+        write_atomic(
+            &dir.join(RECIPIENTS_FILE),
+            format!("{}\n", recipients.join("\n")).as_bytes(),
+        )?;
+    }
+    let recipients = read_recipients(dir)?;
+    for (service, entry) in entries {
+        let mut value = serde_json::to_value(entry)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("attachments");
+        }
+        let plaintext = SecretBytes::new(serde_json::to_vec_pretty(&value)?);
+        write_entry(dir, service, &plaintext, &recipients)?;
+    }
+    Ok(())
+}
+
+/// Decrypts every entry file under `dir` with the identities in
+/// `identity`.
+pub fn read_tree(dir: &Path, identity: &Path) -> io::Result<PasswordStore> {
+    let identities = read_identities(identity)?;
+    let mut entries = PasswordStore::new();
+    for (service, path) in files(dir)? {
+        let plaintext = decrypt(&path, &identities)?;
+        let entry = serde_json::from_slice(plaintext.expose()).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        entries.insert(service, entry);
+    }
+    Ok(entries)
+}
+
+/// Decrypts each entry file of the selected vault on its own, for
+/// `verify`: one broken file doesn't hide problems in the others.
+pub fn read_each() -> io::Result<Vec<(String, io::Result<SecretBytes>)>> {
+    let identities = read_identities(identity()?)?;
+    Ok(files(&config::current().path)?
+        .into_iter()
+        .map(|(service, path)| {
+            let plaintext = decrypt(&path, &identities);
+            (service, plaintext)
+        })
+        .collect())
+}
+
+/// Whether the selected vault's recipients file can be encrypted to.
+pub fn check_recipients() -> io::Result<()> {
+    read_recipients(&config::current().path).map(drop)
+}
+
+/// Changes whenever an entry file or the recipients file does, without
+/// decrypting anything. `None` if the directory is gone.
+pub fn fingerprint() -> io::Result<Option<[u8; 32]>> {
+    let dir = &config::current().path;
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut hasher = Sha256::new();
+    let mut paths: Vec<PathBuf> = files(dir)?.into_iter().map(|(_, path)| path).collect();
+    paths.push(dir.join(RECIPIENTS_FILE));
+    for path in paths {
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
+        };
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(path.as_os_str().as_encoded_bytes());
+        hasher.update(meta.len().to_le_bytes());
+        hasher.update(modified.as_nanos().to_le_bytes());
+    }
+    Ok(Some(hasher.finalize().into()))
+}
+
+/// `(service, path)` of every `.age` file under `dir`, skipping hidden
+/// files and directories such as `.git`.
+fn files(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut found = Vec::new();
+    let mut pending = vec![(String::new(), dir.to_path_buf())];
+    while let Some((prefix, dir)) = pending.pop() {
+        for item in fs::read_dir(&dir)? {
+            let item = item?;
+            let name = item.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = item.path();
+            if item.file_type()?.is_dir() {
+                pending.push((format!("{}{}/", prefix, name), path));
+            } else if let Some(stem) = name.strip_suffix(&format!(".{}", EXTENSION)) {
+                found.push((format!("{}{}", prefix, stem), path));
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Where `service` is stored: `work/github` is `work/github.age`. Names
+/// that would escape the directory or hide the file are refused.
+fn entry_path(dir: &Path, service: &str) -> io::Result<PathBuf> {
+    let usable = service
+        .split('/')
+        .all(|part| !part.is_empty() && !part.starts_with('.') && !part.contains(['\\', '\0']));
+    if !usable {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "'{}' can't be stored as a file: every folder and name must be non-empty and not start with '.'",
+                service
+            ),
+        ));
+    }
+    Ok(dir.join(format!("{}.{}", service, EXTENSION)))
+}
+
+fn write_entry(
+    dir: &Path,
+    service: &str,
+    plaintext: &SecretBytes,
+    recipients: &[age::x25519::Recipient],
+) -> io::Result<()> {
+    let path = entry_path(dir, service)?;
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)?;
+    }
+    let encryptor =
+        age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(invalid)?;
+    let mut bytes = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut bytes)?;
+    writer.write_all(plaintext.expose())?;
+    writer.finish()?;
+    write_atomic(&path, &bytes)
+}
+
+/// Removes `service`'s file and any folders left empty by it.
+fn remove_entry(dir: &Path, service: &str) -> io::Result<()> {
+    let path = entry_path(dir, service)?;
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut parent = path.parent();
+    while let Some(folder) = parent.filter(|p| *p != dir) {
+        if fs::remove_dir(folder).is_err() {
+            break;
+        }
+        parent = folder.parent();
+    }
+    Ok(())
+}
+
+fn decrypt(path: &Path, identities: &[Box<dyn age::Identity>]) -> io::Result<SecretBytes> {
+    let bytes = fs::read(path)?;
+    let decryptor = age::Decryptor::new_buffered(&bytes[..])
+        .map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref()))
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(SecretBytes::new(plaintext))
+}
+
+fn read_identities(path: &Path) -> io::Result<Vec<Box<dyn age::Identity>>> {
+    age::IdentityFile::from_file(path.display().to_string())?
+        .into_identities()
+        .map_err(invalid)
+}
+
+fn read_recipients(dir: &Path) -> io::Result<Vec<age::x25519::Recipient>> {
+    let path = dir.join(RECIPIENTS_FILE);
+    let text = fs::read_to_string(&path).map_err(|e| {
+        let hint = if e.kind() == io::ErrorKind::NotFound {
+            "; list one age public key per line in it"
+        } else {
+            ""
+        };
+        io::Error::new(e.kind(), format!("{}: {}{}", path.display(), e, hint))
+    })?;
+    let recipients =
+        parse_recipients(&text).map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+    if recipients.is_empty() {
+        return Err(invalid(format!("{}: no recipients", path.display())));
+    }
+    Ok(recipients)
+}
+
+fn parse_recipients(text: &str) -> io::Result<Vec<age::x25519::Recipient>> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| invalid(format!("line {}: bad recipient '{}': {}", i + 1, line, e)))
+        })
+        .collect()
+}
+
+fn recipients_digest(dir: &Path) -> io::Result<Option<[u8; 32]>> {
+    match fs::read(dir.join(RECIPIENTS_FILE)) {
+        Ok(bytes) => Ok(Some(Sha256::digest(bytes).into())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn entry_digest(entry: &Entry) -> io::Result<[u8; 32]> {
+    let plaintext = SecretBytes::new(serde_json::to_vec(entry)?);
+    Ok(Sha256::digest(plaintext.expose()).into())
+}
+
+fn invalid<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
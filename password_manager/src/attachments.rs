@@ -0,0 +1,355 @@
+use crate::config;
+use crate::crypto;
+use crate::secret::SecretString;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::Payload;
+use chacha20poly1305::XChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Encrypted attachment blobs live in this sidecar directory, one file per
+/// attachment, named by attachment id. Keys and metadata stay in the vault.
+pub const ATTACHMENT_DIR: &str = "attachments";
+pub const MAX_ATTACHMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+/// XChaCha20's 24-byte nonce minus STREAM's 5-byte counter and last flag.
+const NONCE_PREFIX_LEN: usize = 19;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Attachment {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    /// Per-attachment key, base64.
+    key: SecretString,
+}
+
+/// Whether `id` has the form `add` gives ids: 32 lowercase hex digits.
+/// Anything else could point blob paths outside the attachment directory.
+pub fn valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl Attachment {
+    fn blob_path(&self) -> io::Result<PathBuf> {
+        if !valid_id(&self.id) {
+            return Err(invalid(format!(
+                "attachment '{}' has a malformed id",
+                self.name
+            )));
+        }
+        Ok(config::current().sidecar(ATTACHMENT_DIR).join(&self.id))
+    }
+
+    /// Whether the encrypted blob is where the vault expects it.
+    pub fn blob_exists(&self) -> bool {
+        self.blob_path().is_ok_and(|path| path.is_file())
+    }
+
+    fn key(&self) -> io::Result<Zeroizing<[u8; 32]>> {
+        let raw = Zeroizing::new(B64.decode(self.key.expose()).map_err(invalid)?);
+        let mut key = Zeroizing::new([0u8; 32]);
+        if raw.len() != key.len() {
+            return Err(invalid("bad attachment key length"));
+        }
+        key.copy_from_slice(&raw);
+        Ok(key)
+    }
+}
+
+/// Encrypts `path` into a new blob and returns its metadata. The blob is
+/// written under a temp name and renamed into place, so a half-written
+/// attachment is never visible.
+pub fn add(path: &Path) -> io::Result<Attachment> {
+    let size = fs::metadata(path)?.len();
+    if size > MAX_ATTACHMENT_SIZE {
+        return Err(too_large());
+    }
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| invalid("attachment path has no usable file name"))?
+        .to_string();
+
+    let key = crypto::random_key();
+    let attachment = Attachment {
+        id: hex(crypto::random_bytes(16).as_slice()),
+        name,
+        size,
+        key: SecretString::from(B64.encode(key.as_slice())),
+    };
+
+    create_dir()?;
+    let blob = attachment.blob_path()?;
+    let tmp = blob.with_extension("tmp");
+    let mut writer = BufWriter::new(create_private(&tmp)?);
+    let written =
+        encrypt_stream(&key, &attachment.id, File::open(path)?, &mut writer).and_then(|n| {
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+            Ok(n)
+        });
+    let written = match written {
+        Ok(n) => n,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+    fs::rename(&tmp, &blob)?;
+    Ok(Attachment {
+        size: written,
+        ..attachment
+    })
+}
+
+/// Decrypts an attachment into `out`, which must not exist yet and is
+/// created readable only by the owner. On any failure the partial output
+/// is removed.
+pub fn extract(attachment: &Attachment, out: &Path) -> io::Result<()> {
+    let blob = File::open(attachment.blob_path()?).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("cannot open blob for '{}': {}", attachment.name, e),
+        )
+    })?;
+    let key = attachment.key()?;
+    let mut writer = BufWriter::new(create_private(out)?);
+    let result = decrypt_stream(&key, &attachment.id, blob, &mut writer).and_then(|()| {
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(out);
+    }
+    result
+}
+
+/// Deletes the blob. Call after the vault no longer references it; a
+/// crash in between only leaves an orphaned, unreadable blob.
+pub fn remove_blob(attachment: &Attachment) -> io::Result<()> {
+    match fs::remove_file(attachment.blob_path()?) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn encrypt_stream(
+    key: &[u8; 32],
+    id: &str,
+    reader: impl Read,
+    mut writer: impl Write,
+) -> io::Result<u64> {
+    let nonce = crypto::random_bytes(NONCE_PREFIX_LEN);
+    writer.write_all(&nonce)?;
+    let mut encryptor =
+        EncryptorBE32::<XChaCha20Poly1305>::new(key.into(), nonce.as_slice().into());
+
+    let mut reader = BufReader::new(reader);
+    let mut buf = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    let mut total = 0u64;
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        total += n as u64;
+        if total > MAX_ATTACHMENT_SIZE {
+            return Err(too_large());
+        }
+        let payload = Payload {
+            msg: &buf[..n],
+            aad: id.as_bytes(),
+        };
+        if n < CHUNK_SIZE || reader.fill_buf()?.is_empty() {
+            let chunk = encryptor
+                .encrypt_last(payload)
+                .map_err(|_| invalid("encryption failed"))?;
+            writer.write_all(&chunk)?;
+            return Ok(total);
+        }
+        let chunk = encryptor
+            .encrypt_next(payload)
+            .map_err(|_| invalid("encryption failed"))?;
+        writer.write_all(&chunk)?;
+    }
+}
+
+fn decrypt_stream(
+    key: &[u8; 32],
+    id: &str,
+    reader: impl Read,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut nonce = [0u8; NONCE_PREFIX_LEN];
+    reader.read_exact(&mut nonce)?;
+    let mut decryptor = DecryptorBE32::<XChaCha20Poly1305>::new(key.into(), (&nonce).into());
+
+    let failed = || invalid("attachment failed authentication (corrupted or truncated)");
+    let mut buf = vec![0u8; CHUNK_SIZE + TAG_SIZE];
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        let payload = Payload {
+            msg: &buf[..n],
+            aad: id.as_bytes(),
+        };
+        if n < buf.len() || reader.fill_buf()?.is_empty() {
+            let chunk = Zeroizing::new(decryptor.decrypt_last(payload).map_err(|_| failed())?);
+            return writer.write_all(&chunk);
+        }
+        let chunk = Zeroizing::new(decryptor.decrypt_next(payload).map_err(|_| failed())?);
+        writer.write_all(&chunk)?;
+    }
+}
+
+/// Like `read_exact`, but a short read at end of input is fine.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn create_dir() -> io::Result<()> {
+    config::create_private_dir(&config::current().sidecar(ATTACHMENT_DIR))
+}
+
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "attachments are limited to {} MiB",
+            MAX_ATTACHMENT_SIZE / (1024 * 1024)
+        ),
+    )
+}
+
+fn invalid<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0123456789abcdef";
+
+    fn key() -> [u8; 32] {
+        [7; 32]
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encrypt(plain: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        let written = encrypt_stream(&key(), ID, plain, &mut sealed).unwrap();
+        assert_eq!(written, plain.len() as u64);
+        sealed
+    }
+
+    fn decrypt(sealed: &[u8], id: &str) -> io::Result<Vec<u8>> {
+        let mut plain = Vec::new();
+        decrypt_stream(&key(), id, sealed, &mut plain)?;
+        Ok(plain)
+    }
+
+    /// The sealed chunks after the nonce prefix.
+    fn chunks(sealed: &[u8]) -> Vec<&[u8]> {
+        sealed[NONCE_PREFIX_LEN..]
+            .chunks(CHUNK_SIZE + TAG_SIZE)
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_around_chunk_boundaries() {
+        for len in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            2 * CHUNK_SIZE,
+            2 * CHUNK_SIZE + 17,
+        ] {
+            let plain = data(len);
+            let sealed = encrypt(&plain);
+            let chunk_count = len.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(
+                sealed.len(),
+                NONCE_PREFIX_LEN + len + chunk_count * TAG_SIZE,
+                "{}",
+                len
+            );
+            assert_eq!(decrypt(&sealed, ID).unwrap(), plain, "{}", len);
+        }
+    }
+
+    #[test]
+    fn truncated_blobs_are_refused() {
+        let sealed = encrypt(&data(2 * CHUNK_SIZE + 17));
+        // Cut exactly at a chunk boundary: what is left looks complete
+        // but its last chunk wasn't sealed as the last one.
+        let boundary = NONCE_PREFIX_LEN + CHUNK_SIZE + TAG_SIZE;
+        for len in [boundary, 2 * boundary - NONCE_PREFIX_LEN, sealed.len() - 1] {
+            let err = decrypt(&sealed[..len], ID).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", len);
+        }
+        assert!(decrypt(&sealed[..NONCE_PREFIX_LEN - 1], ID).is_err());
+    }
+
+    #[test]
+    fn reordered_chunks_are_refused() {
+        let sealed = encrypt(&data(3 * CHUNK_SIZE + 17));
+        let chunks = chunks(&sealed);
+        assert_eq!(chunks.len(), 4);
+        let mut swapped = sealed[..NONCE_PREFIX_LEN].to_vec();
+        for i in [1, 0, 2, 3] {
+            swapped.extend_from_slice(chunks[i]);
+        }
+        let err = decrypt(&swapped, ID).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn edited_blobs_and_other_ids_are_refused() {
+        let mut sealed = encrypt(&data(100));
+        assert!(decrypt(&sealed, "another-attachment").is_err());
+        sealed[NONCE_PREFIX_LEN + 5] ^= 1;
+        assert!(decrypt(&sealed, ID).is_err());
+    }
+
+    #[test]
+    fn only_generated_ids_are_valid() {
+        assert!(valid_id(&hex(&[0xab; 16])));
+        for id in ["", ID, "../../../.bashrc", &hex(&[0xab; 16]).to_uppercase()] {
+            assert!(!valid_id(id), "{}", id);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Append-only log of what was done to which entry, as JSON lines chained
+//! by SHA-256: each record holds the hash of the one before it, and the
+//! hash of the last one is kept in a separate head file.
+//!
+//! The chain is not keyed. It shows that a record was edited, removed,
+//! reordered or cut off the end, but someone who can write both files can
+//! rewrite the whole log from the first changed record onwards and
+//! recompute every hash and the head. It also can't be keyed from the
+//! vault: failed unlocks are logged without a key, and plain vaults have
+//! none. Keep a copy of the head elsewhere to catch a rewrite.
+//!
+//! Records are written before the change they describe is saved, so a
+//! crash may leave a record of a change that never happened, but never a
+//! change without its record.
+
+use crate::config;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Sidecar suffix of the log, e.g. `passwords.audit.jsonl`.
+pub const AUDIT_FILE: &str = "audit.jsonl";
+/// Sequence number and hash of the last record, kept apart from the log so
+/// that cutting records off the end is detectable.
+pub const AUDIT_HEAD: &str = "audit.head";
+
+fn log_path() -> PathBuf {
+    config::current().sidecar(AUDIT_FILE)
+}
+
+fn head_path() -> PathBuf {
+    config::current().sidecar(AUDIT_HEAD)
+}
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    View,
+    Add,
+    /// Notes or custom fields changed in the editor.
+    Edit,
+    Delete,
+    Extract,
+    /// Written to an export bundle or plaintext file.
+    Export,
+    UnlockFailed,
+    /// A request to `serve` with a missing or unknown token.
+    AuthFailed,
+}
+
+/// One line of the log. Never holds secret values: only what happened, to
+/// which service, by whom and when.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Record {
+    seq: u64,
+    time: String,
+    event: Event,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    service: Option<String>,
+    user: String,
+    prev: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    hash: String,
+}
+
+impl Record {
+    /// SHA-256 over the record as serialized without its `hash` field.
+    /// `prev` is part of that, which is what chains the records.
+    fn digest(&self) -> String {
+        let body = Record {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let bytes = serde_json::to_vec(&body).expect("record serializes");
+        hex(&Sha256::digest(bytes))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Head {
+    seq: u64,
+    hash: String,
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_head() -> io::Result<Head> {
+    if !head_path().exists() {
+        return Ok(Head {
+            seq: 0,
+            hash: GENESIS.to_string(),
+        });
+    }
+    let head = serde_json::from_slice(&fs::read(head_path())?)?;
+    Ok(head)
+}
+
+/// Appends an event on behalf of the logged-in OS user.
+pub fn record(event: Event, service: Option<&str>) -> io::Result<()> {
+    record_as(&current_user(), event, service)
+}
+
+/// Appends an event. The new record chains from the stored head rather
+/// than from the last line of the log, so a truncated log stays broken
+/// instead of being silently extended. An exclusive lock on the log is
+/// held from reading the head to writing the new one, so records from
+/// processes sharing the vault never fork the chain.
+pub fn record_as(user: &str, event: Event, service: Option<&str>) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut log = options.open(log_path())?;
+    log.lock()?;
+
+    let head = read_head()?;
+    let mut record = Record {
+        seq: head.seq + 1,
+        time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        event,
+        service: service.map(str::to_string),
+        user: user.to_string(),
+        prev: head.hash,
+        hash: String::new(),
+    };
+    record.hash = record.digest();
+    writeln!(log, "{}", serde_json::to_string(&record)?)?;
+    log.sync_all()?;
+
+    let head = Head {
+        seq: record.seq,
+        hash: record.hash,
+    };
+    // Dropping `log` afterwards releases the lock.
+    crate::store::write_atomic(&head_path(), &serde_json::to_vec(&head)?)
+}
+
+/// Result of [`verify`]: the number of intact records, and a description
+/// of every problem found with its line number.
+pub struct Report {
+    pub records: u64,
+    pub head: String,
+    pub problems: Vec<String>,
+}
+
+/// Walks the whole log checking each record's hash, its link to the
+/// previous record and its sequence number, then compares the end of the
+/// chain with the stored head.
+pub fn verify() -> io::Result<Report> {
+    let mut report = Report {
+        records: 0,
+        head: GENESIS.to_string(),
+        problems: Vec::new(),
+    };
+    let mut expected_seq = 1;
+
+    if log_path().exists() {
+        let log = BufReader::new(fs::File::open(log_path())?);
+        for (i, line) in log.lines().enumerate() {
+            let line = line?;
+            let lineno = i + 1;
+            let record: Record = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    report
+                        .problems
+                        .push(format!("line {}: not a valid record ({})", lineno, e));
+                    continue;
+                }
+            };
+            if serde_json::to_string(&record)? != line {
+                report.problems.push(format!(
+                    "line {}: record was reformatted or has extra fields",
+                    lineno
+                ));
+            }
+            if record.seq != expected_seq {
+                report.problems.push(format!(
+                    "line {}: sequence {} where {} was expected",
+                    lineno, record.seq, expected_seq
+                ));
+            }
+            if record.prev != report.head {
+                report.problems.push(format!(
+                    "line {}: does not chain from the previous record",
+                    lineno
+                ));
+            }
+            if record.digest() != record.hash {
+                report
+                    .problems
+                    .push(format!("line {}: hash mismatch, record was edited", lineno));
+            }
+            expected_seq = record.seq + 1;
+            report.head = record.hash;
+            report.records += 1;
+        }
+    }
+
+    let head = read_head()?;
+    if head.seq != expected_seq - 1 || head.hash != report.head {
+        report.problems.push(format!(
+            "log ends at record {} but the head file expects record {}; the log was truncated or the head was replaced",
+            expected_seq - 1,
+            head.seq
+        ));
+    }
+    Ok(report)
+}
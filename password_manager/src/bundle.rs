@@ -0,0 +1,405 @@
+use crate::secret::{SecretBytes, SecretString};
+use crate::store::{write_atomic, Entry, PasswordStore};
+use age::secrecy::ExposeSecret;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::iter;
+use std::path::Path;
+
+/// Marks the decrypted contents of an export bundle.
+pub const BUNDLE_FORMAT: &str = "password_manager/bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// A selection of entries for another vault. On disk it is an age file
+/// (https://age-encryption.org), encrypted to a passphrase or to one or
+/// more X25519 public keys, so `age` itself can open it too.
+#[derive(Serialize, Deserialize)]
+struct Bundle<E> {
+    format: String,
+    version: u32,
+    created: DateTime<Utc>,
+    /// The importer refuses the bundle after this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<DateTime<Utc>>,
+    entries: E,
+}
+
+/// Who can open a bundle.
+pub enum Protection {
+    Passphrase(SecretString),
+    Recipients(Vec<age::x25519::Recipient>),
+}
+
+/// How a bundle is opened, asked for only once the file has been read.
+pub enum Unlock<'a> {
+    Passphrase(&'a mut dyn FnMut() -> io::Result<SecretString>),
+    Identity(&'a Path),
+}
+
+/// Parses `--expires`: an RFC 3339 timestamp, or a date meaning the end of
+/// that day (UTC).
+pub fn parse_expiry(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is neither YYYY-MM-DD nor an RFC 3339 timestamp", s))?;
+    let next = date.succ_opt().ok_or("date out of range")?;
+    Ok(next
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight exists")
+        .and_utc())
+}
+
+/// The copy of `entry` that goes into a bundle. Attachments stay behind,
+/// since their blobs live next to this vault only, and so do old passwords.
+fn shareable(entry: &Entry) -> Entry {
+    Entry {
+        username: entry.username.clone(),
+        password: SecretString::from(entry.password.expose().to_string()),
+        url: entry.url.clone(),
+        tags: entry.tags.clone(),
+        attachments: Vec::new(),
+        password_changed: entry.password_changed,
+        rotate_every_days: entry.rotate_every_days,
+        expires: entry.expires,
+        policy: entry.policy.clone(),
+        derived: entry.derived.clone(),
+        favourite: entry.favourite,
+        last_used: None,
+        use_count: 0,
+        history: Vec::new(),
+        notes: SecretString::from(entry.notes.expose().to_string()),
+        fields: entry
+            .fields
+            .iter()
+            .map(|(name, value)| (name.clone(), SecretString::from(value.expose().to_string())))
+            .collect(),
+    }
+}
+
+pub fn write(
+    path: &Path,
+    entries: &BTreeMap<&str, &Entry>,
+    protection: Protection,
+    expires: Option<DateTime<Utc>>,
+) -> io::Result<()> {
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created: Utc::now(),
+        expires,
+        entries: entries
+            .iter()
+            .map(|(service, entry)| (*service, shareable(entry)))
+            .collect::<BTreeMap<_, _>>(),
+    };
+    let plaintext = SecretBytes::new(serde_json::to_vec(&bundle)?);
+
+    let encryptor = match protection {
+        Protection::Passphrase(passphrase) => age::Encryptor::with_user_passphrase(
+            age::secrecy::SecretString::from(passphrase.expose().to_string()),
+        ),
+        Protection::Recipients(recipients) => {
+            age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                .map_err(invalid)?
+        }
+    };
+    let mut bytes = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut bytes)?;
+    writer.write_all(plaintext.expose())?;
+    writer.finish()?;
+    write_atomic(path, &bytes)
+}
+
+/// Decrypts a bundle and returns its entries, refusing expired bundles.
+pub fn read(path: &Path, unlock: Unlock) -> io::Result<PasswordStore> {
+    let bytes = fs::read(path)?;
+    let decryptor = age::Decryptor::new_buffered(&bytes[..]).map_err(invalid)?;
+    let mut reader = match unlock {
+        _ if decryptor.is_scrypt() => {
+            let Unlock::Passphrase(passphrase) = unlock else {
+                return Err(invalid(
+                    "this bundle is protected by a passphrase; omit --identity",
+                ));
+            };
+            let passphrase = passphrase()?;
+            let identity = age::scrypt::Identity::new(age::secrecy::SecretString::from(
+                passphrase.expose().to_string(),
+            ));
+            decryptor.decrypt(iter::once(&identity as &dyn age::Identity))
+        }
+        Unlock::Identity(identity_path) => {
+            let identities = age::IdentityFile::from_file(identity_path.display().to_string())?
+                .into_identities()
+                .map_err(invalid)?;
+            decryptor.decrypt(identities.iter().map(|i| i.as_ref()))
+        }
+        Unlock::Passphrase(_) => {
+            return Err(invalid(
+                "this bundle is encrypted to a public key; pass --identity <file>",
+            ))
+        }
+    }
+    .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e.to_string()))?;
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    let plaintext = SecretBytes::new(plaintext);
+
+    let bundle: Bundle<PasswordStore> = serde_json::from_slice(plaintext.expose())?;
+    if bundle.format != BUNDLE_FORMAT || bundle.version != BUNDLE_VERSION {
+        return Err(invalid(format!(
+            "not a version {} password_manager bundle",
+            BUNDLE_VERSION
+        )));
+    }
+    if let Some(expires) = bundle.expires
+        && expires <= Utc::now()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("this bundle expired at {}", expires.to_rfc3339()),
+        ));
+    }
+    Ok(bundle.entries)
+}
+
+/// Writes a new X25519 identity in age's format, readable only by the
+/// owner, and returns its public key.
+pub fn keygen(path: &Path) -> io::Result<String> {
+    let identity = age::x25519::Identity::generate();
+    let public = identity.to_public().to_string();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    writeln!(file, "# created: {}", Utc::now().to_rfc3339())?;
+    writeln!(file, "# public key: {}", public)?;
+    writeln!(file, "{}", identity.to_string().expose_secret())?;
+    file.sync_all()?;
+    Ok(public)
+}
+
+/// Unencrypted export. JSON is a plain vault map of the same entries a
+/// bundle would carry; CSV has one row per entry. Derived entries have an
+/// empty password. Usernames and passwords are written exactly as stored,
+/// since the file exists to carry them to another tool.
+pub fn write_plaintext(path: &Path, entries: &BTreeMap<&str, &Entry>, csv: bool) -> io::Result<()> {
+    let bytes = if csv {
+        let mut out = String::from("service,username,password,url,tags\n");
+        for (service, entry) in entries {
+            let row = [
+                defuse(service),
+                entry.username.clone(),
+                entry.password.expose().to_string(),
+                defuse(entry.url.as_deref().unwrap_or("")),
+                defuse(&entry.tags.join(";")),
+            ]
+            .map(|field| csv_field(&field));
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+        out.into_bytes()
+    } else {
+        let shared: BTreeMap<&str, Entry> = entries
+            .iter()
+            .map(|(service, entry)| (*service, shareable(entry)))
+            .collect();
+        serde_json::to_vec_pretty(&shared)?
+    };
+    write_atomic(path, SecretBytes::new(bytes).expose())
+}
+
+/// Prefixes `'` to text a spreadsheet would run as a formula, which keeps
+/// it text.
+fn defuse(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quotes a CSV field if it needs it (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn invalid<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entries() -> PasswordStore {
+        serde_json::from_str(
+            r#"{
+                "github": {
+                    "username": "me",
+                    "password": "hunter2",
+                    "url": "https://github.com",
+                    "tags": ["work"],
+                    "notes": "recovery codes in the safe",
+                    "history": [{"password": "old-one", "retired": "2026-01-01T00:00:00Z"}],
+                    "attachments": [{"id": "00ff", "name": "codes.txt", "size": 9, "key": "c2VjcmV0LWF0dGFjaG1lbnQta2V5"}],
+                    "use_count": 12,
+                    "last_used": "2026-10-01T00:00:00Z"
+                },
+                "sheet": {"username": "=HYPERLINK(\"http://evil\")", "password": "-1+2", "tags": ["@x"]}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn selection(store: &PasswordStore) -> BTreeMap<&str, &Entry> {
+        store
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+            .collect()
+    }
+
+    fn recipient_pair(dir: &Path) -> (std::path::PathBuf, age::x25519::Recipient) {
+        let identity = dir.join("identity.txt");
+        let public = keygen(&identity).unwrap();
+        (identity, public.parse().unwrap())
+    }
+
+    #[test]
+    fn bundles_round_trip_without_local_only_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let (identity, recipient) = recipient_pair(dir.path());
+        let store = entries();
+        let path = dir.path().join("out.age");
+        write(
+            &path,
+            &selection(&store),
+            Protection::Recipients(vec![recipient]),
+            None,
+        )
+        .unwrap();
+        assert!(!fs::read_to_string(&path)
+            .unwrap_or_default()
+            .contains("hunter2"));
+
+        let read = read(&path, Unlock::Identity(&identity)).unwrap();
+        let github = &read["github"];
+        assert_eq!(github.password.expose(), "hunter2");
+        assert_eq!(github.notes.expose(), "recovery codes in the safe");
+        assert_eq!(github.url.as_deref(), Some("https://github.com"));
+        assert!(github.history.is_empty());
+        assert!(github.attachments.is_empty());
+        assert_eq!((github.use_count, github.last_used), (0, None));
+        assert_eq!(read.len(), 2);
+    }
+
+    #[test]
+    fn passphrase_bundles_need_the_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = entries();
+        let path = dir.path().join("out.age");
+        let passphrase = SecretString::from("correct horse".to_string());
+        write(
+            &path,
+            &selection(&store),
+            Protection::Passphrase(passphrase),
+            None,
+        )
+        .unwrap();
+
+        let mut right = || Ok(SecretString::from("correct horse".to_string()));
+        let read = read(&path, Unlock::Passphrase(&mut right)).unwrap();
+        assert_eq!(read["github"].password.expose(), "hunter2");
+
+        let mut wrong = || Ok(SecretString::from("wrong horse".to_string()));
+        let err = super::read(&path, Unlock::Passphrase(&mut wrong)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        let (identity, _) = recipient_pair(dir.path());
+        assert!(super::read(&path, Unlock::Identity(&identity)).is_err());
+    }
+
+    #[test]
+    fn expired_bundles_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (identity, recipient) = recipient_pair(dir.path());
+        let store = entries();
+        let path = dir.path().join("out.age");
+        let expired = Utc::now() - Duration::seconds(1);
+        write(
+            &path,
+            &selection(&store),
+            Protection::Recipients(vec![recipient.clone()]),
+            Some(expired),
+        )
+        .unwrap();
+        let err = read(&path, Unlock::Identity(&identity)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("expired"));
+
+        let later = Utc::now() + Duration::hours(1);
+        write(
+            &path,
+            &selection(&store),
+            Protection::Recipients(vec![recipient]),
+            Some(later),
+        )
+        .unwrap();
+        assert!(read(&path, Unlock::Identity(&identity)).is_ok());
+    }
+
+    #[test]
+    fn plaintext_json_carries_only_what_a_bundle_would() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = entries();
+        let path = dir.path().join("out.json");
+        write_plaintext(&path, &selection(&store), false).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("hunter2"));
+        for local in ["old-one", "c2VjcmV0", "codes.txt", "use_count", "last_used"] {
+            assert!(!text.contains(local), "{} leaked:\n{}", local, text);
+        }
+        let back: PasswordStore = serde_json::from_str(&text).unwrap();
+        assert_eq!(back["github"].notes.expose(), "recovery codes in the safe");
+    }
+
+    #[test]
+    fn plaintext_csv_defuses_formulas_but_not_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = entries();
+        let path = dir.path().join("out.csv");
+        write_plaintext(&path, &selection(&store), true).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "service,username,password,url,tags\n\
+             github,me,hunter2,https://github.com,work\n\
+             sheet,\"=HYPERLINK(\"\"http://evil\"\")\",-1+2,,'@x\n"
+        );
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=1+1"), "=1+1");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(defuse("=1+1"), "'=1+1");
+        assert_eq!(defuse("+49 30"), "'+49 30");
+        assert_eq!(defuse("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(defuse("\tx"), "'\tx");
+        assert_eq!(defuse("a=b"), "a=b");
+    }
+}
@@ -0,0 +1,308 @@
+//! Command-line arguments. Each command is run by a function in
+//! [`crate::commands`].
+
+use crate::{bundle, display, editor, rotation};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "password_manager", about = "A small local password manager")]
+pub struct Cli {
+    /// Named vault from the config file; defaults to its `default` entry
+    #[arg(long, global = true)]
+    pub vault: Option<String>,
+
+    /// Key file to combine with the master password
+    #[arg(long, global = true)]
+    pub key_file: Option<PathBuf>,
+
+    /// Use the line-based menu instead of the full-screen interface
+    #[arg(long)]
+    pub menu: bool,
+
+    /// Read answers to every question from stdin, one per line, instead of
+    /// prompting on the terminal
+    #[arg(long, global = true)]
+    pub batch: bool,
+
+    /// Like --batch, but read the answers from this file
+    #[arg(long, global = true, value_name = "FILE")]
+    pub answers: Option<PathBuf>,
+
+    /// List services alphabetically instead of favourites and most used first
+    #[arg(long, global = true)]
+    pub alphabetical: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Fuzzy-search entries by service, username, tag or URL
+    Search { query: String },
+    /// Show an entry; the password stays masked unless asked otherwise
+    Show {
+        service: String,
+        /// Reveal only these 1-based characters, e.g. 3-6 or 1,4,7
+        #[arg(long, value_name = "POSITIONS", conflicts_with_all = ["full", "qr", "copy"])]
+        chars: Option<String>,
+        /// Print the whole password
+        #[arg(long, conflicts_with_all = ["qr", "copy"])]
+        full: bool,
+        /// Render the password as a QR code
+        #[arg(long, conflicts_with = "copy")]
+        qr: bool,
+        /// Copy via the OSC 52 terminal clipboard instead of printing
+        #[arg(long)]
+        copy: bool,
+        /// Seconds before the screen or clipboard is cleared (0 = never)
+        #[arg(long, default_value_t = display::CLEAR_AFTER_SECS)]
+        clear_after: u64,
+    },
+    /// Encrypt a file and attach it to an entry (encrypted vaults only)
+    Attach { service: String, file: PathBuf },
+    /// List an entry's attachments
+    Attachments { service: String },
+    /// Decrypt an attachment into a new file readable only by you
+    Extract {
+        service: String,
+        name: String,
+        /// Output path; defaults to the attachment's name
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Delete an attachment
+    Detach { service: String, name: String },
+    /// Show or set the password rules a service enforces
+    Policy(PolicyArgs),
+    /// Compute a LessPass-compatible password from the master secret, site,
+    /// login and counter; nothing secret is stored
+    Derive(DeriveArgs),
+    /// Generate a password that satisfies a service's policy or a preset
+    Generate {
+        /// Use this entry's policy
+        service: Option<String>,
+        /// Use a built-in preset instead
+        #[arg(long, conflicts_with = "service")]
+        preset: Option<String>,
+        /// Preferred length, clamped to the policy
+        #[arg(long)]
+        length: Option<usize>,
+        /// Store it as the entry's new password instead of printing it
+        #[arg(long, requires = "service")]
+        save: bool,
+    },
+    /// List passwords that are overdue or due for rotation soon
+    Due {
+        /// Include entries due within this many days
+        #[arg(long, default_value_t = rotation::DEFAULT_WINDOW_DAYS)]
+        within: u32,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Set when an entry's password has to be changed
+    Rotation {
+        service: String,
+        /// Rotate this many days after each password change
+        #[arg(long, value_name = "DAYS", value_parser = clap::value_parser!(u32).range(1..))]
+        every: Option<u32>,
+        /// Fixed expiry date, YYYY-MM-DD
+        #[arg(long, value_name = "DATE")]
+        expires: Option<chrono::NaiveDate>,
+        /// Remove the interval and expiry date
+        #[arg(long, conflicts_with_all = ["every", "expires"])]
+        clear: bool,
+    },
+    /// Write entries to an encrypted bundle for another vault or person
+    Export(ExportArgs),
+    /// Add the entries of an exported bundle to this vault
+    ImportBundle {
+        file: PathBuf,
+        /// age identity file, for bundles encrypted to a public key
+        #[arg(long)]
+        identity: Option<PathBuf>,
+        /// Overwrite entries that already exist instead of skipping them
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Edit an entry's notes and custom fields in $VISUAL or $EDITOR
+    Edit {
+        service: String,
+        /// Document format to edit in
+        #[arg(long, value_enum, default_value_t = editor::Format::Toml)]
+        format: editor::Format,
+    },
+    /// Mark an entry as a favourite, listed before everything else
+    Favourite {
+        service: String,
+        /// Unmark it instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Write entries to an age store: a directory with one file per entry,
+    /// encrypted to the public keys in its `.age-recipients`
+    ExportAge {
+        dir: PathBuf,
+        /// Export this entry (repeatable); default is the whole vault
+        #[arg(long)]
+        service: Vec<String>,
+        /// Export everything in this folder (repeatable)
+        #[arg(long)]
+        folder: Vec<String>,
+        /// Encrypt to this age public key (repeatable), replacing the
+        /// directory's `.age-recipients`
+        #[arg(long, value_name = "AGE_PUBLIC_KEY")]
+        recipient: Vec<String>,
+    },
+    /// Add the entries of an age store directory to this vault
+    ImportAge {
+        dir: PathBuf,
+        /// age identity file that opens the store's entries
+        #[arg(long)]
+        identity: PathBuf,
+        /// Overwrite entries that already exist instead of skipping them
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Find duplicate entries and merge them, one confirmation at a time
+    Dedupe,
+    /// Create a key pair for receiving bundles; prints the public key
+    BundleKeygen { path: PathBuf },
+    /// Encrypt a plaintext vault with a new master password
+    Encrypt {
+        /// Target time in milliseconds for unlocking on this machine
+        #[arg(long, default_value_t = 1000)]
+        unlock_ms: u64,
+    },
+    /// Change the master password and re-encrypt the vault
+    ChangePassword {
+        /// Re-run the KDF calibration for this unlock time in milliseconds
+        #[arg(long)]
+        unlock_ms: Option<u64>,
+    },
+    /// Generate a new data key and re-encrypt every entry with it
+    RotateKey,
+    /// Create a new random key file
+    KeyfileNew { path: PathBuf },
+    /// Require the given key file, in addition to the master password
+    KeyfileRequire { path: PathBuf },
+    /// Stop requiring a key file
+    KeyfileRemove,
+    /// Serve a JSON API for local tools, authenticated with bearer tokens
+    Serve {
+        /// Loopback address to listen on
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        listen: String,
+        /// Listen on a Unix socket instead
+        #[cfg(unix)]
+        #[arg(long, conflicts_with = "listen")]
+        socket: Option<PathBuf>,
+    },
+    /// Create an API token for `serve`; it is shown only once
+    TokenAdd {
+        name: String,
+        /// Allow put and delete, not just reads
+        #[arg(long)]
+        write: bool,
+        /// Limit the token to this folder (repeatable), e.g. `work` for `work/github`
+        #[arg(long)]
+        folder: Vec<String>,
+    },
+    /// List API tokens
+    TokenList,
+    /// Revoke an API token
+    TokenRevoke { name: String },
+    /// Check the audit log's hash chain for edits and truncation. It is not
+    /// keyed, so it won't catch someone rewriting the whole log
+    VerifyLog,
+    /// Check the vault's authentication tags and every entry against the
+    /// schema, listing all problems found
+    Verify,
+    /// List the vaults in the config file
+    Vaults,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PlainFormat {
+    Json,
+    Csv,
+}
+
+/// Options of `policy`.
+#[derive(Args)]
+pub struct PolicyArgs {
+    pub service: String,
+    /// Start from a built-in preset (strong, alphanumeric, legacy, pin4, pin6)
+    #[arg(long)]
+    pub preset: Option<String>,
+    /// Minimum length
+    #[arg(long)]
+    pub min: Option<usize>,
+    /// Maximum length
+    #[arg(long)]
+    pub max: Option<usize>,
+    /// The only characters the service accepts
+    #[arg(long, value_name = "CHARS")]
+    pub allowed: Option<String>,
+    /// At least one of these characters is required (repeatable)
+    #[arg(long, value_name = "CHARS")]
+    pub require: Vec<String>,
+    /// Substring the password must not contain (repeatable)
+    #[arg(long, value_name = "TEXT")]
+    pub forbid: Vec<String>,
+    /// Remove the policy
+    #[arg(long, conflicts_with_all = ["preset", "min", "max", "allowed", "require", "forbid"])]
+    pub clear: bool,
+}
+
+/// Options of `derive`.
+#[derive(Args)]
+pub struct DeriveArgs {
+    pub site: String,
+    pub login: String,
+    #[arg(long, default_value_t = 1)]
+    pub counter: u32,
+    #[arg(long, default_value_t = 16)]
+    pub length: usize,
+    #[arg(long)]
+    pub no_lowercase: bool,
+    #[arg(long)]
+    pub no_uppercase: bool,
+    #[arg(long)]
+    pub no_digits: bool,
+    #[arg(long)]
+    pub no_symbols: bool,
+    /// Record the parameters as a derived entry instead of printing
+    #[arg(long, value_name = "SERVICE")]
+    pub save: Option<String>,
+    /// Let --save turn an existing entry into a derived one; its stored
+    /// password is kept in the history
+    #[arg(long, requires = "save")]
+    pub replace: bool,
+}
+
+/// Options of `export`.
+#[derive(Args)]
+pub struct ExportArgs {
+    pub out: PathBuf,
+    /// Export this entry (repeatable); default is the whole vault
+    #[arg(long)]
+    pub service: Vec<String>,
+    /// Export everything in this folder (repeatable)
+    #[arg(long)]
+    pub folder: Vec<String>,
+    /// Encrypt to this age public key (repeatable) instead of a passphrase
+    #[arg(long, value_name = "AGE_PUBLIC_KEY")]
+    pub recipient: Vec<String>,
+    /// Refuse to import after this date (end of day, UTC) or RFC 3339 time
+    #[arg(long, value_name = "WHEN", value_parser = bundle::parse_expiry)]
+    pub expires: Option<chrono::DateTime<chrono::Utc>>,
+    /// Write the passwords unencrypted instead; asks for confirmation
+    #[arg(long, conflicts_with_all = ["recipient", "expires"])]
+    pub plaintext: bool,
+    /// File format for --plaintext
+    #[arg(long, value_enum, default_value_t = PlainFormat::Json, requires = "plaintext")]
+    pub format: PlainFormat,
+}
@@ -0,0 +1,85 @@
+//! The JSON API and the tokens that authenticate to it.
+
+use super::Context;
+use crate::{server, tokens};
+use std::io;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+pub fn serve(
+    cx: &mut Context,
+    listen: String,
+    #[cfg(unix)] socket: Option<PathBuf>,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    #[cfg(unix)]
+    if let Some(path) = socket {
+        return server::serve(&mut vault, server::Listen::Unix(path));
+    }
+    let addr: std::net::SocketAddr = listen
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "--listen must be IP:PORT"))?;
+    if !addr.ip().is_loopback() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the API only listens on loopback addresses",
+        ));
+    }
+    server::serve(&mut vault, server::Listen::Tcp(listen))
+}
+
+pub fn token_add(name: String, write: bool, folder: Vec<String>) -> io::Result<()> {
+    let mut all = tokens::load()?;
+    if all.iter().any(|t| t.name == name) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a token named '{}' already exists", name),
+        ));
+    }
+    let access = if write {
+        tokens::Access::ReadWrite
+    } else {
+        tokens::Access::ReadOnly
+    };
+    let (token, secret) = tokens::create(name.clone(), access, folder);
+    all.push(token);
+    tokens::save(&all)?;
+    println!("{}", secret);
+    eprintln!(
+        "Token '{}' created. Store it now; it cannot be shown again.",
+        name
+    );
+    Ok(())
+}
+
+pub fn token_list() -> io::Result<()> {
+    for token in tokens::load()? {
+        let folders = if token.folders.is_empty() {
+            "all folders".to_string()
+        } else {
+            token.folders.join(", ")
+        };
+        let access = if token.can_write() {
+            "read-write"
+        } else {
+            "read-only"
+        };
+        println!("- {} ({}, {})", token.name, access, folders);
+    }
+    Ok(())
+}
+
+pub fn token_revoke(name: String) -> io::Result<()> {
+    let mut all = tokens::load()?;
+    let before = all.len();
+    all.retain(|t| t.name != name);
+    if all.len() == before {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no token named '{}'", name),
+        ));
+    }
+    tokens::save(&all)?;
+    println!("Token '{}' revoked.", name);
+    Ok(())
+}
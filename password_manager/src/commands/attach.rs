@@ -0,0 +1,89 @@
+//! Commands for the files attached to entries.
+
+use super::Context;
+use crate::store::{save_store, Entry};
+use crate::{attachments, audit, find_entry};
+use std::io;
+use std::path::PathBuf;
+
+fn attachment_index(entry: &Entry, service: &str, name: &str) -> io::Result<usize> {
+    entry
+        .attachments
+        .iter()
+        .position(|a| a.name == name)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{}' has no attachment named '{}'", service, name),
+            )
+        })
+}
+
+pub fn attach(cx: &mut Context, service: String, file: PathBuf) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    if !vault.is_encrypted() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "attachment keys are stored in the vault, so it must be encrypted first; run `password_manager encrypt`",
+        ));
+    }
+    let entry = find_entry(&mut vault.entries, &service)?;
+    let name = file.file_name().map(|n| n.to_string_lossy().into_owned());
+    if entry
+        .attachments
+        .iter()
+        .any(|a| Some(&a.name) == name.as_ref())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already has an attachment with that name", service),
+        ));
+    }
+    let attachment = attachments::add(&file)?;
+    println!(
+        "Attached '{}' ({} bytes).",
+        attachment.name, attachment.size
+    );
+    entry.attachments.push(attachment);
+    save_store(&vault)?;
+    Ok(())
+}
+
+pub fn list(cx: &mut Context, service: String) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    if entry.attachments.is_empty() {
+        println!("No attachments.");
+    }
+    for attachment in &entry.attachments {
+        println!("- {} ({} bytes)", attachment.name, attachment.size);
+    }
+    Ok(())
+}
+
+pub fn extract(
+    cx: &mut Context,
+    service: String,
+    name: String,
+    out: Option<PathBuf>,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    let attachment = &entry.attachments[attachment_index(entry, &service, &name)?];
+    let out = out.unwrap_or_else(|| PathBuf::from(&attachment.name));
+    audit::record(audit::Event::Extract, Some(&service))?;
+    attachments::extract(attachment, &out)?;
+    println!("Extracted to {}.", out.display());
+    Ok(())
+}
+
+pub fn detach(cx: &mut Context, service: String, name: String) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    let index = attachment_index(entry, &service, &name)?;
+    let attachment = entry.attachments.remove(index);
+    save_store(&vault)?;
+    attachments::remove_blob(&attachment)?;
+    println!("Attachment '{}' deleted.", name);
+    Ok(())
+}
@@ -0,0 +1,324 @@
+//! Commands that read or change individual entries.
+
+use super::Context;
+use crate::cli::{DeriveArgs, PolicyArgs};
+use crate::display::{self, Reveal};
+use crate::secret::SecretString;
+use crate::store::{save_store, Entry};
+use crate::{
+    audit, dedupe, derive, derive_password, edit_notes, editor, entry_password, find_entry,
+    find_preset, password_len, policy, policy_error, rotation, run_search, usage,
+};
+use std::io;
+
+pub fn search(cx: &mut Context, query: &str) -> io::Result<()> {
+    let (vault, _) = cx.open_vault()?;
+    run_search(&vault.entries, query);
+    Ok(())
+}
+
+pub fn show(
+    cx: &mut Context,
+    service: String,
+    chars: Option<String>,
+    full: bool,
+    qr: bool,
+    copy: bool,
+    clear_after: u64,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    usage::record(entry);
+    let reveal = match chars {
+        Some(spec) => Reveal::Chars(display::parse_positions(&spec, password_len(entry))?),
+        None if full => Reveal::Full,
+        None if qr => Reveal::Qr,
+        None if copy => Reveal::Copy,
+        None => Reveal::Masked,
+    };
+    println!("Username: {}", entry.username);
+    if !entry.history.is_empty() {
+        println!("History:  {} previous password(s)", entry.history.len());
+    }
+    if !entry.fields.is_empty() {
+        let names: Vec<&str> = entry.fields.keys().map(String::as_str).collect();
+        println!("Fields:   {}", names.join(", "));
+    }
+    if !entry.notes.is_empty() {
+        println!(
+            "Notes:    {} line(s); `edit {}` to read them",
+            entry.notes.expose().lines().count(),
+            service
+        );
+    }
+    let password = match reveal {
+        Reveal::Masked => None,
+        _ => Some(entry_password(cx.prompt, entry)?),
+    };
+    save_store(&vault)?;
+    let Some(password) = password else {
+        return display::show("", &reveal, clear_after);
+    };
+    audit::record(audit::Event::View, Some(&service))?;
+    display::show(password.expose(), &reveal, clear_after)
+}
+
+/// Shows the entry's policy, or changes it with the given options.
+pub fn policy(cx: &mut Context, args: PolicyArgs) -> io::Result<()> {
+    let PolicyArgs {
+        service,
+        preset,
+        min,
+        max,
+        allowed,
+        require,
+        forbid,
+        clear,
+    } = args;
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    let changes = preset.is_some()
+        || min.is_some()
+        || max.is_some()
+        || allowed.is_some()
+        || !require.is_empty()
+        || !forbid.is_empty();
+    if clear {
+        entry.policy = None;
+        save_store(&vault)?;
+        println!("Policy for '{}' removed.", service);
+        return Ok(());
+    }
+    if !changes {
+        match &entry.policy {
+            Some(policy) => println!("{}: {}", service, policy.summary()),
+            None => println!("'{}' has no policy.", service),
+        }
+        return Ok(());
+    }
+
+    let mut policy = match preset {
+        Some(name) => find_preset(&name)?,
+        None => entry.policy.clone().unwrap_or_default(),
+    };
+    if let Some(min) = min {
+        policy.min_length = min;
+    }
+    if max.is_some() {
+        policy.max_length = max;
+    }
+    if allowed.is_some() {
+        policy.allowed = allowed;
+    }
+    policy.required.extend(require);
+    policy.forbidden.extend(forbid);
+    policy.check().map_err(policy_error)?;
+
+    let problems = policy.violations(entry.password.expose());
+    println!("{}: {}", service, policy.summary());
+    if !problems.is_empty() {
+        println!("The current password does not satisfy it. It:");
+        for problem in problems {
+            println!("  - {}", problem);
+        }
+    }
+    entry.policy = Some(policy);
+    save_store(&vault)?;
+    Ok(())
+}
+
+pub fn generate(
+    cx: &mut Context,
+    service: Option<String>,
+    preset: Option<String>,
+    length: Option<usize>,
+    save: bool,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let policy = match (&service, preset) {
+        (Some(service), _) => {
+            policy::or_default(find_entry(&mut vault.entries, service)?.policy.as_ref())
+        }
+        (None, Some(name)) => find_preset(&name)?,
+        (None, None) => policy::or_default(None),
+    };
+    let password = SecretString::from(policy.generate(length).map_err(policy_error)?);
+    match service {
+        Some(service) if save => {
+            find_entry(&mut vault.entries, &service)?.set_password(password);
+            save_store(&vault)?;
+            println!(
+                "New password saved for '{}'; reveal it with `password_manager show`.",
+                service
+            );
+        }
+        _ => println!("{}", password.expose()),
+    }
+    Ok(())
+}
+
+/// Prints a derived password, or with `--save` records how to derive it.
+pub fn derive(cx: &mut Context, args: DeriveArgs) -> io::Result<()> {
+    let DeriveArgs {
+        site,
+        login,
+        counter,
+        length,
+        no_lowercase,
+        no_uppercase,
+        no_digits,
+        no_symbols,
+        save,
+        replace,
+    } = args;
+    let derivation = derive::Derivation {
+        site,
+        counter,
+        length,
+        lowercase: !no_lowercase,
+        uppercase: !no_uppercase,
+        digits: !no_digits,
+        symbols: !no_symbols,
+    };
+    derivation.check().map_err(policy_error)?;
+    let Some(service) = save else {
+        println!(
+            "{}",
+            derive_password(cx.prompt, &derivation, &login)?.expose()
+        );
+        return Ok(());
+    };
+    let (mut vault, _) = cx.open_vault()?;
+    match vault.entries.get_mut(&service) {
+        Some(_) if !replace => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "'{}' already exists; use --replace to make it a derived entry",
+                    service
+                ),
+            ));
+        }
+        // Keeps its attachments, notes and settings, like a new
+        // password would.
+        Some(entry) => entry.set_derived(login, derivation),
+        None => {
+            vault
+                .entries
+                .insert(service.clone(), Entry::derived(login, derivation));
+        }
+    }
+    audit::record(audit::Event::Add, Some(&service))?;
+    save_store(&vault)?;
+    println!("Derived entry '{}' saved; no password is stored.", service);
+    Ok(())
+}
+
+pub fn due(cx: &mut Context, within: u32, json: bool) -> io::Result<()> {
+    let (vault, _) = cx.open_vault()?;
+    let today = chrono::Local::now().date_naive();
+    let due = rotation::due(&vault.entries, today, within);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&due)?);
+    } else if due.is_empty() {
+        println!("Nothing due in the next {} days.", within);
+    } else {
+        rotation::print_table(&due);
+    }
+    Ok(())
+}
+
+pub fn rotation(
+    cx: &mut Context,
+    service: String,
+    every: Option<u32>,
+    expires: Option<chrono::NaiveDate>,
+    clear: bool,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    if clear {
+        entry.rotate_every_days = None;
+        entry.expires = None;
+    }
+    if every.is_some() {
+        entry.rotate_every_days = every;
+    }
+    if expires.is_some() {
+        entry.expires = expires;
+    }
+    let today = chrono::Local::now().date_naive();
+    match rotation::due_date(entry, today) {
+        Some((date, _)) => println!("'{}' is next due on {}.", service, date),
+        None => println!("'{}' has no rotation schedule.", service),
+    }
+    save_store(&vault)?;
+    Ok(())
+}
+
+pub fn edit(cx: &mut Context, service: String, format: editor::Format) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let entry = find_entry(&mut vault.entries, &service)?;
+    if edit_notes(&service, entry, format)? {
+        audit::record(audit::Event::Edit, Some(&service))?;
+        save_store(&vault)?;
+    }
+    Ok(())
+}
+
+pub fn favourite(cx: &mut Context, service: String, remove: bool) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    find_entry(&mut vault.entries, &service)?.favourite = !remove;
+    save_store(&vault)?;
+    if remove {
+        println!("'{}' is no longer a favourite.", service);
+    } else {
+        println!("'{}' marked as a favourite.", service);
+    }
+    Ok(())
+}
+
+pub fn dedupe(cx: &mut Context) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let proposals = dedupe::find(&vault.entries);
+    if proposals.is_empty() {
+        println!("No duplicates found.");
+        return Ok(());
+    }
+    let mut merged = 0;
+    for proposal in proposals {
+        let (Some(winner), Some(loser)) = (
+            vault.entries.get(&proposal.winner),
+            vault.entries.get(&proposal.loser),
+        ) else {
+            continue;
+        };
+        println!("\n{}:", proposal.reason);
+        for (name, entry) in [(&proposal.winner, winner), (&proposal.loser, loser)] {
+            println!(
+                "  {:<24} {:<20} {}",
+                name,
+                entry.username,
+                entry.url.as_deref().unwrap_or("")
+            );
+        }
+        if cx.prompt.confirm(
+            &format!(
+                "Merge '{}' into '{}'? Its password is kept in the history.",
+                proposal.loser, proposal.winner
+            ),
+            false,
+        )? {
+            if let Err(e) = dedupe::merge(&mut vault.entries, &proposal.winner, &proposal.loser) {
+                eprintln!("Not merged: {}", e);
+                continue;
+            }
+            audit::record(audit::Event::Delete, Some(&proposal.loser))?;
+            audit::record(audit::Event::Add, Some(&proposal.winner))?;
+            save_store(&vault)?;
+            merged += 1;
+        }
+    }
+    println!("\nMerged {} entries.", merged);
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! One function per command, grouped by what they work on. `main` parses
+//! the arguments, selects the vault and dispatches here.
+
+pub mod api;
+pub mod attach;
+pub mod entries;
+pub mod share;
+pub mod vault;
+
+use crate::keyfile::KeyFile;
+use crate::prompt::Prompter;
+use crate::store::Vault;
+use std::io;
+use std::path::Path;
+
+/// What every command gets besides its own arguments.
+pub struct Context<'a> {
+    /// `--key-file`, read only when a command needs it.
+    pub key_file: Option<&'a Path>,
+    pub prompt: &'a mut dyn Prompter,
+}
+
+impl Context<'_> {
+    /// Unlocks the selected vault, see [`crate::open_vault`].
+    pub fn open_vault(&mut self) -> io::Result<(Vault, Option<KeyFile>)> {
+        crate::open_vault(self.key_file, self.prompt)
+    }
+}
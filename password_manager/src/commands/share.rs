@@ -0,0 +1,195 @@
+//! Commands that move entries between this vault and bundles, age stores
+//! or plaintext files.
+
+use super::Context;
+use crate::cli::{ExportArgs, PlainFormat};
+use crate::store::{self, save_store, Entry, PasswordStore, Vault};
+use crate::{agestore, audit, bundle, find_entry, policy_error, prompt_new_secret};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+
+/// The entries named by `--service` and `--folder`, or all of them.
+fn select_entries<'a>(
+    entries: &'a mut PasswordStore,
+    services: &[String],
+    folders: &[String],
+) -> io::Result<BTreeMap<&'a str, &'a Entry>> {
+    for name in services {
+        find_entry(entries, name)?;
+    }
+    let selected: BTreeMap<&str, &Entry> = entries
+        .iter()
+        .filter(|(name, _)| {
+            (services.is_empty() && folders.is_empty())
+                || services.contains(name)
+                || folders.iter().any(|f| store::in_folder(name, f))
+        })
+        .map(|(name, entry)| (name.as_str(), entry))
+        .collect();
+    if selected.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "nothing to export: no entries in those folders",
+        ));
+    }
+    Ok(selected)
+}
+
+/// Adds imported entries, skipping existing ones unless `replace`. A
+/// replaced entry keeps its attachments. Any attachments in the file are
+/// dropped: their blobs are not part of it, and their ids would name paths
+/// this vault later deletes.
+fn import_entries(vault: &mut Vault, entries: PasswordStore, replace: bool) -> io::Result<()> {
+    let (mut added, mut skipped) = (0, Vec::new());
+    for (service, mut entry) in entries {
+        entry.attachments.clear();
+        if let Some(existing) = vault.entries.get_mut(&service) {
+            if !replace {
+                skipped.push(service);
+                continue;
+            }
+            entry.attachments = std::mem::take(&mut existing.attachments);
+        }
+        audit::record(audit::Event::Add, Some(&service))?;
+        vault.entries.insert(service, entry);
+        added += 1;
+    }
+    save_store(vault)?;
+    println!("Imported {} entries.", added);
+    if !skipped.is_empty() {
+        println!(
+            "Skipped existing entries (use --replace to overwrite): {}",
+            skipped.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub fn export(cx: &mut Context, args: ExportArgs) -> io::Result<()> {
+    let ExportArgs {
+        out,
+        service,
+        folder,
+        recipient,
+        expires,
+        plaintext,
+        format,
+    } = args;
+    let (mut vault, _) = cx.open_vault()?;
+    let selected = select_entries(&mut vault.entries, &service, &folder)?;
+    if expires.is_some_and(|t| t <= chrono::Utc::now()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--expires is already in the past",
+        ));
+    }
+
+    if plaintext {
+        let confirmed = cx.prompt.confirm(
+            &format!(
+                "Write {} password(s) UNENCRYPTED to {}?",
+                selected.len(),
+                out.display()
+            ),
+            false,
+        )?;
+        if !confirmed {
+            println!("Export cancelled.");
+            return Ok(());
+        }
+        bundle::write_plaintext(&out, &selected, matches!(format, PlainFormat::Csv))?;
+    } else {
+        let protection = if recipient.is_empty() {
+            bundle::Protection::Passphrase(prompt_new_secret(cx.prompt, "bundle passphrase")?)
+        } else {
+            bundle::Protection::Recipients(
+                recipient
+                    .iter()
+                    .map(|r| {
+                        r.parse()
+                            .map_err(|e| policy_error(format!("bad recipient '{}': {}", r, e)))
+                    })
+                    .collect::<io::Result<_>>()?,
+            )
+        };
+        bundle::write(&out, &selected, protection, expires)?;
+    }
+    for name in selected.keys() {
+        audit::record(audit::Event::Export, Some(name))?;
+    }
+    let skipped = selected
+        .values()
+        .filter(|e| !e.attachments.is_empty())
+        .count();
+    println!("Exported {} entries to {}.", selected.len(), out.display());
+    if skipped > 0 {
+        println!(
+            "Attachments of {} entries were not included; they stay in this vault.",
+            skipped
+        );
+    }
+    Ok(())
+}
+
+pub fn import_bundle(
+    cx: &mut Context,
+    file: PathBuf,
+    identity: Option<PathBuf>,
+    replace: bool,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let mut ask = || cx.prompt.password("Bundle passphrase: ");
+    let unlock = match &identity {
+        Some(path) => bundle::Unlock::Identity(path),
+        None => bundle::Unlock::Passphrase(&mut ask),
+    };
+    import_entries(&mut vault, bundle::read(&file, unlock)?, replace)
+}
+
+pub fn export_age(
+    cx: &mut Context,
+    dir: PathBuf,
+    service: Vec<String>,
+    folder: Vec<String>,
+    recipient: Vec<String>,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    let selected = select_entries(&mut vault.entries, &service, &folder)?;
+    agestore::export(&dir, &selected, &recipient)?;
+    for name in selected.keys() {
+        audit::record(audit::Event::Export, Some(name))?;
+    }
+    let skipped = selected
+        .values()
+        .filter(|e| !e.attachments.is_empty())
+        .count();
+    println!("Exported {} entries to {}.", selected.len(), dir.display());
+    if skipped > 0 {
+        println!(
+            "Attachments of {} entries were not included; they stay in this vault.",
+            skipped
+        );
+    }
+    Ok(())
+}
+
+pub fn import_age(
+    cx: &mut Context,
+    dir: PathBuf,
+    identity: PathBuf,
+    replace: bool,
+) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    import_entries(&mut vault, agestore::read_tree(&dir, &identity)?, replace)
+}
+
+pub fn bundle_keygen(path: PathBuf) -> io::Result<()> {
+    let public = bundle::keygen(&path)?;
+    println!("{}", public);
+    eprintln!(
+        "Identity written to {}. Send the public key above to whoever exports to you.",
+        path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Commands that change how the vault is encrypted and unlocked, and
+//! check it.
+
+use super::Context;
+use crate::keyfile::KeyFile;
+use crate::prompt::Prompter;
+use crate::secret::SecretString;
+use crate::store::save_store;
+use crate::{
+    audit, config, crypto, prompt_master_password, prompt_new_secret, read_key_file, unlock_vault,
+    verify,
+};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn prompt_new_master_password(prompt: &mut dyn Prompter) -> io::Result<SecretString> {
+    prompt_new_secret(prompt, "new master password")
+}
+
+/// Re-asks for the master password before changing how the vault is
+/// unlocked, so a mistyped password can't re-key the vault.
+fn confirm_master_password(
+    prompt: &mut dyn Prompter,
+    key: &crypto::VaultKey,
+    key_file: Option<&KeyFile>,
+) -> io::Result<SecretString> {
+    let password = prompt.password("Confirm master password: ")?;
+    if !key.verify(password.expose(), key_file)? {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "wrong master password",
+        ));
+    }
+    Ok(password)
+}
+
+fn calibrate(unlock_ms: u64) -> io::Result<crypto::KdfParams> {
+    println!("Calibrating key derivation for ~{} ms...", unlock_ms);
+    let kdf = crypto::calibrate(Duration::from_millis(unlock_ms))?;
+    println!(
+        "Using argon2id with {} MiB, {} passes.",
+        kdf.m_cost / 1024,
+        kdf.t_cost
+    );
+    Ok(kdf)
+}
+
+fn not_encrypted() -> io::Error {
+    if config::current().backend == config::Backend::Age {
+        return io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "this vault is encrypted to the keys in its .age-recipients and has no master password",
+        );
+    }
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "vault is not encrypted; run `password_manager encrypt` first",
+    )
+}
+
+fn verify_vault(key_file: Option<&KeyFile>, prompt: &mut dyn Prompter) -> io::Result<()> {
+    let report = verify::verify(key_file, || prompt_master_password(prompt))?;
+    let kind = if report.encrypted {
+        "encrypted"
+    } else {
+        "plain, no authentication tags to check"
+    };
+    if report.problems.is_empty() {
+        println!("Vault OK: {} entries ({}).", report.entries, kind);
+        return Ok(());
+    }
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+    println!(
+        "Vault FAILED verification: {} problem(s).",
+        report.problems.len()
+    );
+    std::process::exit(1);
+}
+
+pub fn encrypt(cx: &mut Context, unlock_ms: u64) -> io::Result<()> {
+    // The key file is for the key about to be created.
+    let key_file = read_key_file(cx.key_file)?;
+    let mut vault = unlock_vault(key_file.as_ref(), cx.prompt)?;
+    if vault.is_encrypted() {
+        println!("Vault is already encrypted.");
+        return Ok(());
+    }
+    let password = prompt_new_master_password(cx.prompt)?;
+    let kdf = calibrate(unlock_ms)?;
+    vault.key = Some(crypto::VaultKey::create(
+        password.expose(),
+        key_file.as_ref(),
+        kdf,
+    )?);
+    save_store(&vault)?;
+    println!("Vault encrypted.");
+    Ok(())
+}
+
+pub fn change_password(cx: &mut Context, unlock_ms: Option<u64>) -> io::Result<()> {
+    let (mut vault, key_file) = cx.open_vault()?;
+    let key = vault.key.as_mut().ok_or_else(not_encrypted)?;
+    let password = prompt_new_master_password(cx.prompt)?;
+    let kdf = unlock_ms.map(calibrate).transpose()?;
+    let key_file = key_file.as_ref().filter(|_| key.requires_key_file());
+    key.change_password(password.expose(), key_file, kdf)?;
+    save_store(&vault)?;
+    println!("Master password changed.");
+    Ok(())
+}
+
+pub fn rotate_key(cx: &mut Context) -> io::Result<()> {
+    let (mut vault, _) = cx.open_vault()?;
+    vault.key.as_mut().ok_or_else(not_encrypted)?.rotate();
+    save_store(&vault)?;
+    println!("Data key rotated; all entries re-encrypted.");
+    Ok(())
+}
+
+pub fn keyfile_new(path: PathBuf) -> io::Result<()> {
+    KeyFile::generate(&path)?;
+    println!(
+        "Key file written to {}. Keep a backup: without it the vault cannot be opened.",
+        path.display()
+    );
+    Ok(())
+}
+
+pub fn keyfile_require(cx: &mut Context, path: PathBuf) -> io::Result<()> {
+    let (mut vault, key_file) = cx.open_vault()?;
+    let key = vault.key.as_mut().ok_or_else(not_encrypted)?;
+    let current = key_file.as_ref().filter(|_| key.requires_key_file());
+    let password = confirm_master_password(cx.prompt, key, current)?;
+    let new_key_file = KeyFile::read(&path)?;
+    key.change_password(password.expose(), Some(&new_key_file), None)?;
+    save_store(&vault)?;
+    println!("Vault now requires key file {}.", path.display());
+    Ok(())
+}
+
+pub fn keyfile_remove(cx: &mut Context) -> io::Result<()> {
+    let (mut vault, key_file) = cx.open_vault()?;
+    let key = vault.key.as_mut().ok_or_else(not_encrypted)?;
+    if !key.requires_key_file() {
+        println!("Vault does not use a key file.");
+        return Ok(());
+    }
+    let password = confirm_master_password(cx.prompt, key, key_file.as_ref())?;
+    key.change_password(password.expose(), None, None)?;
+    save_store(&vault)?;
+    println!("Key file no longer required.");
+    Ok(())
+}
+
+pub fn verify(cx: &mut Context) -> io::Result<()> {
+    let key_file = read_key_file(cx.key_file)?;
+    verify_vault(key_file.as_ref(), cx.prompt)
+}
+
+pub fn verify_log() -> io::Result<()> {
+    let report = audit::verify()?;
+    if report.problems.is_empty() {
+        println!(
+            "Audit log OK: {} records, head {}.",
+            report.records, report.head
+        );
+        return Ok(());
+    }
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+    println!("Audit log FAILED verification.");
+    std::process::exit(1);
+}
+
+pub fn list_vaults(config: &config::Config) -> io::Result<()> {
+    let default = config.default.as_deref().unwrap_or(config::DEFAULT_VAULT);
+    if !config.vaults.contains_key(default) {
+        let location = config::resolve(config, Some(default))?;
+        println!("* {} {}", location.name, location.path.display());
+    }
+    for name in config.vaults.keys() {
+        let location = config::resolve(config, Some(name))?;
+        let marker = if *name == default { "*" } else { " " };
+        println!(
+            "{} {} {} ({:?})",
+            marker,
+            name,
+            location.path.display(),
+            location.backend
+        );
+    }
+    println!(
+        "Config: {}",
+        config::config_dir()?.join(config::CONFIG_FILE).display()
+    );
+    Ok(())
+}
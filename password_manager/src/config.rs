@@ -0,0 +1,367 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// `$XDG_CONFIG_HOME/password_manager/config.toml`, e.g.
+///
+/// ```toml
+/// default = "personal"
+///
+/// [vaults.personal]
+/// path = "~/.local/share/password_manager/passwords.json"
+///
+/// [vaults.team]
+/// path = "/srv/shared/team"
+/// backend = "age"
+/// identity = "~/.config/password_manager/team.key"
+/// ```
+pub const CONFIG_FILE: &str = "config.toml";
+const APP_DIR: &str = "password_manager";
+/// The vault used when there is no config, or it names no default.
+pub const DEFAULT_VAULT: &str = "default";
+/// File name of the default vault, and of vaults from before named vaults
+/// existed.
+pub const LEGACY_DATA_FILE: &str = "passwords.json";
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Config {
+    /// Vault used without `--vault`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub vaults: BTreeMap<String, VaultConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VaultConfig {
+    /// `~/` is expanded; relative paths are under the data directory.
+    pub path: PathBuf,
+    #[serde(default)]
+    pub backend: Backend,
+    /// age identity file that opens an `age` vault.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<PathBuf>,
+}
+
+/// How a vault's entries are stored.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A single JSON file, plain or encrypted.
+    #[default]
+    Json,
+    /// A directory with one age-encrypted file per entry, mirroring
+    /// folders. See [`crate::agestore`].
+    Age,
+}
+
+/// The vault this process works on. Sidecar files (audit log, tokens,
+/// attachment blobs) sit next to the vault file and share its stem, so
+/// `team.json` has `team.audit.jsonl`.
+#[derive(Debug)]
+pub struct Location {
+    pub name: String,
+    pub path: PathBuf,
+    pub backend: Backend,
+    pub identity: Option<PathBuf>,
+}
+
+impl Location {
+    /// `<dir>/<stem>.<suffix>`.
+    pub fn sidecar(&self, suffix: &str) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map_or_else(|| "passwords".into(), |s| s.to_string_lossy());
+        self.path.with_file_name(format!("{}.{}", stem, suffix))
+    }
+}
+
+static CURRENT: OnceLock<Location> = OnceLock::new();
+
+/// The vault chosen at startup with [`select`].
+pub fn current() -> &'static Location {
+    CURRENT.get().expect("vault selected at startup")
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> io::Result<PathBuf> {
+    let base = match env::var_os(var).filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => home()?.join(fallback),
+    };
+    Ok(base.join(APP_DIR))
+}
+
+fn home() -> io::Result<PathBuf> {
+    env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "$HOME is not set"))
+}
+
+pub fn config_dir() -> io::Result<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn data_dir() -> io::Result<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+pub fn load() -> io::Result<Config> {
+    let path = config_dir()?.join(CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn expand(path: &Path) -> io::Result<PathBuf> {
+    if let Ok(rest) = path.strip_prefix("~") {
+        return Ok(home()?.join(rest));
+    }
+    if path.is_relative() {
+        return Ok(data_dir()?.join(path));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Resolves `--vault` (or the configured default) to a location. The
+/// default vault works without any config; other names must be listed.
+pub fn resolve(config: &Config, name: Option<&str>) -> io::Result<Location> {
+    let name = name.or(config.default.as_deref()).unwrap_or(DEFAULT_VAULT);
+    match config.vaults.get(name) {
+        Some(vault) => Ok(Location {
+            name: name.to_string(),
+            path: expand(&vault.path)?,
+            backend: vault.backend,
+            identity: vault.identity.as_deref().map(expand).transpose()?,
+        }),
+        None if name == DEFAULT_VAULT => Ok(Location {
+            name: name.to_string(),
+            path: data_dir()?.join(LEGACY_DATA_FILE),
+            backend: Backend::Json,
+            identity: None,
+        }),
+        None => {
+            let known: Vec<&str> = config.vaults.keys().map(String::as_str).collect();
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "no vault named '{}' in {}; configured: {}",
+                    name,
+                    config_dir()?.join(CONFIG_FILE).display(),
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                ),
+            ))
+        }
+    }
+}
+
+/// Makes `location` the vault for the rest of the process.
+pub fn select(location: Location) {
+    CURRENT.set(location).expect("vault selected only once");
+}
+
+/// Files to move for a `./passwords.json` from before vaults lived in the
+/// data directory: its sidecar files, then the vault itself. Empty if
+/// there is none, or `location` already has a vault.
+pub fn legacy_files(location: &Location) -> Vec<PathBuf> {
+    let legacy = legacy_location();
+    // Also true when run from the data directory itself.
+    if location.path.exists() || !legacy.path.is_file() {
+        return Vec::new();
+    }
+    let mut files: Vec<PathBuf> = SIDECARS
+        .iter()
+        .map(|suffix| legacy.sidecar(suffix))
+        .filter(|path| path.exists())
+        .collect();
+    files.push(legacy.path);
+    files
+}
+
+fn legacy_location() -> Location {
+    Location {
+        name: String::new(),
+        path: PathBuf::from(LEGACY_DATA_FILE),
+        backend: Backend::Json,
+        identity: None,
+    }
+}
+
+/// Whether `dir` is inside a git, Mercurial or Subversion checkout. A
+/// `passwords.json` there may be a fixture or someone else's file, so it
+/// isn't moved without asking.
+pub fn under_version_control(dir: &Path) -> bool {
+    dir.ancestors().any(|dir| {
+        [".git", ".hg", ".svn"]
+            .iter()
+            .any(|vcs| dir.join(vcs).exists())
+    })
+}
+
+/// Moves the [`legacy_files`] into `location`. Returns what was moved.
+pub fn migrate_legacy(location: &Location) -> io::Result<Vec<PathBuf>> {
+    let files = legacy_files(location);
+    if files.is_empty() {
+        return Ok(files);
+    }
+    if let Some(dir) = location.path.parent() {
+        create_private_dir(dir)?;
+    }
+    let legacy = legacy_location();
+    for suffix in SIDECARS {
+        let from = legacy.sidecar(suffix);
+        if from.exists() {
+            move_path(&from, &location.sidecar(suffix))?;
+        }
+    }
+    // The vault itself last, so an interrupted move is retried next run.
+    move_path(&legacy.path, &location.path)?;
+    Ok(files)
+}
+
+/// Every sidecar suffix, for moving a vault as a whole.
+const SIDECARS: [&str; 4] = [
+    crate::audit::AUDIT_FILE,
+    crate::audit::AUDIT_HEAD,
+    crate::attachments::ATTACHMENT_DIR,
+    crate::tokens::TOKENS_FILE,
+];
+
+/// `rename`, falling back to copy and delete across file systems.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        create_private_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}
+
+/// Creates `dir` and its parents, readable only by the owner.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn expand_home_relative_and_absolute_paths() {
+        assert_eq!(
+            expand(Path::new("~/vaults/a.json")).unwrap(),
+            home().unwrap().join("vaults/a.json")
+        );
+        assert_eq!(
+            expand(Path::new("team.json")).unwrap(),
+            data_dir().unwrap().join("team.json")
+        );
+        assert_eq!(
+            expand(Path::new("/srv/team.json")).unwrap(),
+            PathBuf::from("/srv/team.json")
+        );
+        // Only a leading `~` component is the home directory.
+        assert_eq!(
+            expand(Path::new("~team/x")).unwrap(),
+            data_dir().unwrap().join("~team/x")
+        );
+    }
+
+    #[test]
+    fn resolve_picks_named_then_configured_then_builtin_default() {
+        let empty = Config::default();
+        let location = resolve(&empty, None).unwrap();
+        assert_eq!(location.name, DEFAULT_VAULT);
+        assert_eq!(location.path, data_dir().unwrap().join(LEGACY_DATA_FILE));
+        assert_eq!(location.backend, Backend::Json);
+        let err = resolve(&empty, Some("team")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("configured: none"));
+
+        let config = config(
+            r#"
+            default = "personal"
+            [vaults.personal]
+            path = "/vaults/me.json"
+            [vaults.team]
+            path = "team"
+            backend = "age"
+            identity = "~/team.key"
+            "#,
+        );
+        let location = resolve(&config, None).unwrap();
+        assert_eq!(
+            (location.name.as_str(), location.path),
+            ("personal", PathBuf::from("/vaults/me.json"))
+        );
+        let location = resolve(&config, Some("team")).unwrap();
+        assert_eq!(location.path, data_dir().unwrap().join("team"));
+        assert_eq!(location.backend, Backend::Age);
+        assert_eq!(location.identity, Some(home().unwrap().join("team.key")));
+        // The built-in default stays reachable by name.
+        let location = resolve(&config, Some(DEFAULT_VAULT)).unwrap();
+        assert_eq!(location.path, data_dir().unwrap().join(LEGACY_DATA_FILE));
+        let err = resolve(&config, Some("prod")).unwrap_err();
+        assert!(err.to_string().contains("configured: personal, team"));
+    }
+
+    #[test]
+    fn sidecars_share_the_vault_stem() {
+        let location = Location {
+            name: "team".to_string(),
+            path: PathBuf::from("/srv/team.json"),
+            backend: Backend::Json,
+            identity: None,
+        };
+        assert_eq!(
+            location.sidecar("audit.jsonl"),
+            PathBuf::from("/srv/team.audit.jsonl")
+        );
+    }
+
+    #[test]
+    fn version_control_is_found_in_any_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        assert!(!under_version_control(&nested));
+        fs::write(dir.path().join("a/.git"), "gitdir: elsewhere").unwrap();
+        assert!(under_version_control(&nested));
+        assert!(!under_version_control(dir.path()));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::keyfile::KeyFile;
+use crate::secret::SecretBytes;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Marks a `passwords.json` as an encrypted vault rather than a plain map.
+pub const VAULT_FORMAT: &str = "password_manager/vault";
+pub const VAULT_VERSION: u32 = 1;
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const WRAP_AAD: &[u8] = b"password_manager/vault/v1/key";
+const PAYLOAD_AAD: &[u8] = b"password_manager/vault/v1/payload";
+
+pub type Key = Zeroizing<[u8; KEY_LEN]>;
+
+/// Argon2id parameters used to turn the master password into the key that
+/// wraps the vault's data key.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub salt: String,
+    /// Memory in KiB.
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    pub fn new(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            algorithm: "argon2id".to_string(),
+            salt: B64.encode(salt),
+            m_cost,
+            t_cost,
+            p_cost,
+        }
+    }
+
+    /// Same cost, new salt. Used whenever the master password changes.
+    pub fn resalted(&self) -> Self {
+        Self::new(self.m_cost, self.t_cost, self.p_cost)
+    }
+
+    pub fn derive(&self, password: &[u8]) -> io::Result<Key> {
+        if self.algorithm != "argon2id" {
+            return Err(invalid(format!("unsupported KDF '{}'", self.algorithm)));
+        }
+        let salt = B64.decode(&self.salt).map_err(invalid)?;
+        let params =
+            Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN)).map_err(invalid)?;
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, &salt, key.as_mut())
+            .map_err(invalid)?;
+        Ok(key)
+    }
+}
+
+/// Picks Argon2id parameters that take roughly `target` to derive a key on
+/// this machine. Memory starts at 64 MiB and is halved (down to 8 MiB) only
+/// if a single pass is already too slow; then passes are added until the
+/// target is reached.
+pub fn calibrate(target: Duration) -> io::Result<KdfParams> {
+    const MAX_M_COST: u32 = 64 * 1024;
+    const MIN_M_COST: u32 = 8 * 1024;
+
+    let mut m_cost = MAX_M_COST;
+    loop {
+        let elapsed = time_derive(&KdfParams::new(m_cost, 1, 1))?;
+        if elapsed <= target || m_cost <= MIN_M_COST {
+            let per_pass = elapsed.as_secs_f64().max(f64::EPSILON);
+            let t_cost = (target.as_secs_f64() / per_pass).floor().max(1.0) as u32;
+            return Ok(KdfParams::new(m_cost, t_cost, 1));
+        }
+        m_cost /= 2;
+    }
+}
+
+fn time_derive(params: &KdfParams) -> io::Result<Duration> {
+    let start = Instant::now();
+    params.derive(b"calibration")?;
+    Ok(start.elapsed())
+}
+
+/// Nonce and ciphertext, both base64.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Sealed {
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8], aad: &[u8]) -> io::Result<Sealed> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| invalid("encryption failed"))?;
+    Ok(Sealed {
+        nonce: B64.encode(nonce),
+        ciphertext: B64.encode(ciphertext),
+    })
+}
+
+/// Decrypts and authenticates. A wrong key and a tampered ciphertext are
+/// indistinguishable and both come back as `None`.
+pub fn open(key: &[u8; KEY_LEN], sealed: &Sealed, aad: &[u8]) -> io::Result<Option<SecretBytes>> {
+    let nonce = B64.decode(&sealed.nonce).map_err(invalid)?;
+    let ciphertext = B64.decode(&sealed.ciphertext).map_err(invalid)?;
+    if nonce.len() != 24 {
+        return Err(invalid("bad nonce length"));
+    }
+    let cipher = XChaCha20Poly1305::new(key.into());
+    Ok(cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .ok()
+        .map(SecretBytes::new))
+}
+
+pub fn random_key() -> Key {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    OsRng.fill_bytes(key.as_mut());
+    key
+}
+
+pub fn random_bytes(len: usize) -> Zeroizing<Vec<u8>> {
+    let mut bytes = Zeroizing::new(vec![0u8; len]);
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// The bytes fed to the KDF. Without a key file this is just the password,
+/// so vaults created before key files existed still open. With one it is
+/// `SHA-256(password) || SHA-256(key file)`, as in KeePass's composite key.
+fn composite(password: &str, key_file: Option<&KeyFile>) -> Zeroizing<Vec<u8>> {
+    match key_file {
+        None => Zeroizing::new(password.as_bytes().to_vec()),
+        Some(key_file) => {
+            let mut bytes = Zeroizing::new(Sha256::digest(password.as_bytes()).to_vec());
+            bytes.extend_from_slice(key_file.digest());
+            bytes
+        }
+    }
+}
+
+/// On-disk layout of an encrypted vault. The entries are encrypted with a
+/// random data key; only the data key is encrypted with the key derived
+/// from the master password, so a password change doesn't depend on the
+/// size of the vault and a key rotation doesn't need the KDF.
+#[derive(Serialize, Deserialize)]
+pub struct Envelope {
+    pub format: String,
+    pub version: u32,
+    pub kdf: KdfParams,
+    /// Fingerprint of the required key file, if any. See [`KeyFile::check`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+    pub wrapped_key: Sealed,
+    pub payload: Sealed,
+}
+
+impl Envelope {
+    /// Matches the supplied key file against the one this vault requires.
+    /// Cheap, so it runs before the master password is even asked for.
+    pub fn check_key_file<'a>(
+        &self,
+        key_file: Option<&'a KeyFile>,
+    ) -> io::Result<Option<&'a KeyFile>> {
+        match (&self.key_file, key_file) {
+            (None, _) => Ok(None),
+            (Some(_), None) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "this vault requires a key file; pass --key-file <path>",
+            )),
+            (Some(check), Some(key_file)) if *check != key_file.check() => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "key file does not match this vault",
+            )),
+            (Some(_), Some(key_file)) => Ok(Some(key_file)),
+        }
+    }
+}
+
+/// Key material of an unlocked vault, kept for the session so saving
+/// doesn't have to ask for the master password again.
+pub struct VaultKey {
+    kdf: KdfParams,
+    key_file: Option<String>,
+    kek: Key,
+    data_key: Key,
+}
+
+impl VaultKey {
+    pub fn create(password: &str, key_file: Option<&KeyFile>, kdf: KdfParams) -> io::Result<Self> {
+        let kek = kdf.derive(&composite(password, key_file))?;
+        Ok(Self {
+            kdf,
+            key_file: key_file.map(KeyFile::check),
+            kek,
+            data_key: random_key(),
+        })
+    }
+
+    /// Unlocks `envelope` and returns the key together with the decrypted
+    /// entries JSON.
+    /// A key file is only used if the vault requires one.
+    pub fn unlock(
+        envelope: &Envelope,
+        password: &str,
+        key_file: Option<&KeyFile>,
+    ) -> io::Result<(Self, SecretBytes)> {
+        if envelope.version != VAULT_VERSION {
+            return Err(invalid(format!(
+                "unsupported vault version {}",
+                envelope.version
+            )));
+        }
+        let key_file = envelope.check_key_file(key_file)?;
+        let kek = envelope.kdf.derive(&composite(password, key_file))?;
+        let raw_key = open(&kek, &envelope.wrapped_key, WRAP_AAD)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::PermissionDenied, "wrong master password")
+        })?;
+        let data_key: [u8; KEY_LEN] = raw_key
+            .expose()
+            .try_into()
+            .map_err(|_| invalid("bad data key length"))?;
+        let data_key = Zeroizing::new(data_key);
+        let plaintext = open(&data_key, &envelope.payload, PAYLOAD_AAD)?
+            .ok_or_else(|| invalid("vault payload failed authentication"))?;
+        let key = Self {
+            kdf: envelope.kdf.clone(),
+            key_file: envelope.key_file.clone(),
+            kek,
+            data_key,
+        };
+        Ok((key, plaintext))
+    }
+
+    /// Decrypts a copy of the vault written by another process since this
+    /// key was unlocked. A rotated data key is picked up; a changed master
+    /// password or key file can't be, and is an error.
+    pub fn reopen(&mut self, envelope: &Envelope) -> io::Result<SecretBytes> {
+        let rekeyed = || {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the master key was changed by another process; restart to unlock with the new one",
+            )
+        };
+        if envelope.kdf.salt != self.kdf.salt || envelope.key_file != self.key_file {
+            return Err(rekeyed());
+        }
+        let raw_key = open(&self.kek, &envelope.wrapped_key, WRAP_AAD)?.ok_or_else(rekeyed)?;
+        let data_key: [u8; KEY_LEN] = raw_key
+            .expose()
+            .try_into()
+            .map_err(|_| invalid("bad data key length"))?;
+        self.data_key = Zeroizing::new(data_key);
+        open(&self.data_key, &envelope.payload, PAYLOAD_AAD)?
+            .ok_or_else(|| invalid("vault payload failed authentication"))
+    }
+
+    pub fn requires_key_file(&self) -> bool {
+        self.key_file.is_some()
+    }
+
+    /// Whether `password` and `key_file` are the current master key. Costs
+    /// one KDF run.
+    pub fn verify(&self, password: &str, key_file: Option<&KeyFile>) -> io::Result<bool> {
+        if self.key_file.as_deref() != key_file.map(KeyFile::check).as_deref() {
+            return Ok(false);
+        }
+        let kek = self.kdf.derive(&composite(password, key_file))?;
+        Ok(*kek == *self.kek)
+    }
+
+    /// Re-derives the wrapping key from a new password and key file (or
+    /// none). A fresh salt is always used; pass new `kdf` params to also
+    /// change the cost.
+    pub fn change_password(
+        &mut self,
+        password: &str,
+        key_file: Option<&KeyFile>,
+        kdf: Option<KdfParams>,
+    ) -> io::Result<()> {
+        let kdf = kdf.unwrap_or_else(|| self.kdf.resalted());
+        self.kek = kdf.derive(&composite(password, key_file))?;
+        self.kdf = kdf;
+        self.key_file = key_file.map(KeyFile::check);
+        Ok(())
+    }
+
+    /// Replaces the data key. The next `seal` re-encrypts every entry
+    /// under the new key.
+    pub fn rotate(&mut self) {
+        self.data_key = random_key();
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> io::Result<Envelope> {
+        Ok(Envelope {
+            format: VAULT_FORMAT.to_string(),
+            version: VAULT_VERSION,
+            kdf: self.kdf.clone(),
+            key_file: self.key_file.clone(),
+            wrapped_key: seal(&self.kek, self.data_key.as_ref(), WRAP_AAD)?,
+            payload: seal(&self.data_key, plaintext, PAYLOAD_AAD)?,
+        })
+    }
+}
+
+fn invalid<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cheapest parameters Argon2 accepts, so the tests stay fast.
+    fn kdf() -> KdfParams {
+        KdfParams::new(8, 1, 1)
+    }
+
+    fn unlock(envelope: &Envelope, password: &str) -> io::Result<Vec<u8>> {
+        VaultKey::unlock(envelope, password, None).map(|(_, plain)| plain.expose().to_vec())
+    }
+
+    #[test]
+    fn seal_and_unlock_round_trip() {
+        let key = VaultKey::create("master", None, kdf()).unwrap();
+        let envelope = key.seal(b"{\"github\": {}}").unwrap();
+        assert_eq!(unlock(&envelope, "master").unwrap(), b"{\"github\": {}}");
+        // Survives being written out and read back.
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert!(!json.contains("github"));
+        let envelope: Envelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(unlock(&envelope, "master").unwrap(), b"{\"github\": {}}");
+    }
+
+    #[test]
+    fn wrong_password_is_refused() {
+        let key = VaultKey::create("master", None, kdf()).unwrap();
+        let envelope = key.seal(b"secret").unwrap();
+        let err = unlock(&envelope, "Master").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!key.verify("Master", None).unwrap());
+        assert!(key.verify("master", None).unwrap());
+    }
+
+    #[test]
+    fn tampered_payload_is_refused() {
+        let key = VaultKey::create("master", None, kdf()).unwrap();
+        let mut envelope = key.seal(b"secret").unwrap();
+        let mut ciphertext = B64.decode(&envelope.payload.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        envelope.payload.ciphertext = B64.encode(ciphertext);
+        let err = unlock(&envelope, "master").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rotate_replaces_the_data_key_only() {
+        let mut key = VaultKey::create("master", None, kdf()).unwrap();
+        let before = key.seal(b"entries").unwrap();
+        key.rotate();
+        let after = key.seal(b"entries").unwrap();
+        assert_eq!(before.kdf.salt, after.kdf.salt);
+        assert_eq!(unlock(&after, "master").unwrap(), b"entries");
+        // The old envelope's payload can't be read with the new data key.
+        let mut mixed = key.seal(b"entries").unwrap();
+        mixed.payload = before.payload;
+        assert!(unlock(&mixed, "master").is_err());
+    }
+
+    #[test]
+    fn change_password_keeps_the_data_and_drops_the_old_password() {
+        let mut key = VaultKey::create("old", None, kdf()).unwrap();
+        let before = key.seal(b"entries").unwrap();
+        key.change_password("new", None, None).unwrap();
+        let after = key.seal(b"entries").unwrap();
+        assert_ne!(before.kdf.salt, after.kdf.salt);
+        assert_eq!(unlock(&after, "new").unwrap(), b"entries");
+        assert!(unlock(&after, "old").is_err());
+        assert!(key.verify("new", None).unwrap());
+        assert!(!key.verify("old", None).unwrap());
+        // The previous file still opens with the previous password, so a
+        // crash before the new one is written loses nothing.
+        assert_eq!(unlock(&before, "old").unwrap(), b"entries");
+    }
+
+    #[test]
+    fn unknown_version_is_refused() {
+        let key = VaultKey::create("master", None, kdf()).unwrap();
+        let mut envelope = key.seal(b"entries").unwrap();
+        envelope.version = VAULT_VERSION + 1;
+        assert!(unlock(&envelope, "master").is_err());
+    }
+}
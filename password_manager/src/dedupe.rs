@@ -0,0 +1,351 @@
+use crate::store::{Entry, PasswordStore};
+use std::fmt;
+use std::io;
+
+/// Why two entries look like the same login.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    SameCredentials,
+    SameUserAndDomain,
+    SimilarName,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Reason::SameCredentials => "same username and password",
+            Reason::SameUserAndDomain => "same username and domain",
+            Reason::SimilarName => "similar service names",
+        })
+    }
+}
+
+/// A proposed merge: `loser` is folded into `winner`.
+pub struct Proposal {
+    pub winner: String,
+    pub loser: String,
+    pub reason: Reason,
+}
+
+/// Host of an entry's URL, without scheme, port, path or a leading `www.`.
+fn domain(entry: &Entry) -> Option<String> {
+    let url = entry.url.as_deref()?.trim().to_lowercase();
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+/// Lowercase name split into words on anything that isn't a letter or
+/// digit, so `Amazon_Services` and `amazon-services` compare equal.
+fn words(service: &str) -> Vec<String> {
+    service
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Names within a small edit distance of each other, or where one name's
+/// words are the start of the other's (`amazon` and `amazon_services`).
+fn similar_names(a: &str, b: &str) -> bool {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a.starts_with(&b) || b.starts_with(&a) {
+        return true;
+    }
+    let (a, b) = (a.concat(), b.concat());
+    let shorter = a.chars().count().min(b.chars().count());
+    strsim::levenshtein(&a, &b) <= (shorter / 4).max(1)
+}
+
+fn reason(a: &Entry, b: &Entry, a_name: &str, b_name: &str) -> Option<Reason> {
+    let same_user = a.username == b.username;
+    if same_user && !a.password.is_empty() && a.password.expose() == b.password.expose() {
+        Some(Reason::SameCredentials)
+    } else if same_user && domain(a).is_some() && domain(a) == domain(b) {
+        Some(Reason::SameUserAndDomain)
+    } else if similar_names(a_name, b_name) {
+        Some(Reason::SimilarName)
+    } else {
+        None
+    }
+}
+
+/// Every pair that looks like a duplicate, strongest reason first. The
+/// entry whose password changed most recently wins; it's the one most
+/// likely to still work. A derived entry always wins over a stored one,
+/// since it can't be merged away (see [`merge`]), and two derived entries
+/// are never proposed.
+pub fn find(store: &PasswordStore) -> Vec<Proposal> {
+    let entries: Vec<(&String, &Entry)> = store.iter().collect();
+    let mut proposals = Vec::new();
+    for (i, (a_name, a)) in entries.iter().enumerate() {
+        for (b_name, b) in &entries[i + 1..] {
+            let Some(reason) = reason(a, b, a_name, b_name) else {
+                continue;
+            };
+            let (winner, loser) = match (a.derived.is_some(), b.derived.is_some()) {
+                (true, true) => continue,
+                (true, false) => (a_name, b_name),
+                (false, true) => (b_name, a_name),
+                _ if b.password_changed > a.password_changed => (b_name, a_name),
+                _ => (a_name, b_name),
+            };
+            proposals.push(Proposal {
+                winner: winner.to_string(),
+                loser: loser.to_string(),
+                reason,
+            });
+        }
+    }
+    proposals.sort_by_key(|p| p.reason as u8);
+    proposals
+}
+
+/// Folds `loser` into `winner` and removes it. The winner keeps its own
+/// values, takes anything it lacks from the loser, and keeps the loser's
+/// password (and history, with its original dates) in its history. Nothing
+/// is deleted: the loser's attachments become the winner's.
+///
+/// A derived loser is refused: it has no stored password to keep, and its
+/// derivation would be lost.
+pub fn merge(store: &mut PasswordStore, winner: &str, loser: &str) -> io::Result<()> {
+    let missing =
+        |name: &str| io::Error::new(io::ErrorKind::NotFound, format!("no entry '{}'", name));
+    if !store.contains_key(winner) {
+        return Err(missing(winner));
+    }
+    let Some(old) = store.get(loser) else {
+        return Err(missing(loser));
+    };
+    if old.derived.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "'{}' is a derived entry; merging it would lose how its password is derived",
+                loser
+            ),
+        ));
+    }
+    let mut old = store.remove(loser).expect("checked above");
+    let entry = store.get_mut(winner).expect("checked above");
+    if entry.url.is_none() {
+        entry.url = old.url.take();
+    }
+    for tag in old.tags.drain(..) {
+        if !entry.tags.contains(&tag) {
+            entry.tags.push(tag);
+        }
+    }
+    // Attachments move over with their blobs, renamed if the winner
+    // already has one by that name, so each can still be extracted.
+    for mut attachment in old.attachments.drain(..) {
+        if entry.attachments.iter().any(|a| a.name == attachment.name) {
+            attachment.name = format!("{} (from {})", attachment.name, loser);
+        }
+        entry.attachments.push(attachment);
+    }
+    entry.rotate_every_days = entry.rotate_every_days.or(old.rotate_every_days);
+    entry.expires = entry.expires.or(old.expires);
+    if entry.policy.is_none() {
+        entry.policy = old.policy.take();
+    }
+    entry.favourite |= old.favourite;
+    entry.use_count = entry.use_count.saturating_add(old.use_count);
+    entry.last_used = entry.last_used.max(old.last_used);
+    if entry.notes.is_empty() {
+        entry.notes = std::mem::take(&mut old.notes);
+    }
+    for (name, value) in std::mem::take(&mut old.fields) {
+        entry.fields.entry(name).or_insert(value);
+    }
+    for mut past in old.history.drain(..) {
+        if past.password.expose() == entry.password.expose() {
+            continue;
+        }
+        past.merged_from.get_or_insert_with(|| loser.to_string());
+        entry.history.push(past);
+    }
+    // Retired now, so it stays last.
+    entry.history.sort_by_key(|past| past.retired);
+    entry.retire(std::mem::take(&mut old.password), Some(loser.to_string()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(json: &str) -> PasswordStore {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn url(url: &str) -> Entry {
+        serde_json::from_value(serde_json::json!({"username": "me", "url": url})).unwrap()
+    }
+
+    #[test]
+    fn domain_is_the_bare_host() {
+        for (input, host) in [
+            ("https://www.github.com/login?next=/", "github.com"),
+            ("http://user:pw@Example.COM:8080/path", "example.com"),
+            ("github.com", "github.com"),
+            ("  accounts.google.com#frag ", "accounts.google.com"),
+        ] {
+            assert_eq!(domain(&url(input)).as_deref(), Some(host), "{}", input);
+        }
+        assert_eq!(domain(&url("https://")), None);
+        assert_eq!(domain(&url("")), None);
+        let none: Entry = serde_json::from_str(r#"{"username": "me"}"#).unwrap();
+        assert_eq!(domain(&none), None);
+    }
+
+    #[test]
+    fn similar_names_by_prefix_or_edit_distance() {
+        assert!(similar_names("Amazon_Services", "amazon-services"));
+        assert!(similar_names("amazon", "amazon_services"));
+        assert!(similar_names("github", "githb"));
+        assert!(!similar_names("github", "gitlab"));
+        assert!(!similar_names("bank", "mail"));
+        // Prefixes count by whole words only.
+        assert!(!similar_names("amazon", "amazonia-travel"));
+        assert!(!similar_names("", "github"));
+        assert!(!similar_names("--", "--"));
+    }
+
+    #[test]
+    fn find_ranks_reasons_and_picks_the_newest_winner() {
+        let store = store(
+            r#"{
+                "github": {"username": "me", "password": "pw1", "password_changed": "2026-01-01T00:00:00Z"},
+                "github-old": {"username": "me", "password": "pw1", "password_changed": "2025-01-01T00:00:00Z"},
+                "bank": {"username": "anna", "password": "a", "url": "https://bank.example"},
+                "online-banking": {"username": "anna", "password": "b", "url": "https://www.bank.example/login",
+                                   "password_changed": "2026-05-01T00:00:00Z"},
+                "netflix": {"username": "x", "password": "c"},
+                "netflx": {"username": "y", "password": "d"},
+                "unrelated": {"username": "z", "password": "e"}
+            }"#,
+        );
+        let found: Vec<(String, String, Reason)> = find(&store)
+            .into_iter()
+            .map(|p| (p.winner, p.loser, p.reason))
+            .collect();
+        let expected = [
+            ("github", "github-old", Reason::SameCredentials),
+            ("online-banking", "bank", Reason::SameUserAndDomain),
+            ("netflix", "netflx", Reason::SimilarName),
+        ]
+        .map(|(w, l, r)| (w.to_string(), l.to_string(), r));
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn find_never_proposes_merging_a_derived_entry_away() {
+        let derivation = r#"{"site": "github.com", "counter": 1, "length": 16}"#;
+        let store = store(&format!(
+            r#"{{
+                "github": {{"username": "me", "derived": {derivation}, "password_changed": "2025-01-01T00:00:00Z"}},
+                "githb": {{"username": "me", "password": "pw", "password_changed": "2026-01-01T00:00:00Z"}},
+                "bank": {{"username": "me", "derived": {derivation}}},
+                "bank-2": {{"username": "me", "derived": {derivation}}}
+            }}"#
+        ));
+        let found = find(&store);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].winner.as_str(), found[0].loser.as_str()),
+            ("github", "githb")
+        );
+    }
+
+    #[test]
+    fn merge_keeps_the_losers_password_and_dated_history() {
+        let mut store = store(
+            r#"{
+                "winner": {
+                    "username": "me", "password": "new", "tags": ["a"],
+                    "history": [{"password": "w-old", "retired": "2025-06-01T00:00:00Z"}],
+                    "attachments": [{"id": "01", "name": "codes.txt", "size": 1, "key": "AAAA"}],
+                    "use_count": 2
+                },
+                "loser": {
+                    "username": "me", "password": "old", "url": "https://x.example",
+                    "tags": ["a", "b"], "notes": "keep me", "fields": {"pin": "1234"},
+                    "history": [
+                        {"password": "ancient", "retired": "2024-01-01T00:00:00Z"},
+                        {"password": "new", "retired": "2025-01-01T00:00:00Z"},
+                        {"password": "via", "retired": "2025-09-01T00:00:00Z", "merged_from": "third"}
+                    ],
+                    "attachments": [{"id": "02", "name": "codes.txt", "size": 1, "key": "AAAA"}],
+                    "use_count": 3, "favourite": true
+                }
+            }"#,
+        );
+        merge(&mut store, "winner", "loser").unwrap();
+        assert!(!store.contains_key("loser"));
+        let entry = &store["winner"];
+        assert_eq!(entry.password.expose(), "new");
+        assert_eq!(entry.url.as_deref(), Some("https://x.example"));
+        assert_eq!(entry.tags, ["a", "b"]);
+        assert_eq!(entry.notes.expose(), "keep me");
+        assert_eq!(entry.fields["pin"].expose(), "1234");
+        assert_eq!(entry.use_count, 5);
+        assert!(entry.favourite);
+        let names: Vec<&str> = entry.attachments.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["codes.txt", "codes.txt (from loser)"]);
+
+        let history: Vec<(&str, String, Option<&str>)> = entry
+            .history
+            .iter()
+            .map(|p| {
+                (
+                    p.password.expose(),
+                    p.retired.date_naive().to_string(),
+                    p.merged_from.as_deref(),
+                )
+            })
+            .collect();
+        let today = chrono::Utc::now().date_naive().to_string();
+        assert_eq!(
+            history,
+            [
+                ("ancient", "2024-01-01".to_string(), Some("loser")),
+                ("w-old", "2025-06-01".to_string(), None),
+                ("via", "2025-09-01".to_string(), Some("third")),
+                ("old", today, Some("loser")),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_refuses_derived_losers_and_missing_entries() {
+        let mut store = store(
+            r#"{
+                "stored": {"username": "me", "password": "pw"},
+                "derived": {"username": "me", "derived": {"site": "x.example", "counter": 1, "length": 16}}
+            }"#,
+        );
+        let err = merge(&mut store, "stored", "derived").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(store["derived"].derived.is_some());
+        assert_eq!(store.len(), 2);
+
+        let err = merge(&mut store, "nope", "stored").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let err = merge(&mut store, "stored", "nope").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.len(), 2);
+
+        merge(&mut store, "derived", "stored").unwrap();
+        let entry = &store["derived"];
+        assert!(entry.derived.is_some());
+        assert_eq!(entry.history[0].password.expose(), "pw");
+    }
+}
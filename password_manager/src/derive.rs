@@ -0,0 +1,232 @@
+//! Stateless passwords, compatible with LessPass v2.
+//!
+//! 1. `entropy = PBKDF2-HMAC-SHA256(master, site || login || hex(counter),
+//!    100_000 iterations, 32 bytes)`, read as a big-endian integer.
+//! 2. The alphabet is the enabled sets, in the order lowercase, uppercase,
+//!    digits, symbols. `length - sets` characters are taken by repeated
+//!    `divmod(entropy, alphabet.len())`, the remainder picking each one.
+//! 3. One character per enabled set is taken the same way from that set.
+//! 4. Those are inserted one at a time at position
+//!    `divmod(entropy, password.len())`.
+//!
+//! Nothing secret is stored: the same inputs give the same password on any
+//! machine.
+
+use crate::policy::{DIGITS, LOWER, UPPER};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+pub const ITERATIONS: u32 = 100_000;
+/// LessPass's symbol set, which is wider than [`crate::policy::SYMBOLS`].
+pub const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// Everything needed to recompute a derived password except the master
+/// secret and the login, which is the entry's username.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Derivation {
+    pub site: String,
+    pub counter: u32,
+    pub length: usize,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub lowercase: bool,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub uppercase: bool,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub digits: bool,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub symbols: bool,
+}
+
+impl Derivation {
+    fn sets(&self) -> Vec<&'static str> {
+        [
+            (self.lowercase, LOWER),
+            (self.uppercase, UPPER),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, set)| enabled.then_some(set))
+        .collect()
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        let sets = self.sets().len();
+        if sets == 0 {
+            return Err("enable at least one character set".to_string());
+        }
+        if !(5..=35).contains(&self.length) {
+            return Err("length must be between 5 and 35".to_string());
+        }
+        if self.length < sets {
+            return Err(format!("length must be at least {}", sets));
+        }
+        Ok(())
+    }
+
+    pub fn derive(&self, master: &str, login: &str) -> Result<Zeroizing<String>, String> {
+        self.check()?;
+        let salt = format!("{}{}{:x}", self.site, login, self.counter);
+        let mut entropy = Zeroizing::new([0u8; 32]);
+        pbkdf2::pbkdf2_hmac::<Sha256>(
+            master.as_bytes(),
+            salt.as_bytes(),
+            ITERATIONS,
+            entropy.as_mut(),
+        );
+        Ok(render(&entropy, self))
+    }
+}
+
+/// Steps 2-4 of the module docs, on the raw PBKDF2 output.
+fn render(entropy: &[u8; 32], derivation: &Derivation) -> Zeroizing<String> {
+    let sets = derivation.sets();
+    let alphabet: Vec<char> = sets.concat().chars().collect();
+    let mut quotient = BigUint::from_be_bytes(entropy);
+
+    let mut password: Zeroizing<Vec<char>> = Zeroizing::new(Vec::new());
+    while password.len() < derivation.length - sets.len() {
+        let r = quotient.divmod(alphabet.len() as u32);
+        password.push(alphabet[r as usize]);
+    }
+    let one_per_set: Vec<char> = sets
+        .iter()
+        .map(|set| {
+            let chars: Vec<char> = set.chars().collect();
+            chars[quotient.divmod(chars.len() as u32) as usize]
+        })
+        .collect();
+    for c in one_per_set {
+        let at = quotient.divmod(password.len() as u32) as usize;
+        password.insert(at, c);
+    }
+    Zeroizing::new(password.iter().collect())
+}
+
+/// Just enough arbitrary-precision arithmetic for the above: an unsigned
+/// integer as little-endian `u32` limbs, divided in place by small numbers.
+struct BigUint {
+    limbs: Zeroizing<Vec<u32>>,
+}
+
+impl BigUint {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        let limbs = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0u32, |acc, &b| (acc << 8) | u32::from(b)))
+            .collect();
+        Self {
+            limbs: Zeroizing::new(limbs),
+        }
+    }
+
+    /// Replaces `self` with `self / divisor` and returns the remainder.
+    fn divmod(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (remainder << 32) | u64::from(*limb);
+            *limb = (value / u64::from(divisor)) as u32;
+            remainder = value % u64::from(divisor);
+        }
+        remainder as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derivation(length: usize) -> Derivation {
+        Derivation {
+            site: "example.org".to_string(),
+            counter: 1,
+            length,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+        }
+    }
+
+    #[test]
+    fn lesspass_reference_vector() {
+        let password = derivation(16)
+            .derive("password", "contact@example.org")
+            .unwrap();
+        assert_eq!(password.as_str(), "WHLpUL)e00[iHR+w");
+    }
+
+    #[test]
+    fn stable_vectors() {
+        let cases = [
+            (
+                Derivation {
+                    counter: 2,
+                    ..derivation(16)
+                },
+                "contact@example.org",
+                "9#ymnCH6J@pGN4vN",
+            ),
+            (
+                Derivation {
+                    symbols: false,
+                    ..derivation(14)
+                },
+                "contact@example.org",
+                "y5Im77Ctww2695",
+            ),
+            (
+                Derivation {
+                    lowercase: false,
+                    uppercase: false,
+                    symbols: false,
+                    ..derivation(6)
+                },
+                "contact@example.org",
+                "874623",
+            ),
+            (
+                derivation(35),
+                "alice",
+                "UYg;?>^;d)V&[<1\\n+`1Gdz$yyENm/\\[FZk",
+            ),
+        ];
+        for (derivation, login, expected) in cases {
+            let password = derivation.derive("password", login).unwrap();
+            assert_eq!(password.as_str(), expected, "{:?} {}", derivation, login);
+        }
+    }
+
+    #[test]
+    fn every_enabled_set_is_used() {
+        let d = derivation(5);
+        let password = d.derive("password", "contact@example.org").unwrap();
+        assert_eq!(password.chars().count(), 5);
+        for set in d.sets() {
+            assert!(password.chars().any(|c| set.contains(c)), "{}", set);
+        }
+    }
+
+    #[test]
+    fn rejects_unusable_parameters() {
+        assert!(derivation(4).check().is_err());
+        assert!(derivation(36).check().is_err());
+        let none = Derivation {
+            lowercase: false,
+            uppercase: false,
+            digits: false,
+            symbols: false,
+            ..derivation(16)
+        };
+        assert!(none.check().is_err());
+    }
+}
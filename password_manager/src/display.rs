@@ -0,0 +1,192 @@
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Seconds a revealed secret stays on screen (or in the clipboard) before
+/// it is cleared.
+pub const CLEAR_AFTER_SECS: u64 = 20;
+
+/// Fixed-width so the mask doesn't give away the password length.
+pub const MASK: &str = "********";
+
+/// How a secret is shown.
+pub enum Reveal {
+    Masked,
+    /// 1-based character positions, as banks ask for them.
+    Chars(Vec<usize>),
+    Full,
+    Qr,
+    /// Send to the terminal's clipboard with OSC 52 instead of printing.
+    Copy,
+}
+
+/// Parses positions like `3-6`, `1,4,7` or `2,5-6` against a secret of
+/// `len` characters.
+pub fn parse_positions(spec: &str, len: usize) -> io::Result<Vec<usize>> {
+    let bad = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    let number = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|_| bad(format!("'{}' is not a position", s.trim())))
+    };
+
+    let mut positions = Vec::new();
+    for part in spec.split(',').filter(|p| !p.trim().is_empty()) {
+        let (start, end) = match part.split_once(['-', '–']) {
+            Some((a, b)) => (number(a)?, number(b)?),
+            None => {
+                let n = number(part)?;
+                (n, n)
+            }
+        };
+        if start == 0 || end > len || start > end {
+            return Err(bad(format!(
+                "positions must be between 1 and {} (got '{}')",
+                len,
+                part.trim()
+            )));
+        }
+        positions.extend(start..=end);
+    }
+    if positions.is_empty() {
+        return Err(bad("no positions given".to_string()));
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Ok(positions)
+}
+
+fn render_chars(secret: &str, positions: &[usize]) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    positions
+        .iter()
+        .map(|&p| format!("  #{:<3} {}", p, chars[p - 1]))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_qr(secret: &str) -> io::Result<String> {
+    let code = QrCode::new(secret.as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Clears the visible screen and, on terminals that support `CSI 3 J`, the
+/// scrollback too.
+pub fn clear_screen() -> io::Result<()> {
+    let mut out = io::stdout();
+    out.write_all(b"\x1b[2J\x1b[3J\x1b[H")?;
+    out.flush()
+}
+
+/// Sets the terminal's clipboard via OSC 52. Works over SSH, but only on
+/// terminals that allow it.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut out = io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", B64.encode(text))?;
+    out.flush()
+}
+
+pub fn clear_clipboard() -> io::Result<()> {
+    copy_to_clipboard("")
+}
+
+fn countdown(secs: u64, what: &str) -> io::Result<()> {
+    let mut out = io::stdout();
+    for left in (1..=secs).rev() {
+        write!(out, "\r{} in {:>3}s ", what, left)?;
+        out.flush()?;
+        thread::sleep(Duration::from_secs(1));
+    }
+    writeln!(out)
+}
+
+/// Shows `secret` as asked. Anything that puts the full secret on screen
+/// or in the clipboard is cleared again after `clear_after` seconds; `0`
+/// leaves it.
+pub fn show(secret: &str, reveal: &Reveal, clear_after: u64) -> io::Result<()> {
+    match reveal {
+        Reveal::Masked => {
+            println!("Password: {}", MASK);
+            return Ok(());
+        }
+        Reveal::Chars(positions) => {
+            println!("Password characters:\n{}", render_chars(secret, positions));
+        }
+        Reveal::Full => println!("Password: {}", secret),
+        Reveal::Qr => println!("{}", render_qr(secret)?),
+        Reveal::Copy => {
+            copy_to_clipboard(secret)?;
+            println!("Password sent to the terminal clipboard.");
+            if clear_after > 0 {
+                countdown(clear_after, "Clearing clipboard")?;
+                clear_clipboard()?;
+                println!("Clipboard cleared.");
+            }
+            return Ok(());
+        }
+    }
+    if clear_after > 0 {
+        countdown(clear_after, "Clearing screen")?;
+        clear_screen()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_positions_and_ranges() {
+        assert_eq!(parse_positions("3", 8).unwrap(), [3]);
+        assert_eq!(parse_positions("3-6", 8).unwrap(), [3, 4, 5, 6]);
+        assert_eq!(parse_positions("2,5-6", 8).unwrap(), [2, 5, 6]);
+        assert_eq!(parse_positions(" 1 , 8 ", 8).unwrap(), [1, 8]);
+        assert_eq!(parse_positions("4–5", 8).unwrap(), [4, 5]);
+        assert_eq!(
+            parse_positions("1-8", 8).unwrap(),
+            (1..=8).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn duplicates_and_overlaps_are_merged_and_sorted() {
+        assert_eq!(parse_positions("7,2,2", 8).unwrap(), [2, 7]);
+        assert_eq!(parse_positions("3-5,4-6,5", 8).unwrap(), [3, 4, 5, 6]);
+        assert_eq!(parse_positions("1,,2,", 8).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn out_of_range_positions_are_refused() {
+        for spec in ["0", "9", "8-9", "0-2", "5-3"] {
+            let err = parse_positions(spec, 8).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", spec);
+            assert!(err.to_string().contains("between 1 and 8"), "{}", spec);
+        }
+        assert!(parse_positions("1", 0).is_err());
+    }
+
+    #[test]
+    fn malformed_specs_are_refused() {
+        assert!(parse_positions("", 8).is_err());
+        assert!(parse_positions(" , ", 8).is_err());
+        assert!(parse_positions("two", 8).is_err());
+        assert!(parse_positions("1-", 8).is_err());
+        assert!(parse_positions("-3", 8).is_err());
+        assert!(parse_positions("1-2-3", 8).is_err());
+    }
+
+    #[test]
+    fn characters_are_counted_not_bytes() {
+        assert_eq!(render_chars("pässwörd", &[2, 6]), "  #2   ä\n  #6   ö");
+    }
+}
@@ -0,0 +1,458 @@
+use crate::secret::SecretString;
+use crate::store::Entry;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zeroize::Zeroizing;
+
+/// Marks the lines listing problems, so they can be dropped again before
+/// the next attempt is parsed.
+const PROBLEM_MARKER: &str = "#! ";
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// The part of an entry edited as a document.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(default)]
+    notes: SecretString,
+    #[serde(default)]
+    fields: BTreeMap<String, SecretString>,
+}
+
+pub enum Outcome {
+    Saved,
+    Unchanged,
+    Cancelled,
+}
+
+/// Opens `entry`'s notes and custom fields in `$VISUAL` or `$EDITOR` and
+/// applies what comes back. A document that doesn't parse or validate is
+/// reopened with the problems listed at the top, until it does or the
+/// user deletes everything in it to cancel. Leaving only comments clears
+/// the notes and fields instead.
+///
+/// The document lives in a 0600 file in a RAM-backed directory when there
+/// is one, and is overwritten with zeros before it is deleted.
+pub fn edit(service: &str, entry: &mut Entry, format: Format) -> io::Result<Outcome> {
+    let document = Document {
+        notes: SecretString::from(entry.notes.expose().to_string()),
+        fields: entry
+            .fields
+            .iter()
+            .map(|(name, value)| (name.clone(), SecretString::from(value.expose().to_string())))
+            .collect(),
+    };
+    let original = Zeroizing::new(format!(
+        "{}{}",
+        header(service),
+        to_text(&document, format)?.as_str()
+    ));
+    let file = TempFile::create(format.extension())?;
+    let mut text = Zeroizing::new(original.to_string());
+    loop {
+        file.write(&text)?;
+        run_editor(&file.path)?;
+        let edited = strip_problems(&Zeroizing::new(fs::read_to_string(&file.path)?));
+        if edited.trim().is_empty() {
+            return Ok(Outcome::Cancelled);
+        }
+        if *edited == *original {
+            return Ok(Outcome::Unchanged);
+        }
+        match parse(&edited, format) {
+            Ok(document) => {
+                entry.notes = document.notes;
+                entry.fields = document.fields;
+                return Ok(Outcome::Saved);
+            }
+            Err(problems) => text = annotate(&problems, &edited),
+        }
+    }
+}
+
+fn header(service: &str) -> String {
+    format!(
+        "# Notes and custom fields of '{}'. Save and quit to apply.\n\
+         # Remove them to clear them; delete everything, these lines too,\n\
+         # to cancel. Field values are strings.\n",
+        service
+    )
+}
+
+fn to_text(document: &Document, format: Format) -> io::Result<Zeroizing<String>> {
+    let text = match format {
+        Format::Toml => toml::to_string_pretty(document).map_err(io::Error::other)?,
+        Format::Yaml => serde_yaml::to_string(document).map_err(io::Error::other)?,
+    };
+    Ok(Zeroizing::new(text))
+}
+
+/// A problem and the 1-based line it is on, if known.
+type Problem = (Option<usize>, String);
+
+fn parse(text: &str, format: Format) -> Result<Document, Vec<Problem>> {
+    // Only comments left: everything was removed on purpose.
+    if text
+        .lines()
+        .all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+    {
+        return Ok(Document::default());
+    }
+    let document: Document = match format {
+        Format::Toml => toml::from_str(text).map_err(|e| {
+            let line = e
+                .span()
+                .map(|span| text[..span.start].matches('\n').count() + 1);
+            vec![(line, e.message().to_string())]
+        })?,
+        Format::Yaml => serde_yaml::from_str(text).map_err(|e| {
+            let line = e.location().map(|l| l.line());
+            let message = e.to_string();
+            // The location is reported separately, with the line adjusted.
+            let message = match message.rsplit_once(" at line ") {
+                Some((message, _)) => message.to_string(),
+                None => message,
+            };
+            vec![(line, message)]
+        })?,
+    };
+    let problems: Vec<Problem> = document
+        .fields
+        .iter()
+        .filter_map(|(name, value)| {
+            let problem = if name.trim().is_empty() {
+                "field names can't be empty".to_string()
+            } else if name.trim() != name || name.chars().any(char::is_control) {
+                format!(
+                    "field name '{}' has surrounding spaces or control characters",
+                    name
+                )
+            } else if value.is_empty() {
+                format!("field '{}' is empty; delete its line to remove it", name)
+            } else {
+                return None;
+            };
+            Some((line_of(text, name), problem))
+        })
+        .collect();
+    if problems.is_empty() {
+        Ok(document)
+    } else {
+        Err(problems)
+    }
+}
+
+/// The first line that starts with `name`, as a field's key would. Quoted
+/// keys can have spaces around the name.
+fn line_of(text: &str, name: &str) -> Option<usize> {
+    if name.trim().is_empty() {
+        return None;
+    }
+    text.lines()
+        .position(|line| {
+            let key = line.trim_start().trim_start_matches(['"', '\'']);
+            key.starts_with(name) || key.starts_with(name.trim())
+        })
+        .map(|i| i + 1)
+}
+
+/// `text` with `problems` listed above it. Lines are numbered as they will
+/// be in the reopened file.
+fn annotate(problems: &[Problem], text: &str) -> Zeroizing<String> {
+    let offset = problems.len() + 2;
+    let mut annotated = format!("{}Fix these and save again:\n", PROBLEM_MARKER);
+    for (line, problem) in problems {
+        match line {
+            Some(line) => annotated.push_str(&format!(
+                "{}  line {}: {}\n",
+                PROBLEM_MARKER,
+                line + offset,
+                problem
+            )),
+            None => annotated.push_str(&format!("{}  {}\n", PROBLEM_MARKER, problem)),
+        }
+    }
+    annotated.push_str(&format!("{}\n", PROBLEM_MARKER.trim_end()));
+    annotated.push_str(text);
+    Zeroizing::new(annotated)
+}
+
+/// `text` without the block [`annotate`] put at its top, including the
+/// bare marker closing the list. Marker-like lines further down, such as a
+/// `#!/bin/sh` in the notes, are content and stay.
+fn strip_problems(text: &str) -> Zeroizing<String> {
+    Zeroizing::new(
+        text.split_inclusive('\n')
+            .skip_while(|line| line.starts_with(PROBLEM_MARKER.trim_end()))
+            .collect(),
+    )
+}
+
+/// `$VISUAL`, `$EDITOR` or `vi`, run through the shell so values like
+/// `code --wait` work.
+fn run_editor(path: &Path) -> io::Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    #[cfg(unix)]
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    #[cfg(not(unix))]
+    let status = Command::new(&editor).arg(path).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}; nothing was changed",
+            editor, status
+        )));
+    }
+    Ok(())
+}
+
+/// The document on disk. Overwritten with zeros and removed when dropped,
+/// whichever way the edit ends.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create(extension: &str) -> io::Result<Self> {
+        let dir = ram_dir().unwrap_or_else(|| {
+            let dir = env::temp_dir();
+            eprintln!(
+                "Note: no RAM-backed directory found; editing in {}, which is wiped afterwards.",
+                dir.display()
+            );
+            dir
+        });
+        let path = dir.join(format!(
+            "password_manager-{:016x}.{}",
+            rand::random::<u64>(),
+            extension
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)?;
+        Ok(Self { path })
+    }
+
+    fn write(&self, text: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Ok(meta) = fs::metadata(&self.path) {
+            let zeros = vec![0u8; meta.len() as usize];
+            if let Ok(mut file) = OpenOptions::new().write(true).open(&self.path) {
+                let _ = file.write_all(&zeros).and_then(|_| file.sync_all());
+            }
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A tmpfs or ramfs directory, so the document never reaches a disk:
+/// `$XDG_RUNTIME_DIR`, else `/dev/shm`.
+#[cfg(target_os = "linux")]
+fn ram_dir() -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    const TMPFS_MAGIC: i64 = 0x0102_1994;
+    const RAMFS_MAGIC: i64 = 0x8584_58f6;
+    let candidates = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from("/dev/shm")]);
+    candidates.into_iter().find(|dir| {
+        let Ok(c_path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        let ok = unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } == 0;
+        #[allow(clippy::unnecessary_cast)]
+        let kind = stat.f_type as i64;
+        ok && (kind == TMPFS_MAGIC || kind == RAMFS_MAGIC)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ram_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(text: &str, format: Format) -> Vec<Problem> {
+        match parse(text, format) {
+            Ok(_) => panic!("parsed:\n{}", text),
+            Err(problems) => problems,
+        }
+    }
+
+    #[test]
+    fn parse_reads_both_formats() {
+        let toml = "# header\nnotes = \"\"\"\nline one\n\"\"\"\n[fields]\npin = \"1234\"\n";
+        let yaml = "# header\nnotes: |\n  line one\nfields:\n  pin: '1234'\n";
+        for (text, format) in [(toml, Format::Toml), (yaml, Format::Yaml)] {
+            let Ok(document) = parse(text, format) else {
+                panic!("didn't parse:\n{}", text);
+            };
+            assert_eq!(document.notes.expose(), "line one\n");
+            assert_eq!(document.fields["pin"].expose(), "1234");
+        }
+    }
+
+    #[test]
+    fn a_document_of_only_comments_clears_everything() {
+        for format in [Format::Toml, Format::Yaml] {
+            let Ok(document) = parse(&header("github"), format) else {
+                panic!("comments only didn't parse");
+            };
+            assert!(document.notes.is_empty());
+            assert!(document.fields.is_empty());
+        }
+    }
+
+    #[test]
+    fn parse_reports_problems_on_their_lines() {
+        assert_eq!(
+            problems("# a\n# b\nnotes = 1\n", Format::Toml),
+            [(
+                Some(3),
+                "invalid type: integer `1`, expected a string".to_string()
+            )]
+        );
+        let unknown = problems("notes = \"x\"\npassword = \"no\"\n", Format::Toml);
+        assert_eq!(unknown[0].0, Some(2));
+        assert!(
+            unknown[0].1.contains("unknown field `password`"),
+            "{:?}",
+            unknown
+        );
+        let yaml = problems("notes: x\nfields: [\n", Format::Yaml);
+        assert!(
+            yaml[0].0.is_some() && !yaml[0].1.contains(" at line "),
+            "{:?}",
+            yaml
+        );
+
+        let text = "notes = \"x\"\n[fields]\npin = \"\"\n\" code\" = \"1\"\n";
+        assert_eq!(
+            problems(text, Format::Toml),
+            [
+                (
+                    Some(4),
+                    "field name ' code' has surrounding spaces or control characters".to_string()
+                ),
+                (
+                    Some(3),
+                    "field 'pin' is empty; delete its line to remove it".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_of_finds_a_fields_key() {
+        let text = "notes = \"pin is in the safe\"\n[fields]\n  \"pin\" = \"1\"\n'puk' = \"2\"\n";
+        assert_eq!(line_of(text, "pin"), Some(3));
+        assert_eq!(line_of(text, " puk "), Some(4));
+        assert_eq!(line_of(text, "missing"), None);
+        assert_eq!(line_of(text, "  "), None);
+    }
+
+    #[test]
+    fn annotated_line_numbers_match_the_reopened_file() {
+        let text = "notes = \"x\"\n[fields]\na = \"\"\nb = \"\"\n";
+        let problems = problems(text, Format::Toml);
+        let annotated = annotate(&problems, text);
+        let lines: Vec<&str> = annotated.lines().collect();
+        for (field, at) in [("a", 3), ("b", 4)] {
+            let listed = lines
+                .iter()
+                .find(|line| line.contains(&format!("field '{}'", field)))
+                .unwrap();
+            let number: usize = listed
+                .split("line ")
+                .nth(1)
+                .and_then(|rest| rest.split(':').next())
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert_eq!(lines[number - 1], format!("{} = \"\"", field));
+            assert_eq!(number, at + problems.len() + 2);
+        }
+        // Reopening strips the list again, so numbers don't drift.
+        assert_eq!(strip_problems(&annotated).as_str(), text);
+        let again = annotate(&problems, &strip_problems(&annotated));
+        assert_eq!(again, annotated);
+    }
+
+    #[test]
+    fn only_the_annotation_block_is_stripped() {
+        let text = "notes = \"\"\"\n#!/bin/sh\necho hi\n#! not a problem\n\"\"\"\n";
+        assert_eq!(strip_problems(text).as_str(), text);
+        let document = parse(text, Format::Toml).ok().unwrap();
+        assert_eq!(
+            document.notes.expose(),
+            "#!/bin/sh\necho hi\n#! not a problem\n"
+        );
+        // An unedited document is still unchanged.
+        let original = format!(
+            "{}{}",
+            header("deploy"),
+            to_text(&document, Format::Toml).unwrap().as_str()
+        );
+        assert_eq!(strip_problems(&original).as_str(), original);
+
+        let annotated = annotate(&[(None, "something".to_string())], text);
+        assert_eq!(strip_problems(&annotated).as_str(), text);
+    }
+
+    #[test]
+    fn problems_without_a_line_are_listed_plainly() {
+        let annotated = annotate(&[(None, "something".to_string())], "x\n");
+        assert_eq!(
+            annotated.as_str(),
+            "#! Fix these and save again:\n#!   something\n#!\nx\n"
+        );
+    }
+}
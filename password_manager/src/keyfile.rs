@@ -0,0 +1,183 @@
+use crate::crypto;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const KEY_FILE_LEN: usize = 64;
+const CHECK_CONTEXT: &[u8] = b"password_manager/keyfile-check";
+
+/// Second factor for unlocking: any file, hashed with SHA-256. Only the
+/// digest is kept in memory.
+pub struct KeyFile {
+    digest: Zeroizing<[u8; 32]>,
+}
+
+impl KeyFile {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let contents = Zeroizing::new(fs::read(path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot read key file {}: {}", path.display(), e),
+            )
+        })?);
+        if contents.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("key file {} is empty", path.display()),
+            ));
+        }
+        let mut digest = Zeroizing::new([0u8; 32]);
+        digest.copy_from_slice(&Sha256::digest(contents.as_slice()));
+        Ok(Self { digest })
+    }
+
+    /// Writes a new random key file readable only by the owner. Refuses to
+    /// overwrite an existing file, since that would lock out any vault that
+    /// uses it.
+    pub fn generate(path: &Path) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        file.write_all(crypto::random_bytes(KEY_FILE_LEN).as_slice())?;
+        file.sync_all()
+    }
+
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.digest
+    }
+
+    /// Short fingerprint stored in the vault so a wrong key file can be
+    /// told apart from a wrong password. 32 bits of a hash of a 256-bit
+    /// random digest reveal nothing useful about the file.
+    pub fn check(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(CHECK_CONTEXT);
+        hasher.update(self.digest.as_slice());
+        hasher.finalize()[..4]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{KdfParams, VaultKey};
+
+    fn kdf() -> KdfParams {
+        KdfParams::new(8, 1, 1)
+    }
+
+    #[test]
+    fn generated_files_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.key");
+        KeyFile::generate(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), KEY_FILE_LEN as u64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let first = KeyFile::read(&path).unwrap();
+        let second = KeyFile::read(&path).unwrap();
+        assert_eq!(first.digest(), second.digest());
+        assert_eq!(first.check(), second.check());
+        assert_eq!(first.check().len(), 8);
+
+        let other = dir.path().join("other.key");
+        KeyFile::generate(&other).unwrap();
+        assert_ne!(KeyFile::read(&other).unwrap().check(), first.check());
+    }
+
+    #[test]
+    fn generate_never_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.key");
+        fs::write(&path, b"existing").unwrap();
+        let err = KeyFile::generate(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"existing");
+    }
+
+    #[test]
+    fn empty_and_missing_files_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty.key");
+        fs::write(&empty, b"").unwrap();
+        assert_eq!(
+            KeyFile::read(&empty).err().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let missing = dir.path().join("missing.key");
+        assert_eq!(
+            KeyFile::read(&missing).err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn a_vault_with_a_key_file_needs_that_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (right, wrong) = (dir.path().join("right.key"), dir.path().join("wrong.key"));
+        KeyFile::generate(&right).unwrap();
+        KeyFile::generate(&wrong).unwrap();
+        let (right, wrong) = (
+            KeyFile::read(&right).unwrap(),
+            KeyFile::read(&wrong).unwrap(),
+        );
+
+        let key = VaultKey::create("master", Some(&right), kdf()).unwrap();
+        let envelope = key.seal(b"entries").unwrap();
+        let (_, plain) = VaultKey::unlock(&envelope, "master", Some(&right)).unwrap();
+        assert_eq!(plain.expose(), b"entries");
+
+        let missing = VaultKey::unlock(&envelope, "master", None).err().unwrap();
+        assert_eq!(missing.kind(), io::ErrorKind::PermissionDenied);
+        assert!(missing.to_string().contains("requires a key file"));
+        let mismatched = VaultKey::unlock(&envelope, "master", Some(&wrong))
+            .err()
+            .unwrap();
+        assert_eq!(mismatched.kind(), io::ErrorKind::PermissionDenied);
+        assert!(mismatched.to_string().contains("does not match"));
+        // The right file doesn't make up for a wrong password.
+        let password = VaultKey::unlock(&envelope, "Master", Some(&right))
+            .err()
+            .unwrap();
+        assert!(password.to_string().contains("wrong master password"));
+    }
+
+    #[test]
+    fn the_key_file_is_mixed_into_the_key_not_just_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let (right, wrong) = (dir.path().join("right.key"), dir.path().join("wrong.key"));
+        KeyFile::generate(&right).unwrap();
+        KeyFile::generate(&wrong).unwrap();
+        let (right, wrong) = (
+            KeyFile::read(&right).unwrap(),
+            KeyFile::read(&wrong).unwrap(),
+        );
+
+        let key = VaultKey::create("master", Some(&right), kdf()).unwrap();
+        let mut envelope = key.seal(b"entries").unwrap();
+        // An attacker who rewrites the stored check still needs the file.
+        envelope.key_file = Some(wrong.check());
+        let err = VaultKey::unlock(&envelope, "master", Some(&wrong))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("wrong master password"));
+        assert!(key.verify("master", Some(&right)).unwrap());
+        assert!(!key.verify("master", Some(&wrong)).unwrap());
+        assert!(!key.verify("master", None).unwrap());
+    }
+}
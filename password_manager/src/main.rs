@@ -1,46 +1,435 @@
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
-use rpassword::read_password;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, Write};
-use std::path::Path;
+mod agestore;
+mod attachments;
+mod audit;
+mod bundle;
+mod cli;
+mod commands;
+mod config;
+mod crypto;
+mod dedupe;
+mod derive;
+mod display;
+mod editor;
+mod keyfile;
+mod policy;
+mod prompt;
+mod rotation;
+mod search;
+mod secret;
+mod server;
+mod store;
+mod tokens;
+mod tui;
+mod usage;
+mod verify;
+mod watch;
 
-const DATA_FILE: &str = "passwords.json";
+use clap::Parser;
+use cli::{Cli, Command};
+use commands::{api, attach, entries, share, vault};
+use display::Reveal;
+use keyfile::KeyFile;
+use policy::Policy;
+use prompt::Prompter;
+use secret::SecretString;
+use std::io;
+use store::{load_store, Entry, PasswordStore, Vault};
+use watch::Watch;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Entry {
-    username: String,
-    password: String,
+fn prompt_master_password(prompt: &mut dyn Prompter) -> io::Result<SecretString> {
+    prompt.password("Master password: ")
 }
 
-type PasswordStore = HashMap<String, Entry>;
+/// Asks twice and refuses empty or mismatched input.
+fn prompt_new_secret(prompt: &mut dyn Prompter, what: &str) -> io::Result<SecretString> {
+    let question = what[..1].to_uppercase() + &what[1..] + ": ";
+    let password = prompt.password(&question)?;
+    if password.expose().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} cannot be empty", what),
+        ));
+    }
+    let repeated = prompt.password(&format!("Repeat {}: ", what))?;
+    if repeated.expose() != password.expose() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "passwords do not match",
+        ));
+    }
+    Ok(password)
+}
+
+fn find_entry<'a>(entries: &'a mut PasswordStore, service: &str) -> io::Result<&'a mut Entry> {
+    entries.get_mut(service).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "no entry named '{}'; try `password_manager search`",
+                service
+            ),
+        )
+    })
+}
+
+fn find_preset(name: &str) -> io::Result<Policy> {
+    policy::preset(name).ok_or_else(|| {
+        let names: Vec<&str> = policy::PRESETS.iter().map(|(name, _)| *name).collect();
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown preset '{}'; choose one of {}",
+                name,
+                names.join(", ")
+            ),
+        )
+    })
+}
+
+fn policy_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Preset picker for entries that don't have a policy yet.
+fn choose_policy(prompt: &mut dyn Prompter) -> io::Result<Option<Policy>> {
+    let mut items = vec!["No policy".to_string()];
+    items.extend(
+        policy::PRESETS
+            .iter()
+            .map(|(name, description)| format!("{} - {}", name, description)),
+    );
+    let items: Vec<&str> = items.iter().map(String::as_str).collect();
+    let selected = prompt.select("Password policy", &items, 0)?;
+    Ok(selected
+        .checked_sub(1)
+        .and_then(|i| policy::preset(policy::PRESETS[i].0)))
+}
+
+/// Generates a password, or asks for one until it satisfies `policy`.
+/// `None` means the user gave up by entering nothing.
+fn prompt_entry_password(
+    prompt: &mut dyn Prompter,
+    policy: Option<&Policy>,
+) -> io::Result<Option<SecretString>> {
+    if prompt.confirm("Generate a password?", true)? {
+        match policy::or_default(policy).generate(None) {
+            Ok(password) => {
+                println!("Generated a password; use View Password to reveal or copy it.");
+                return Ok(Some(SecretString::from(password)));
+            }
+            Err(e) => println!("Cannot generate a password: {}", e),
+        }
+    }
 
-fn load_store() -> io::Result<PasswordStore> {
-    if !Path::new(DATA_FILE).exists() {
-        return Ok(HashMap::new());
+    loop {
+        let password = prompt.password("Password (hidden, empty to cancel): ")?;
+        if password.expose().trim().is_empty() {
+            return Ok(None);
+        }
+        let problems = policy
+            .map(|p| p.violations(password.expose()))
+            .unwrap_or_default();
+        if problems.is_empty() {
+            return Ok(Some(password));
+        }
+        println!("Password rejected by this service's policy. It:");
+        for problem in problems {
+            println!("  - {}", problem);
+        }
+        if !prompt.interactive() {
+            return Err(policy_error("password rejected by policy".to_string()));
+        }
     }
-    let file = File::open(DATA_FILE)?;
-    let reader = BufReader::new(file);
-    let store = serde_json::from_reader(reader).unwrap_or_else(|_| HashMap::new());
-    Ok(store)
 }
 
-fn save_store(store: &PasswordStore) -> io::Result<()> {
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(DATA_FILE)?;
-    serde_json::to_writer_pretty(file, store)?;
+fn derive_password(
+    prompt: &mut dyn Prompter,
+    derivation: &derive::Derivation,
+    login: &str,
+) -> io::Result<SecretString> {
+    let master = prompt.password("Derivation master password: ")?;
+    let password = derivation
+        .derive(master.expose(), login)
+        .map_err(policy_error)?;
+    Ok(SecretString::from(password.to_string()))
+}
+
+/// The password to reveal: the stored one, or recomputed for a derived
+/// entry, which asks for the derivation master password.
+fn entry_password(prompt: &mut dyn Prompter, entry: &Entry) -> io::Result<SecretString> {
+    match &entry.derived {
+        Some(derivation) => derive_password(prompt, derivation, &entry.username),
+        None => Ok(SecretString::from(entry.password.expose().to_string())),
+    }
+}
+
+fn password_len(entry: &Entry) -> usize {
+    match &entry.derived {
+        Some(derivation) => derivation.length,
+        None => entry.password.expose().chars().count(),
+    }
+}
+
+/// Runs the editor round-trip and says how it went. Returns whether the
+/// entry changed.
+fn edit_notes(service: &str, entry: &mut Entry, format: editor::Format) -> io::Result<bool> {
+    match editor::edit(service, entry, format)? {
+        editor::Outcome::Saved => {
+            println!("Notes and fields of '{}' saved.", service);
+            Ok(true)
+        }
+        editor::Outcome::Unchanged => {
+            println!("No changes.");
+            Ok(false)
+        }
+        editor::Outcome::Cancelled => {
+            println!("Edit cancelled.");
+            Ok(false)
+        }
+    }
+}
+
+/// Type-to-filter picker over every entry. Returns the chosen service name.
+fn pick_service(
+    prompt: &mut dyn Prompter,
+    store: &PasswordStore,
+    question: &str,
+    alphabetical: bool,
+) -> io::Result<String> {
+    let services = usage::ordered(store, alphabetical);
+    let lines: Vec<String> = services
+        .iter()
+        .map(|service| search::haystack(service, &store[*service]))
+        .collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+
+    let selected = prompt.fuzzy_select(question, &lines)?;
+    Ok(services[selected].clone())
+}
+
+fn run_search(store: &PasswordStore, query: &str) {
+    let matches = search::search(store, query);
+    if matches.is_empty() {
+        println!("No entries match '{}'.", query);
+        return;
+    }
+    for m in matches {
+        println!("{}", search::highlight(&m.line, &m.indices));
+    }
+}
+
+/// Makes the vault named on the command line, or the default one, the
+/// vault every later load and save uses.
+fn select_vault(
+    config: &config::Config,
+    name: Option<&str>,
+    prompt: &mut dyn Prompter,
+) -> io::Result<()> {
+    let location = config::resolve(config, name)?;
+    if name.is_none() {
+        migrate_legacy(&location, prompt)?;
+    }
+    config::select(location);
+    Ok(())
+}
+
+/// Moves a vault left in the current directory by an old version into
+/// `location`. Inside a version-controlled directory that only happens
+/// when a person agrees to it; otherwise the file is left alone.
+fn migrate_legacy(location: &config::Location, prompt: &mut dyn Prompter) -> io::Result<()> {
+    let files = config::legacy_files(location);
+    if files.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = files.iter().map(|p| p.display().to_string()).collect();
+    let names = names.join(", ");
+    let dir = location.path.parent().unwrap_or(&location.path);
+    if config::under_version_control(&std::env::current_dir()?) {
+        let question = format!(
+            "{} in this directory is under version control. Move it to {}?",
+            names,
+            dir.display()
+        );
+        if !(prompt.interactive() && prompt.confirm(&question, false)?) {
+            eprintln!(
+                "Note: not moving {} (under version control); using {}.",
+                names,
+                location.path.display()
+            );
+            return Ok(());
+        }
+    }
+    config::migrate_legacy(location)?;
+    eprintln!(
+        "Moved {} from the current directory to {} (one-time migration).",
+        names,
+        dir.display()
+    );
     Ok(())
 }
 
+/// Reads `--key-file`, if given.
+fn read_key_file(path: Option<&std::path::Path>) -> io::Result<Option<KeyFile>> {
+    path.map(KeyFile::read).transpose()
+}
+
+/// Unlocks the selected vault, asking for the master password if it is
+/// encrypted. Wrong passwords and key files are written to the audit log.
+fn unlock_vault(key_file: Option<&KeyFile>, prompt: &mut dyn Prompter) -> io::Result<Vault> {
+    match load_store(key_file, || prompt_master_password(prompt)) {
+        Ok(vault) => Ok(vault),
+        Err(e) => {
+            if e.kind() == io::ErrorKind::PermissionDenied {
+                audit::record(audit::Event::UnlockFailed, None)?;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Reads `--key-file` and unlocks the vault with it.
+fn open_vault(
+    key_file: Option<&std::path::Path>,
+    prompt: &mut dyn Prompter,
+) -> io::Result<(Vault, Option<KeyFile>)> {
+    let key_file = read_key_file(key_file)?;
+    let vault = unlock_vault(key_file.as_ref(), prompt)?;
+    if key_file.is_some() && !vault.is_encrypted() {
+        eprintln!("Note: this vault is not encrypted; --key-file ignored.");
+    }
+    Ok((vault, key_file))
+}
+
 fn main() -> io::Result<()> {
-    let theme = ColorfulTheme::default();
-    let mut store = load_store()?;
+    let cli = Cli::parse();
+    let mut prompter: Box<dyn Prompter> = match &cli.answers {
+        Some(path) => Box::new(prompt::Scripted::new(io::BufReader::new(
+            std::fs::File::open(path)?,
+        ))),
+        None if cli.batch => Box::new(prompt::Scripted::new(io::stdin().lock())),
+        None => Box::new(prompt::Terminal::new()),
+    };
+    let prompt = prompter.as_mut();
+
+    let config = config::load()?;
+    // Listing the vaults is the one command that doesn't pick one.
+    if !matches!(cli.command, Some(Command::Vaults)) {
+        select_vault(&config, cli.vault.as_deref(), prompt)?;
+    }
+
+    let mut cx = commands::Context {
+        key_file: cli.key_file.as_deref(),
+        prompt,
+    };
+    match cli.command {
+        Some(Command::Vaults) => vault::list_vaults(&config),
+        Some(Command::Search { query }) => entries::search(&mut cx, &query),
+        Some(Command::Show {
+            service,
+            chars,
+            full,
+            qr,
+            copy,
+            clear_after,
+        }) => entries::show(&mut cx, service, chars, full, qr, copy, clear_after),
+        Some(Command::Attach { service, file }) => attach::attach(&mut cx, service, file),
+        Some(Command::Attachments { service }) => attach::list(&mut cx, service),
+        Some(Command::Extract { service, name, out }) => {
+            attach::extract(&mut cx, service, name, out)
+        }
+        Some(Command::Detach { service, name }) => attach::detach(&mut cx, service, name),
+        Some(Command::Policy(args)) => entries::policy(&mut cx, args),
+        Some(Command::Derive(args)) => entries::derive(&mut cx, args),
+        Some(Command::Generate {
+            service,
+            preset,
+            length,
+            save,
+        }) => entries::generate(&mut cx, service, preset, length, save),
+        Some(Command::Due { within, json }) => entries::due(&mut cx, within, json),
+        Some(Command::Rotation {
+            service,
+            every,
+            expires,
+            clear,
+        }) => entries::rotation(&mut cx, service, every, expires, clear),
+        Some(Command::Export(args)) => share::export(&mut cx, args),
+        Some(Command::ImportBundle {
+            file,
+            identity,
+            replace,
+        }) => share::import_bundle(&mut cx, file, identity, replace),
+        Some(Command::Edit { service, format }) => entries::edit(&mut cx, service, format),
+        Some(Command::Favourite { service, remove }) => {
+            entries::favourite(&mut cx, service, remove)
+        }
+        Some(Command::ExportAge {
+            dir,
+            service,
+            folder,
+            recipient,
+        }) => share::export_age(&mut cx, dir, service, folder, recipient),
+        Some(Command::ImportAge {
+            dir,
+            identity,
+            replace,
+        }) => share::import_age(&mut cx, dir, identity, replace),
+        Some(Command::Dedupe) => entries::dedupe(&mut cx),
+        Some(Command::BundleKeygen { path }) => share::bundle_keygen(path),
+        Some(Command::Encrypt { unlock_ms }) => vault::encrypt(&mut cx, unlock_ms),
+        Some(Command::ChangePassword { unlock_ms }) => vault::change_password(&mut cx, unlock_ms),
+        Some(Command::RotateKey) => vault::rotate_key(&mut cx),
+        Some(Command::KeyfileNew { path }) => vault::keyfile_new(path),
+        Some(Command::KeyfileRequire { path }) => vault::keyfile_require(&mut cx, path),
+        Some(Command::KeyfileRemove) => vault::keyfile_remove(&mut cx),
+        Some(Command::Serve {
+            listen,
+            #[cfg(unix)]
+            socket,
+        }) => api::serve(
+            &mut cx,
+            listen,
+            #[cfg(unix)]
+            socket,
+        ),
+        Some(Command::TokenAdd {
+            name,
+            write,
+            folder,
+        }) => api::token_add(name, write, folder),
+        Some(Command::TokenList) => api::token_list(),
+        Some(Command::TokenRevoke { name }) => api::token_revoke(name),
+        Some(Command::VerifyLog) => vault::verify_log(),
+        Some(Command::Verify) => vault::verify(&mut cx),
+        None => {
+            let (mut vault, _) = cx.open_vault()?;
+            if !cli.menu && cx.prompt.interactive() && tui::supported() {
+                tui::run(&mut vault, cli.alphabetical)
+            } else {
+                run_menu(vault, cx.prompt, cli.alphabetical)
+            }
+        }
+    }
+}
+
+/// Tells the user what another process changed in the vault.
+fn report_changes(changes: &[watch::Change]) {
+    for change in changes {
+        println!("Note: {}", change);
+    }
+}
+
+fn run_menu(mut vault: Vault, prompt: &mut dyn Prompter, alphabetical: bool) -> io::Result<()> {
+    if let Some(banner) = rotation::banner(&vault.entries, chrono::Local::now().date_naive()) {
+        println!("\n{}", banner);
+    }
+    let mut watch = Watch::start(&vault)?;
 
     loop {
+        match watch.poll(&mut vault) {
+            Ok(changes) => report_changes(&changes),
+            Err(e) => println!("Could not reload the vault: {}", e),
+        }
         println!("\n--- Password Manager ---\n");
 
         let options = &[
@@ -48,119 +437,217 @@ fn main() -> io::Result<()> {
             "List Services",
             "View Password",
             "Delete Entry",
+            "Toggle Favourite",
+            "Edit Notes",
             "Exit",
         ];
 
-        let choice = Select::with_theme(&theme)
-            .with_prompt("Choose an action")
-            .items(options)
-            .default(0)
-            .interact()
-            .unwrap();
+        let choice = prompt.select("Choose an action", options, 0)?;
 
         match choice {
             0 => {
                 // Add Entry
-                let service: String = Input::with_theme(&theme)
-                    .with_prompt("Enter service name")
-                    .interact_text()
-                    .unwrap();
+                let service = prompt.text("Enter service name", false)?;
 
                 if service.trim().is_empty() {
                     println!("Service name cannot be empty.");
                     continue;
                 }
 
-                let username: String = Input::with_theme(&theme)
-                    .with_prompt("Enter username")
-                    .interact_text()
-                    .unwrap();
+                let username = prompt.text("Enter username", false)?;
 
-                println!("Enter password (input will be hidden):");
-                let password = read_password().unwrap_or_else(|_| "".to_string());
+                let policy = match vault.entries.get(&service) {
+                    Some(existing) => existing.policy.clone(),
+                    None => choose_policy(prompt)?,
+                };
+                if let Some(policy) = &policy {
+                    println!("Policy: {}", policy.summary());
+                }
 
-                if password.trim().is_empty() {
+                let Some(password) = prompt_entry_password(prompt, policy.as_ref())? else {
                     println!("Password cannot be empty.");
                     continue;
-                }
+                };
+
+                let url = prompt.text("URL (optional)", true)?;
 
-                store.insert(service.clone(), Entry { username, password });
+                let tags = prompt.text("Tags, comma separated (optional)", true)?;
 
-                save_store(&store)?;
+                let rotate_every = prompt.input("Rotate every N days (optional)", true, &|s| {
+                    if s.trim().is_empty() || s.trim().parse::<u32>().is_ok_and(|d| d > 0) {
+                        Ok(())
+                    } else {
+                        Err("enter a number of days, or leave empty".to_string())
+                    }
+                })?;
+
+                // Updating an existing entry keeps its attachments and
+                // settings; the new password restarts its rotation clock.
+                let entry = match vault.entries.get_mut(&service) {
+                    Some(entry) => {
+                        entry.username = username;
+                        entry.set_password(password);
+                        entry
+                    }
+                    None => vault
+                        .entries
+                        .entry(service.clone())
+                        .or_insert(Entry::new(username, password)),
+                };
+                entry.url = Some(url.trim().to_string()).filter(|u| !u.is_empty());
+                entry.tags = tags
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                if let Ok(days) = rotate_every.trim().parse() {
+                    entry.rotate_every_days = Some(days);
+                }
+                entry.policy = policy;
+
+                audit::record(audit::Event::Add, Some(&service))?;
+                report_changes(&watch.save(&mut vault)?);
                 println!("Entry for '{}' saved.", service);
             }
             1 => {
                 // List Services
-                if store.is_empty() {
+                if vault.entries.is_empty() {
                     println!("No entries stored.");
                 } else {
                     println!("Stored services:");
-                    for service in store.keys() {
-                        println!("- {}", service);
+                    for service in usage::ordered(&vault.entries, alphabetical) {
+                        if vault.entries[service].favourite {
+                            println!("- {} ★", service);
+                        } else {
+                            println!("- {}", service);
+                        }
                     }
                 }
             }
             2 => {
                 // View Password
-                if store.is_empty() {
+                if vault.entries.is_empty() {
                     println!("No entries to view.");
                     continue;
                 }
 
-                let services: Vec<String> = store.keys().cloned().collect();
-                let selected = Select::with_theme(&theme)
-                    .with_prompt("Select service")
-                    .items(&services)
-                    .default(0)
-                    .interact()
-                    .unwrap();
-
-                let service = &services[selected];
-
-                if Confirm::with_theme(&theme)
-                    .with_prompt(format!("Show password for '{}'? ", service))
-                    .default(false)
-                    .interact()
-                    .unwrap()
-                {
-                    let entry = store.get(service).unwrap();
-                    println!("Username: {}", entry.username);
-                    println!("Password: {}", entry.password);
-                } else {
-                    println!("Aborted.");
+                let service = pick_service(prompt, &vault.entries, "Search service", alphabetical)?;
+                usage::record(vault.entries.get_mut(&service).unwrap());
+                report_changes(&watch.save(&mut vault)?);
+                let Some(entry) = vault.entries.get(&service) else {
+                    continue;
+                };
+                println!("Username: {}", entry.username);
+                println!("Password: {}", display::MASK);
+
+                let modes = &[
+                    "Keep masked",
+                    "Reveal some characters",
+                    "Show in full",
+                    "Show as QR code",
+                    "Copy to terminal clipboard",
+                ];
+                let mode = prompt.select("Reveal password?", modes, 0)?;
+
+                let reveal = match mode {
+                    0 => Reveal::Masked,
+                    1 => {
+                        let spec = prompt.text("Which characters? (e.g. 3-6 or 1,4,7)", false)?;
+                        match display::parse_positions(&spec, password_len(entry)) {
+                            Ok(positions) => Reveal::Chars(positions),
+                            Err(e) => {
+                                println!("{}", e);
+                                Reveal::Masked
+                            }
+                        }
+                    }
+                    2 => Reveal::Full,
+                    3 => Reveal::Qr,
+                    4 => Reveal::Copy,
+                    _ => unreachable!(),
+                };
+                // Keeping it masked never asks for a derived entry's secret.
+                if !matches!(reveal, Reveal::Masked) {
+                    match entry_password(prompt, entry) {
+                        Ok(password) => {
+                            audit::record(audit::Event::View, Some(&service))?;
+                            // Nobody is watching a batch run's screen.
+                            let clear_after = if prompt.interactive() {
+                                display::CLEAR_AFTER_SECS
+                            } else {
+                                0
+                            };
+                            display::show(password.expose(), &reveal, clear_after)?;
+                            // Changed elsewhere while it was on screen.
+                            if let Ok(changes) = watch.poll(&mut vault) {
+                                report_changes(&changes);
+                                if changes.iter().any(|c| c.service() == service) {
+                                    println!("The password shown above may be out of date.");
+                                }
+                            }
+                        }
+                        Err(e) => println!("{}", e),
+                    }
                 }
             }
             3 => {
                 // Delete Entry
-                if store.is_empty() {
+                if vault.entries.is_empty() {
                     println!("No entries to delete.");
                     continue;
                 }
 
-                let services: Vec<String> = store.keys().cloned().collect();
-                let selected = Select::with_theme(&theme)
-                    .with_prompt("Select service to delete")
-                    .items(&services)
-                    .default(0)
-                    .interact()
-                    .unwrap();
-
-                let service = &services[selected];
-
-                if Confirm::with_theme(&theme)
-                    .with_prompt(format!("Delete entry for '{}'? ", service))
-                    .default(false)
-                    .interact()
-                    .unwrap()
-                {
-                    store.remove(service);
-                    save_store(&store)?;
+                let service = pick_service(
+                    prompt,
+                    &vault.entries,
+                    "Search service to delete",
+                    alphabetical,
+                )?;
+
+                if prompt.confirm(&format!("Delete entry for '{}'? ", service), false)? {
+                    audit::record(audit::Event::Delete, Some(&service))?;
+                    vault.delete(&service, |vault| {
+                        report_changes(&watch.save(vault)?);
+                        Ok(())
+                    })?;
                     println!("Entry deleted.");
                 } else {
                     println!("Deletion cancelled.");
                 }
             }
             4 => {
+                // Toggle Favourite
+                if vault.entries.is_empty() {
+                    println!("No entries stored.");
+                    continue;
+                }
+
+                let service = pick_service(prompt, &vault.entries, "Search service", alphabetical)?;
+                let entry = vault.entries.get_mut(&service).unwrap();
+                entry.favourite = !entry.favourite;
+                let favourite = entry.favourite;
+                report_changes(&watch.save(&mut vault)?);
+                if favourite {
+                    println!("'{}' marked as a favourite.", service);
+                } else {
+                    println!("'{}' is no longer a favourite.", service);
+                }
+            }
+            5 => {
+                // Edit Notes
+                if vault.entries.is_empty() {
+                    println!("No entries stored.");
+                    continue;
+                }
+
+                let service = pick_service(prompt, &vault.entries, "Search service", alphabetical)?;
+                let entry = vault.entries.get_mut(&service).unwrap();
+                if edit_notes(&service, entry, editor::Format::Toml)? {
+                    audit::record(audit::Event::Edit, Some(&service))?;
+                    report_changes(&watch.save(&mut vault)?);
+                }
+            }
+            6 => {
                 println!("Goodbye!");
                 break;
             }
@@ -0,0 +1,383 @@
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+pub const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+pub const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub const DIGITS: &str = "0123456789";
+pub const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+/// Length generated when the policy allows it.
+pub const DEFAULT_LENGTH: usize = 20;
+
+/// What a service accepts as a password. Every field is optional in JSON so
+/// a policy only has to state what the site actually restricts.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    #[serde(default)]
+    pub min_length: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// Characters that may be used at all. `None` allows letters, digits
+    /// and [`SYMBOLS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<String>,
+    /// Each of these sets must contribute at least one character.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// Substrings that must not appear, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden: Vec<String>,
+}
+
+/// Name and one-line description of each built-in preset.
+pub const PRESETS: &[(&str, &str)] = &[
+    ("strong", "16-64 characters, upper, lower, digit and symbol"),
+    ("alphanumeric", "12-32 letters and digits, no symbols"),
+    ("legacy", "8-12 letters and digits, upper, lower and digit"),
+    ("pin4", "exactly 4 digits"),
+    ("pin6", "exactly 6 digits"),
+];
+
+/// The preset passwords are generated with when an entry has no policy.
+pub const DEFAULT_PRESET: &str = "strong";
+
+/// The policy to generate with: the entry's own, or [`DEFAULT_PRESET`].
+pub fn or_default(policy: Option<&Policy>) -> Policy {
+    policy
+        .cloned()
+        .or_else(|| preset(DEFAULT_PRESET))
+        .expect("the default preset exists")
+}
+
+pub fn preset(name: &str) -> Option<Policy> {
+    let alnum = || format!("{}{}{}", LOWER, UPPER, DIGITS);
+    let policy = match name {
+        "strong" => Policy {
+            min_length: 16,
+            max_length: Some(64),
+            allowed: None,
+            required: vec![LOWER.into(), UPPER.into(), DIGITS.into(), SYMBOLS.into()],
+            forbidden: Vec::new(),
+        },
+        "alphanumeric" => Policy {
+            min_length: 12,
+            max_length: Some(32),
+            allowed: Some(alnum()),
+            required: Vec::new(),
+            forbidden: Vec::new(),
+        },
+        "legacy" => Policy {
+            min_length: 8,
+            max_length: Some(12),
+            allowed: Some(alnum()),
+            required: vec![LOWER.into(), UPPER.into(), DIGITS.into()],
+            forbidden: Vec::new(),
+        },
+        "pin4" | "pin6" => {
+            let len = if name == "pin4" { 4 } else { 6 };
+            Policy {
+                min_length: len,
+                max_length: Some(len),
+                allowed: Some(DIGITS.into()),
+                required: Vec::new(),
+                forbidden: Vec::new(),
+            }
+        }
+        _ => return None,
+    };
+    Some(policy)
+}
+
+/// Human name for the common sets, the set itself otherwise.
+fn describe_set(set: &str) -> String {
+    match set {
+        LOWER => "a lowercase letter".to_string(),
+        UPPER => "an uppercase letter".to_string(),
+        DIGITS => "a digit".to_string(),
+        SYMBOLS => format!("a symbol ({})", SYMBOLS),
+        other => format!("one of '{}'", other),
+    }
+}
+
+impl Policy {
+    fn alphabet(&self) -> Vec<char> {
+        match &self.allowed {
+            Some(allowed) => allowed.chars().collect(),
+            None => format!("{}{}{}{}", LOWER, UPPER, DIGITS, SYMBOLS)
+                .chars()
+                .collect(),
+        }
+    }
+
+    fn allows(&self, c: char) -> bool {
+        match &self.allowed {
+            Some(allowed) => allowed.contains(c),
+            None => !c.is_control(),
+        }
+    }
+
+    /// Every way `password` breaks the policy, as sentences for the user.
+    /// Empty means it's accepted.
+    pub fn violations(&self, password: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        let len = password.chars().count();
+        if len < self.min_length {
+            problems.push(format!(
+                "is {} characters long; at least {} required",
+                len, self.min_length
+            ));
+        }
+        if let Some(max) = self.max_length.filter(|&max| len > max) {
+            problems.push(format!(
+                "is {} characters long; at most {} allowed",
+                len, max
+            ));
+        }
+        let mut rejected: Vec<char> = password.chars().filter(|&c| !self.allows(c)).collect();
+        rejected.sort_unstable();
+        rejected.dedup();
+        if !rejected.is_empty() {
+            let list: Vec<String> = rejected.iter().map(|c| format!("'{}'", c)).collect();
+            problems.push(format!(
+                "contains characters this service rejects: {}",
+                list.join(", ")
+            ));
+        }
+        for set in &self.required {
+            if !password.chars().any(|c| set.contains(c)) {
+                problems.push(format!("needs at least {}", describe_set(set)));
+            }
+        }
+        let lowered = password.to_lowercase();
+        for word in &self.forbidden {
+            if !word.is_empty() && lowered.contains(&word.to_lowercase()) {
+                problems.push(format!("must not contain '{}'", word));
+            }
+        }
+        problems
+    }
+
+    /// Checks the policy can be satisfied at all, so generation can't loop
+    /// forever and a typo in `--allowed` is caught when the policy is set.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(max) = self.max_length {
+            if max < self.min_length {
+                return Err(format!(
+                    "maximum length {} is below the minimum {}",
+                    max, self.min_length
+                ));
+            }
+            if max < self.required.len() {
+                return Err(format!(
+                    "maximum length {} cannot fit {} required character sets",
+                    max,
+                    self.required.len()
+                ));
+            }
+            if max == 0 {
+                return Err("maximum length must be at least 1".to_string());
+            }
+        }
+        if self.alphabet().is_empty() {
+            return Err("no characters are allowed".to_string());
+        }
+        for set in &self.required {
+            if !set.chars().any(|c| self.allows(c)) {
+                return Err(format!(
+                    "requires {} but none of those are allowed",
+                    describe_set(set)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// A random password that satisfies the policy: one character from each
+    /// required set, the rest from the allowed alphabet, shuffled, and
+    /// retried if it happens to contain a forbidden substring.
+    pub fn generate(&self, length: Option<usize>) -> Result<String, String> {
+        self.check()?;
+        let length = length
+            .unwrap_or(DEFAULT_LENGTH)
+            .max(self.min_length)
+            .max(self.required.len());
+        let length = self.max_length.map_or(length, |max| length.min(max));
+        let alphabet = self.alphabet();
+        let mut rng = OsRng;
+
+        for _ in 0..1000 {
+            let mut chars: Vec<char> = self
+                .required
+                .iter()
+                .map(|set| {
+                    let usable: Vec<char> = set.chars().filter(|&c| self.allows(c)).collect();
+                    *usable.choose(&mut rng).expect("checked above")
+                })
+                .collect();
+            while chars.len() < length {
+                chars.push(*alphabet.choose(&mut rng).expect("checked above"));
+            }
+            chars.shuffle(&mut rng);
+            let password: String = chars.into_iter().collect();
+            if self.violations(&password).is_empty() {
+                return Ok(password);
+            }
+        }
+        Err("could not generate a password that avoids the forbidden substrings".to_string())
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match self.max_length {
+            Some(max) if max == self.min_length => format!("exactly {} characters", max),
+            Some(max) => format!("{}-{} characters", self.min_length, max),
+            None => format!("at least {} characters", self.min_length),
+        }];
+        if let Some(allowed) = &self.allowed {
+            parts.push(format!("only '{}'", allowed));
+        }
+        for set in &self.required {
+            parts.push(format!("needs {}", describe_set(set)));
+        }
+        for word in &self.forbidden {
+            parts.push(format!("not '{}'", word));
+        }
+        parts.join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_preset_generates_passwords_it_accepts() {
+        for (name, _) in PRESETS {
+            let policy = preset(name).unwrap();
+            assert_eq!(policy.check(), Ok(()), "{}", name);
+            for length in [None, Some(1), Some(10), Some(100)] {
+                for _ in 0..50 {
+                    let password = policy.generate(length).unwrap();
+                    assert_eq!(
+                        policy.violations(&password),
+                        Vec::<String>::new(),
+                        "{} {:?}: {}",
+                        name,
+                        length,
+                        password
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generate_honours_the_length_within_the_limits() {
+        let strong = preset("strong").unwrap();
+        assert_eq!(
+            strong.generate(None).unwrap().chars().count(),
+            DEFAULT_LENGTH
+        );
+        assert_eq!(strong.generate(Some(30)).unwrap().chars().count(), 30);
+        assert_eq!(strong.generate(Some(4)).unwrap().chars().count(), 16);
+        assert_eq!(strong.generate(Some(500)).unwrap().chars().count(), 64);
+        let pin = preset("pin6").unwrap().generate(None).unwrap();
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn generate_avoids_forbidden_substrings() {
+        let policy = Policy {
+            min_length: 3,
+            max_length: Some(3),
+            allowed: Some("ab".to_string()),
+            forbidden: vec!["AA".to_string()],
+            ..Policy::default()
+        };
+        for _ in 0..50 {
+            assert!(!policy.generate(None).unwrap().contains("aa"));
+        }
+        let impossible = Policy {
+            forbidden: vec!["a".to_string(), "b".to_string()],
+            ..policy
+        };
+        assert!(impossible.generate(None).is_err());
+    }
+
+    #[test]
+    fn violations_lists_every_problem() {
+        let policy = Policy {
+            min_length: 8,
+            max_length: Some(10),
+            allowed: Some(format!("{}{}", LOWER, DIGITS)),
+            required: vec![DIGITS.to_string()],
+            forbidden: vec!["Pass".to_string()],
+        };
+        assert!(policy.violations("abcdef12").is_empty());
+        assert_eq!(
+            policy.violations("PASS!"),
+            [
+                "is 5 characters long; at least 8 required",
+                "contains characters this service rejects: '!', 'A', 'P', 'S'",
+                "needs at least a digit",
+                "must not contain 'Pass'",
+            ]
+        );
+        assert_eq!(
+            policy.violations("abcdefghijk1"),
+            ["is 12 characters long; at most 10 allowed"]
+        );
+        // Lengths are counted in characters, not bytes.
+        assert!(Policy {
+            min_length: 2,
+            max_length: Some(2),
+            ..Policy::default()
+        }
+        .violations("éé")
+        .is_empty());
+    }
+
+    #[test]
+    fn check_refuses_policies_nothing_can_satisfy() {
+        let base = Policy::default();
+        assert_eq!(base.check(), Ok(()));
+        let cases = [
+            Policy {
+                min_length: 10,
+                max_length: Some(8),
+                ..base.clone()
+            },
+            Policy {
+                max_length: Some(1),
+                required: vec![LOWER.into(), DIGITS.into()],
+                ..base.clone()
+            },
+            Policy {
+                max_length: Some(0),
+                ..base.clone()
+            },
+            Policy {
+                allowed: Some(String::new()),
+                ..base.clone()
+            },
+            Policy {
+                allowed: Some(LOWER.into()),
+                required: vec![DIGITS.into()],
+                ..base.clone()
+            },
+        ];
+        for policy in cases {
+            assert!(policy.check().is_err(), "{:?}", policy);
+            assert!(policy.generate(None).is_err(), "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn entries_without_a_policy_use_the_default_preset() {
+        assert_eq!(or_default(None), preset(DEFAULT_PRESET).unwrap());
+        let pin = preset("pin4").unwrap();
+        assert_eq!(or_default(Some(&pin)), pin);
+        assert!(preset("nope").is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::secret::SecretString;
+use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, Select};
+use std::io::{self, BufRead, IsTerminal};
+
+/// Where answers to questions come from. Every prompt goes through this,
+/// so the same session can be driven by a person at a terminal or by a
+/// script (`--batch`, and the integration tests).
+pub trait Prompter {
+    /// Whether a person is answering. Things like screen clearing
+    /// countdowns are skipped when not.
+    fn interactive(&self) -> bool;
+
+    /// Index of the chosen item.
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> io::Result<usize>;
+
+    /// Like `select`, with type-to-filter on a terminal.
+    fn fuzzy_select(&mut self, prompt: &str, items: &[&str]) -> io::Result<usize>;
+
+    /// A line of text, re-asked (or, scripted, refused) until `validate`
+    /// accepts it.
+    fn input(
+        &mut self,
+        prompt: &str,
+        allow_empty: bool,
+        validate: &dyn Fn(&str) -> Result<(), String>,
+    ) -> io::Result<String>;
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> io::Result<bool>;
+
+    /// Hidden input. `prompt` should end with ": ".
+    fn password(&mut self, prompt: &str) -> io::Result<SecretString>;
+
+    fn text(&mut self, prompt: &str, allow_empty: bool) -> io::Result<String> {
+        self.input(prompt, allow_empty, &|_| Ok(()))
+    }
+}
+
+/// `dialoguer` prompts and `rpassword` on the controlling terminal.
+pub struct Terminal {
+    theme: ColorfulTheme,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self {
+            theme: ColorfulTheme::default(),
+        }
+    }
+
+    /// `dialoguer` can't work without a terminal; fail cleanly instead.
+    fn check(&self) -> io::Result<()> {
+        if io::stdin().is_terminal() && io::stderr().is_terminal() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "no terminal to ask on; use --batch to read answers from stdin",
+            ))
+        }
+    }
+}
+
+impl Prompter for Terminal {
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> io::Result<usize> {
+        self.check()?;
+        Select::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .interact()
+    }
+
+    fn fuzzy_select(&mut self, prompt: &str, items: &[&str]) -> io::Result<usize> {
+        self.check()?;
+        FuzzySelect::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .items(items)
+            .default(0)
+            .interact()
+    }
+
+    fn input(
+        &mut self,
+        prompt: &str,
+        allow_empty: bool,
+        validate: &dyn Fn(&str) -> Result<(), String>,
+    ) -> io::Result<String> {
+        self.check()?;
+        Input::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .allow_empty(allow_empty)
+            .validate_with(|s: &String| validate(s))
+            .interact_text()
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> io::Result<bool> {
+        self.check()?;
+        Confirm::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .default(default)
+            .interact()
+    }
+
+    fn password(&mut self, prompt: &str) -> io::Result<SecretString> {
+        rpassword::prompt_password(prompt).map(SecretString::from)
+    }
+}
+
+/// Answers read one per line from a file or stdin. Each question and the
+/// answer used are echoed to stderr (passwords masked) so a run can be
+/// followed.
+///
+/// - select: the item's text, a unique prefix of it, or its 1-based number;
+///   an empty line takes the default
+/// - confirm: `y`/`yes` or `n`/`no`; an empty line takes the default
+/// - input and password: the line as is
+pub struct Scripted<R> {
+    answers: R,
+}
+
+impl<R: BufRead> Scripted<R> {
+    pub fn new(answers: R) -> Self {
+        Self { answers }
+    }
+
+    fn next(&mut self, prompt: &str) -> io::Result<String> {
+        let mut line = String::new();
+        if self.answers.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("ran out of answers at '{}'", prompt.trim_end()),
+            ));
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(line)
+    }
+
+    fn choose(&mut self, prompt: &str, items: &[&str], default: usize) -> io::Result<usize> {
+        let answer = self.next(prompt)?;
+        let index = pick(items, &answer, default).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not one unique choice at '{}'", answer, prompt),
+            )
+        })?;
+        eprintln!("? {} {}", prompt, items[index]);
+        Ok(index)
+    }
+}
+
+/// Resolves a scripted answer to an item. A prefix followed by whitespace
+/// in the item (a whole leading word) wins over a plain prefix, so `git`
+/// picks `git  me` over `github  me`.
+fn pick(items: &[&str], answer: &str, default: usize) -> Option<usize> {
+    if answer.is_empty() {
+        return (default < items.len()).then_some(default);
+    }
+    if let Some(i) = items.iter().position(|item| *item == answer) {
+        return Some(i);
+    }
+    let prefixed: Vec<usize> = (0..items.len())
+        .filter(|&i| items[i].to_lowercase().starts_with(&answer.to_lowercase()))
+        .collect();
+    let whole: Vec<usize> = prefixed
+        .iter()
+        .copied()
+        .filter(|&i| {
+            items[i]
+                .get(answer.len()..)
+                .is_some_and(|rest| rest.chars().next().is_none_or(char::is_whitespace))
+        })
+        .collect();
+    match (whole.as_slice(), prefixed.as_slice()) {
+        ([i], _) | ([], [i]) => Some(*i),
+        _ => answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .filter(|&i| i < items.len()),
+    }
+}
+
+impl<R: BufRead> Prompter for Scripted<R> {
+    fn interactive(&self) -> bool {
+        false
+    }
+
+    fn select(&mut self, prompt: &str, items: &[&str], default: usize) -> io::Result<usize> {
+        self.choose(prompt, items, default)
+    }
+
+    fn fuzzy_select(&mut self, prompt: &str, items: &[&str]) -> io::Result<usize> {
+        self.choose(prompt, items, 0)
+    }
+
+    fn input(
+        &mut self,
+        prompt: &str,
+        allow_empty: bool,
+        validate: &dyn Fn(&str) -> Result<(), String>,
+    ) -> io::Result<String> {
+        let answer = self.next(prompt)?;
+        eprintln!("? {} {}", prompt, answer);
+        if answer.is_empty() && !allow_empty {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("an answer is required at '{}'", prompt),
+            ));
+        }
+        validate(&answer).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' rejected at '{}': {}", answer, prompt, e),
+            )
+        })?;
+        Ok(answer)
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> io::Result<bool> {
+        let answer = self.next(prompt)?;
+        let yes = match answer.trim().to_lowercase().as_str() {
+            "" => default,
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("expected y or n at '{}', got '{}'", prompt, answer),
+                ))
+            }
+        };
+        eprintln!("? {} {}", prompt, if yes { "yes" } else { "no" });
+        Ok(yes)
+    }
+
+    fn password(&mut self, prompt: &str) -> io::Result<SecretString> {
+        let answer = SecretString::from(self.next(prompt)?);
+        eprintln!("{}********", prompt);
+        Ok(answer)
+    }
+}
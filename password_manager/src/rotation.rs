@@ -0,0 +1,257 @@
+use crate::store::{Entry, PasswordStore};
+use chrono::{Days, Local, NaiveDate};
+use serde::Serialize;
+
+/// Days ahead that count as "upcoming" in the startup banner and `due`.
+pub const DEFAULT_WINDOW_DAYS: u32 = 14;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// `rotate_every_days` has passed since the password was last changed.
+    Rotation,
+    /// The explicit `expires` date.
+    Expiry,
+    /// A rotation interval is set but the last change date is unknown.
+    NeverRotated,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Due {
+    pub service: String,
+    pub due: NaiveDate,
+    /// Negative when overdue.
+    pub days_left: i64,
+    pub reason: Reason,
+}
+
+impl Due {
+    pub fn overdue(&self) -> bool {
+        self.days_left < 0
+    }
+}
+
+/// The earliest date the entry's password has to be changed by, if any.
+pub fn due_date(entry: &Entry, today: NaiveDate) -> Option<(NaiveDate, Reason)> {
+    let rotation = entry.rotate_every_days.map(|days| {
+        match entry.password_changed {
+            Some(changed) => (
+                changed.with_timezone(&Local).date_naive() + Days::new(u64::from(days)),
+                Reason::Rotation,
+            ),
+            // Unknown age: due now.
+            None => (today, Reason::NeverRotated),
+        }
+    });
+    let expiry = entry.expires.map(|date| (date, Reason::Expiry));
+    match (rotation, expiry) {
+        (Some(r), Some(e)) => Some(if e.0 < r.0 { e } else { r }),
+        (r, e) => r.or(e),
+    }
+}
+
+/// Entries that are overdue or due within `window_days`, soonest first.
+pub fn due(store: &PasswordStore, today: NaiveDate, window_days: u32) -> Vec<Due> {
+    let mut due: Vec<Due> = store
+        .iter()
+        .filter_map(|(service, entry)| {
+            let (date, reason) = due_date(entry, today)?;
+            let days_left = (date - today).num_days();
+            (days_left <= i64::from(window_days)).then(|| Due {
+                service: service.clone(),
+                due: date,
+                days_left,
+                reason,
+            })
+        })
+        .collect();
+    due.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.service.cmp(&b.service)));
+    due
+}
+
+pub fn describe(item: &Due) -> String {
+    let when = match item.days_left {
+        d if d < 0 => format!("{} days overdue", -d),
+        0 => "due today".to_string(),
+        1 => "due tomorrow".to_string(),
+        d => format!("due in {} days", d),
+    };
+    let why = match item.reason {
+        Reason::Rotation => "rotation",
+        Reason::Expiry => "expiry",
+        Reason::NeverRotated => "rotation, last change unknown",
+    };
+    format!("{} ({})", when, why)
+}
+
+pub fn print_table(items: &[Due]) {
+    let width = items
+        .iter()
+        .map(|d| d.service.chars().count())
+        .chain(std::iter::once("SERVICE".len()))
+        .max()
+        .unwrap_or(0);
+    println!(
+        "{:<width$}  {:<10}  STATUS",
+        "SERVICE",
+        "DUE",
+        width = width
+    );
+    for item in items {
+        println!(
+            "{:<width$}  {:<10}  {}",
+            item.service,
+            item.due,
+            describe(item),
+            width = width
+        );
+    }
+}
+
+/// The startup notice, or `None` when nothing needs attention.
+pub fn banner(store: &PasswordStore, today: NaiveDate) -> Option<String> {
+    let items = due(store, today, DEFAULT_WINDOW_DAYS);
+    if items.is_empty() {
+        return None;
+    }
+    let overdue = items.iter().filter(|d| d.overdue()).count();
+    let mut lines = vec![format!(
+        "Password rotation: {} overdue, {} upcoming",
+        overdue,
+        items.len() - overdue
+    )];
+    lines.extend(
+        items
+            .iter()
+            .map(|item| format!("  ! {} - {}", item.service, describe(item))),
+    );
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretString;
+    use chrono::{TimeZone, Utc};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// An entry whose password was changed at noon local time on `changed`.
+    fn entry(changed: Option<NaiveDate>, every: Option<u32>, expires: Option<NaiveDate>) -> Entry {
+        let mut entry = Entry::new("me".to_string(), SecretString::from("pw".to_string()));
+        entry.password_changed = changed.map(|d| {
+            Local
+                .from_local_datetime(&d.and_hms_opt(12, 0, 0).unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+        });
+        entry.rotate_every_days = every;
+        entry.expires = expires;
+        entry
+    }
+
+    fn store(entries: Vec<(&str, Entry)>) -> PasswordStore {
+        entries
+            .into_iter()
+            .map(|(name, entry)| (name.to_string(), entry))
+            .collect()
+    }
+
+    #[test]
+    fn due_date_picks_the_earlier_of_rotation_and_expiry() {
+        let today = date(2026, 10, 19);
+        let changed = Some(date(2026, 10, 1));
+        assert_eq!(
+            due_date(&entry(changed, Some(30), None), today),
+            Some((date(2026, 10, 31), Reason::Rotation))
+        );
+        assert_eq!(
+            due_date(&entry(changed, Some(30), Some(date(2026, 10, 25))), today),
+            Some((date(2026, 10, 25), Reason::Expiry))
+        );
+        assert_eq!(
+            due_date(&entry(changed, Some(10), Some(date(2026, 10, 25))), today),
+            Some((date(2026, 10, 11), Reason::Rotation))
+        );
+        assert_eq!(
+            due_date(&entry(None, None, Some(date(2027, 1, 1))), today),
+            Some((date(2027, 1, 1), Reason::Expiry))
+        );
+        // Exactly one interval after the change is due today, not overdue.
+        let store = store(vec![(
+            "exact",
+            entry(Some(date(2026, 9, 19)), Some(30), None),
+        )]);
+        let due = due(&store, today, 0);
+        assert_eq!((due[0].due, due[0].days_left), (today, 0));
+        assert!(!due[0].overdue());
+    }
+
+    #[test]
+    fn entries_without_dates() {
+        let today = date(2026, 10, 19);
+        // No schedule at all: never due.
+        assert_eq!(due_date(&entry(None, None, None), today), None);
+        assert_eq!(
+            due_date(&entry(Some(date(2020, 1, 1)), None, None), today),
+            None
+        );
+        // An interval but no known change: due now.
+        assert_eq!(
+            due_date(&entry(None, Some(90), None), today),
+            Some((today, Reason::NeverRotated))
+        );
+    }
+
+    #[test]
+    fn the_window_includes_its_last_day() {
+        let today = date(2026, 10, 19);
+        let store = store(vec![
+            ("edge", entry(None, None, Some(date(2026, 11, 2)))),
+            ("past-edge", entry(None, None, Some(date(2026, 11, 3)))),
+            ("today", entry(None, None, Some(today))),
+            ("overdue", entry(Some(date(2026, 9, 1)), Some(30), None)),
+            ("never", entry(None, Some(30), None)),
+            ("unscheduled", entry(None, None, None)),
+        ]);
+        let due = due(&store, today, 14);
+        let listed: Vec<(&str, i64)> = due
+            .iter()
+            .map(|d| (d.service.as_str(), d.days_left))
+            .collect();
+        assert_eq!(
+            listed,
+            [("overdue", -18), ("never", 0), ("today", 0), ("edge", 14)]
+        );
+        assert!(due[0].overdue());
+        assert!(!due[1].overdue());
+        assert!(super::due(&store, today, 0)
+            .iter()
+            .all(|d| d.days_left <= 0));
+    }
+
+    #[test]
+    fn banner_counts_overdue_and_upcoming() {
+        let today = date(2026, 10, 19);
+        let quiet = store(vec![
+            ("later", entry(None, None, Some(date(2027, 1, 1)))),
+            ("unscheduled", entry(None, None, None)),
+        ]);
+        assert_eq!(banner(&quiet, today), None);
+        assert_eq!(banner(&PasswordStore::new(), today), None);
+
+        let busy = store(vec![
+            ("bank", entry(None, None, Some(date(2026, 10, 17)))),
+            ("mail", entry(None, None, Some(date(2026, 10, 20)))),
+            ("later", entry(None, None, Some(date(2027, 1, 1)))),
+        ]);
+        assert_eq!(
+            banner(&busy, today).unwrap(),
+            "Password rotation: 1 overdue, 1 upcoming\n  \
+             ! bank - 2 days overdue (expiry)\n  \
+             ! mail - due tomorrow (expiry)"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::store::{Entry, PasswordStore};
+use dialoguer::console::Style;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+/// A ranked search hit.
+pub struct Match<'a> {
+    pub service: &'a str,
+    pub line: String,
+    pub score: i64,
+    /// Char positions in `line` that matched the query.
+    pub indices: Vec<usize>,
+}
+
+/// The single line an entry is matched against: service, username, tags
+/// and URL. The interactive picker shows exactly these lines, so what the
+/// user types is scored the same way as `search`.
+pub fn haystack(service: &str, entry: &Entry) -> String {
+    let mut line = format!("{}  {}", service, entry.username);
+    if !entry.tags.is_empty() {
+        line.push_str("  #");
+        line.push_str(&entry.tags.join(" #"));
+    }
+    if let Some(url) = &entry.url {
+        line.push_str("  ");
+        line.push_str(url);
+    }
+    line
+}
+
+/// Fuzzy-matches `query` against every entry, best score first. Ties fall
+/// back to service name so the output is deterministic.
+pub fn search<'a>(store: &'a PasswordStore, query: &str) -> Vec<Match<'a>> {
+    let matcher = SkimMatcherV2::default();
+    let mut matches: Vec<Match> = store
+        .iter()
+        .filter_map(|(service, entry)| {
+            let line = haystack(service, entry);
+            let (score, indices) = matcher.fuzzy_indices(&line, query)?;
+            Some(Match {
+                service,
+                line,
+                score,
+                indices,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.service.cmp(b.service)));
+    matches
+}
+
+/// Renders `line` with the matched characters in bold.
+pub fn highlight(line: &str, indices: &[usize]) -> String {
+    let style = Style::new().bold();
+    line.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if indices.contains(&i) {
+                style.apply_to(c).to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretString;
+
+    fn store(entries: &[(&str, &str)]) -> PasswordStore {
+        entries
+            .iter()
+            .map(|(service, username)| {
+                let entry = Entry::new(username.to_string(), SecretString::from("x".to_string()));
+                (service.to_string(), entry)
+            })
+            .collect()
+    }
+
+    fn services<'a>(matches: &[Match<'a>]) -> Vec<&'a str> {
+        matches.iter().map(|m| m.service).collect()
+    }
+
+    #[test]
+    fn better_matches_rank_first() {
+        let store = store(&[
+            ("gitlab", "bob"),
+            ("github", "alice"),
+            ("digital-hub", "carol"),
+        ]);
+        let matches = search(&store, "github");
+        assert_eq!(services(&matches)[0], "github");
+        assert!(matches.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn non_matches_are_left_out() {
+        let store = store(&[("github", "alice"), ("bank", "bob")]);
+        assert_eq!(services(&search(&store, "gthb")), ["github"]);
+        assert!(search(&store, "zzz").is_empty());
+    }
+
+    #[test]
+    fn equal_scores_fall_back_to_service_name() {
+        // Same username, and the query only hits the username.
+        let store = store(&[("zeta", "shared"), ("alpha", "shared"), ("mid", "shared")]);
+        let matches = search(&store, "shared");
+        assert_eq!(services(&matches), ["alpha", "mid", "zeta"]);
+    }
+
+    #[test]
+    fn username_tags_and_url_are_searched() {
+        let mut store = store(&[("one", "alice"), ("two", "bob"), ("three", "carol")]);
+        store.get_mut("two").unwrap().tags = vec!["banking".to_string()];
+        store.get_mut("three").unwrap().url = Some("https://example.org".to_string());
+        assert_eq!(services(&search(&store, "alice")), ["one"]);
+        assert_eq!(services(&search(&store, "#banking")), ["two"]);
+        assert_eq!(services(&search(&store, "example.org")), ["three"]);
+    }
+
+    #[test]
+    fn indices_point_at_the_matched_chars() {
+        let store = store(&[("github", "alice")]);
+        let matches = search(&store, "gh");
+        let line: Vec<char> = matches[0].line.chars().collect();
+        let hit: String = matches[0].indices.iter().map(|&i| line[i]).collect();
+        assert_eq!(hit, "gh");
+    }
+
+    #[test]
+    fn highlight_keeps_the_text() {
+        dialoguer::console::set_colors_enabled(true);
+        let highlighted = highlight("github", &[0, 3]);
+        assert_ne!(highlighted, "github");
+        assert_eq!(dialoguer::console::strip_ansi_codes(&highlighted), "github");
+        assert_eq!(highlight("github", &[]), "github");
+    }
+}
@@ -0,0 +1,280 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use zeroize::Zeroize;
+
+const REDACTED: &str = "<redacted>";
+
+/// A secret string: locked into RAM where the OS allows it, wiped when
+/// dropped, and redacted in `Debug` and `Display`. It deliberately isn't
+/// `Clone`; the value is only reachable through [`SecretString::expose`].
+pub struct SecretString {
+    inner: String,
+}
+
+impl SecretString {
+    pub fn new(inner: String) -> Self {
+        memlock::lock(inner.as_ptr(), inner.capacity());
+        Self { inner }
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.inner
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+impl Default for SecretString {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(inner: String) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.inner.as_ptr(), self.inner.capacity());
+        self.inner.zeroize();
+        memlock::unlock(ptr, capacity);
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString({})", REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.inner)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Plaintext bytes, such as a decrypted vault, with the same lock-and-wipe
+/// treatment as [`SecretString`].
+pub struct SecretBytes {
+    inner: Vec<u8>,
+}
+
+impl SecretBytes {
+    pub fn new(inner: Vec<u8>) -> Self {
+        memlock::lock(inner.as_ptr(), inner.capacity());
+        Self { inner }
+    }
+
+    pub fn expose(&self) -> &[u8] {
+        &self.inner
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.inner.as_ptr(), self.inner.capacity());
+        self.inner.zeroize();
+        memlock::unlock(ptr, capacity);
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes({})", REDACTED)
+    }
+}
+
+/// Best-effort `mlock`, keeping secrets out of swap. Failure (for example
+/// hitting `RLIMIT_MEMLOCK`) is ignored: the secret is still wiped on drop.
+/// The kernel locks whole pages and doesn't count nested locks, so small
+/// secrets sharing a page are reference-counted here: a page is only
+/// unlocked when the last secret on it is dropped.
+mod memlock {
+    #[cfg(unix)]
+    use std::collections::BTreeMap;
+    #[cfg(unix)]
+    use std::sync::{Mutex, OnceLock};
+
+    /// Secrets locked on each page, by page number.
+    #[cfg(unix)]
+    static PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+    #[cfg(unix)]
+    pub fn page_size() -> usize {
+        static SIZE: OnceLock<usize> = OnceLock::new();
+        *SIZE.get_or_init(|| match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        })
+    }
+
+    /// Numbers of the pages that `len` bytes at `ptr` touch.
+    #[cfg(unix)]
+    fn pages(ptr: *const u8, len: usize) -> std::ops::Range<usize> {
+        let size = page_size();
+        let start = ptr as usize / size;
+        let end = (ptr as usize + len).div_ceil(size);
+        start..end
+    }
+
+    #[cfg(unix)]
+    fn page_ptr(page: usize) -> *const libc::c_void {
+        (page * page_size()) as *const libc::c_void
+    }
+
+    #[cfg(unix)]
+    pub fn lock(ptr: *const u8, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut counts = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in self::pages(ptr, len) {
+            let count = counts.entry(page).or_insert(0);
+            if *count == 0 {
+                unsafe {
+                    libc::mlock(page_ptr(page), page_size());
+                }
+            }
+            *count += 1;
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn unlock(ptr: *const u8, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut counts = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in self::pages(ptr, len) {
+            let Some(count) = counts.get_mut(&page) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&page);
+                unsafe {
+                    libc::munlock(page_ptr(page), page_size());
+                }
+            }
+        }
+    }
+
+    /// How many secrets are locked on the page holding `ptr`.
+    #[cfg(all(unix, test))]
+    pub fn holders(ptr: *const u8) -> usize {
+        let counts = PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        let page = self::pages(ptr, 1).start;
+        counts.get(&page).copied().unwrap_or(0)
+    }
+
+    #[cfg(not(unix))]
+    pub fn lock(_ptr: *const u8, _len: usize) {}
+
+    #[cfg(not(unix))]
+    pub fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Entry;
+
+    const VALUE: &str = "hunter2-correct-horse";
+
+    #[test]
+    fn debug_does_not_leak() {
+        let secret = SecretString::from(VALUE.to_string());
+        let debug = format!("{:?} {:#?}", secret, secret);
+        assert!(!debug.contains(VALUE));
+        assert!(debug.contains(REDACTED));
+    }
+
+    #[test]
+    fn display_does_not_leak() {
+        let secret = SecretString::from(VALUE.to_string());
+        assert_eq!(secret.to_string(), REDACTED);
+        assert!(!format!("{:>40}", secret).contains(VALUE));
+    }
+
+    #[test]
+    fn bytes_debug_does_not_leak() {
+        let secret = SecretBytes::new(VALUE.as_bytes().to_vec());
+        assert!(!format!("{:?}", secret).contains(VALUE));
+        assert!(!format!("{:?}", secret).contains("104, 117"));
+    }
+
+    #[test]
+    fn entry_debug_does_not_leak() {
+        let entry: Entry = serde_json::from_str(&format!(
+            r#"{{"username": "alice", "password": "{}"}}"#,
+            VALUE
+        ))
+        .unwrap();
+        let debug = format!("{:?}", entry);
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains(VALUE));
+    }
+
+    #[test]
+    fn serde_round_trips_the_value() {
+        let secret = SecretString::from(VALUE.to_string());
+        let json = serde_json::to_string(&secret).unwrap();
+        let back: SecretString = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.expose(), VALUE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_shared_page_stays_locked_until_its_last_secret_is_dropped() {
+        let size = memlock::page_size();
+        // The middle one of three pages belongs to this buffer alone.
+        let buffer = vec![0u8; size * 3];
+        let page = buffer
+            .as_ptr()
+            .wrapping_add(size)
+            .wrapping_sub(buffer.as_ptr() as usize % size);
+        let (first, second) = (page, page.wrapping_add(64));
+        memlock::lock(first, 32);
+        memlock::lock(second, 32);
+        assert_eq!(memlock::holders(page), 2);
+        memlock::unlock(first, 32);
+        assert_eq!(memlock::holders(page), 1);
+        memlock::unlock(second, 32);
+        assert_eq!(memlock::holders(page), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secrets_spanning_pages_count_on_each() {
+        let size = memlock::page_size();
+        let buffer = vec![0u8; size * 4];
+        let page = buffer
+            .as_ptr()
+            .wrapping_add(size)
+            .wrapping_sub(buffer.as_ptr() as usize % size);
+        let straddling = page.wrapping_add(size - 8);
+        memlock::lock(straddling, 16);
+        assert_eq!(memlock::holders(page), 1);
+        assert_eq!(memlock::holders(page.wrapping_add(size)), 1);
+        memlock::unlock(straddling, 16);
+        assert_eq!(memlock::holders(page), 0);
+        assert_eq!(memlock::holders(page.wrapping_add(size)), 0);
+    }
+}
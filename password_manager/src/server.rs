@@ -0,0 +1,387 @@
+use crate::audit::{self, Event};
+use crate::search;
+use crate::secret::SecretString;
+use crate::store::{Entry, Vault};
+use crate::tokens::{self, Token};
+use crate::usage;
+use crate::watch::Watch;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Failed authentications allowed per client and window before its bad
+/// tokens get 429 instead of 401 until the window has passed.
+const MAX_AUTH_FAILURES: usize = 5;
+const AUTH_WINDOW: Duration = Duration::from_secs(60);
+const MAX_BODY: u64 = 1024 * 1024;
+
+pub enum Listen {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+/// Entry as returned by the API. Listings and search leave out the
+/// password.
+#[derive(Serialize)]
+struct EntryView<'a> {
+    service: &'a str,
+    username: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+}
+
+impl<'a> EntryView<'a> {
+    fn new(service: &'a str, entry: &'a Entry, with_password: bool) -> Self {
+        Self {
+            service,
+            username: &entry.username,
+            password: with_password.then(|| entry.password.expose()),
+            url: entry.url.as_deref(),
+            tags: &entry.tags,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PutEntry {
+    username: String,
+    password: SecretString,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+struct ApiError(u16, String);
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self(status, message.into())
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> Self {
+        Self(500, e.to_string())
+    }
+}
+
+type Reply = Result<(u16, serde_json::Value), ApiError>;
+
+/// Counts failed authentications per client address. A client that keeps
+/// presenting bad tokens is told to back off and no longer fills the audit
+/// log; requests with a valid token are always served, so one misbehaving
+/// tool can't lock out the others. The tokens themselves are 256-bit random
+/// values, which is what actually stops guessing.
+#[derive(Default)]
+struct AuthLimiter {
+    failures: HashMap<Option<IpAddr>, VecDeque<Instant>>,
+}
+
+impl AuthLimiter {
+    fn locked_for(&mut self, client: Option<IpAddr>) -> Option<Duration> {
+        let now = Instant::now();
+        let failures = self.failures.get_mut(&client)?;
+        while failures
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > AUTH_WINDOW)
+        {
+            failures.pop_front();
+        }
+        if failures.is_empty() {
+            self.failures.remove(&client);
+            return None;
+        }
+        if failures.len() < MAX_AUTH_FAILURES {
+            return None;
+        }
+        let oldest = *failures.front()?;
+        Some(AUTH_WINDOW.saturating_sub(now.duration_since(oldest)))
+    }
+
+    fn fail(&mut self, client: Option<IpAddr>) {
+        self.failures
+            .entry(client)
+            .or_default()
+            .push_back(Instant::now());
+    }
+}
+
+pub fn serve(vault: &mut Vault, listen: Listen) -> io::Result<()> {
+    // A socket left behind by a previous run would make binding fail.
+    #[cfg(unix)]
+    if let Listen::Unix(path) = &listen {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    let server = match &listen {
+        Listen::Tcp(addr) => Server::http(addr),
+        #[cfg(unix)]
+        Listen::Unix(path) => Server::http_unix(path),
+    }
+    .map_err(|e| io::Error::other(e.to_string()))?;
+    match &listen {
+        Listen::Tcp(addr) => println!("Serving on http://{}", addr),
+        #[cfg(unix)]
+        Listen::Unix(path) => println!("Serving on unix:{}", path.display()),
+    }
+
+    let mut limiter = AuthLimiter::default();
+    let mut watch = Watch::start(vault)?;
+    for mut request in server.incoming_requests() {
+        let client = request.remote_addr().map(|addr| addr.ip());
+        // Answer from the vault as it is now, not as it was at startup.
+        if let Err(e) = watch.poll(vault) {
+            eprintln!("could not reload the vault: {}", e);
+        }
+        let reply = handle(vault, &mut watch, &mut limiter, &mut request);
+        let (status, body, retry_after) = match reply {
+            Ok((status, body)) => (status, body, None),
+            Err(ApiError(429, message)) => {
+                let retry = limiter.locked_for(client).map(|d| d.as_secs() + 1);
+                (429, json!({ "error": message }), retry)
+            }
+            Err(ApiError(status, message)) => (status, json!({ "error": message }), None),
+        };
+        let mut response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json"));
+        if let Some(secs) = retry_after {
+            response = response.with_header(header("Retry-After", &secs.to_string()));
+        }
+        if let Err(e) = request.respond(response) {
+            eprintln!("failed to send response: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn authenticate(request: &Request, limiter: &mut AuthLimiter) -> Result<Token, ApiError> {
+    let presented = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .map(str::trim);
+    // Re-read on every request so `token-add`/`token-revoke` apply without
+    // a restart.
+    let tokens = tokens::load()?;
+    if let Some(token) = presented.and_then(|p| tokens::authenticate(tokens, p)) {
+        return Ok(token);
+    }
+    let client = request.remote_addr().map(|addr| addr.ip());
+    if limiter.locked_for(client).is_some() {
+        return Err(ApiError::new(429, "too many failed authentications"));
+    }
+    limiter.fail(client);
+    audit::record_as("api", Event::AuthFailed, None)?;
+    Err(ApiError::new(401, "missing or invalid bearer token"))
+}
+
+fn handle(
+    vault: &mut Vault,
+    watch: &mut Watch,
+    limiter: &mut AuthLimiter,
+    request: &mut Request,
+) -> Reply {
+    let token = authenticate(request, limiter)?;
+    let actor = format!("api:{}", token.name);
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    match (request.method(), path) {
+        (Method::Get, "/entries") => {
+            let list: Vec<EntryView> = vault
+                .entries
+                .iter()
+                .filter(|(service, _)| token.can_see(service))
+                .map(|(service, entry)| EntryView::new(service, entry, false))
+                .collect();
+            Ok((200, json!(list)))
+        }
+        (Method::Get, "/search") => {
+            let q = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("q="))
+                .map(|q| percent_decode(&q.replace('+', "%20")))
+                .ok_or_else(|| ApiError::new(400, "missing ?q="))??;
+            let results: Vec<EntryView> = search::search(&vault.entries, &q)
+                .into_iter()
+                .filter(|m| token.can_see(m.service))
+                .map(|m| EntryView::new(m.service, &vault.entries[m.service], false))
+                .collect();
+            Ok((200, json!(results)))
+        }
+        (method, path) if path.starts_with("/entries/") => {
+            let service = percent_decode(&path["/entries/".len()..])?;
+            if service.is_empty() {
+                return Err(ApiError::new(404, "not found"));
+            }
+            if !token.can_see(&service) {
+                return Err(ApiError::new(403, "outside this token's folders"));
+            }
+            match method {
+                Method::Get => {
+                    let entry = vault
+                        .entries
+                        .get_mut(&service)
+                        .ok_or_else(|| ApiError::new(404, "no such entry"))?;
+                    if entry.derived.is_some() {
+                        return Err(ApiError::new(
+                            409,
+                            "derived entries need the master secret and can't be read over the API",
+                        ));
+                    }
+                    audit::record_as(&actor, Event::View, Some(&service))?;
+                    usage::record(entry);
+                    let view = json!(EntryView::new(&service, entry, true));
+                    report_changes(&watch.save(vault)?);
+                    Ok((200, view))
+                }
+                Method::Put => {
+                    require_write(&token)?;
+                    let mut body = String::new();
+                    request
+                        .as_reader()
+                        .take(MAX_BODY)
+                        .read_to_string(&mut body)?;
+                    let put: PutEntry = serde_json::from_str(&body)
+                        .map_err(|e| ApiError::new(400, format!("bad entry JSON: {}", e)))?;
+                    put_entry(vault, &service, put)?;
+                    audit::record_as(&actor, Event::Add, Some(&service))?;
+                    report_changes(&watch.save(vault)?);
+                    Ok((200, json!({ "saved": service })))
+                }
+                Method::Delete => {
+                    require_write(&token)?;
+                    if !vault.entries.contains_key(&service) {
+                        return Err(ApiError::new(404, "no such entry"));
+                    }
+                    audit::record_as(&actor, Event::Delete, Some(&service))?;
+                    vault.delete(&service, |vault| {
+                        report_changes(&watch.save(vault)?);
+                        Ok(())
+                    })?;
+                    Ok((200, json!({ "deleted": service })))
+                }
+                _ => Err(ApiError::new(405, "method not allowed")),
+            }
+        }
+        _ => Err(ApiError::new(404, "not found")),
+    }
+}
+
+/// Logs what another process changed in the vault while serving.
+fn report_changes(changes: &[crate::watch::Change]) {
+    for change in changes {
+        eprintln!("{}", change);
+    }
+}
+
+fn require_write(token: &Token) -> Result<(), ApiError> {
+    if token.can_write() {
+        Ok(())
+    } else {
+        Err(ApiError::new(403, "token is read-only"))
+    }
+}
+
+/// Same rules as the Add Entry menu: non-empty password that satisfies the
+/// entry's policy. Updating keeps the entry's other settings.
+fn put_entry(vault: &mut Vault, service: &str, put: PutEntry) -> Result<(), ApiError> {
+    if put.password.expose().trim().is_empty() {
+        return Err(ApiError::new(422, "password cannot be empty"));
+    }
+    if let Some(policy) = vault.entries.get(service).and_then(|e| e.policy.as_ref()) {
+        let problems = policy.violations(put.password.expose());
+        if !problems.is_empty() {
+            return Err(ApiError::new(
+                422,
+                format!("password rejected by policy: {}", problems.join("; ")),
+            ));
+        }
+    }
+    let entry = match vault.entries.get_mut(service) {
+        Some(entry) => {
+            entry.username = put.username;
+            entry.set_password(put.password);
+            entry
+        }
+        None => vault
+            .entries
+            .entry(service.to_string())
+            .or_insert(Entry::new(put.username, put.password)),
+    };
+    entry.url = put.url;
+    entry.tags = put.tags;
+    Ok(())
+}
+
+fn percent_decode(s: &str) -> Result<String, ApiError> {
+    let bad = || ApiError::new(400, "bad percent-encoding");
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3).ok_or_else(bad)?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| bad())?);
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| bad())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("work%2Fjira").ok().unwrap(), "work/jira");
+        assert_eq!(percent_decode("caf%C3%A9").ok().unwrap(), "café");
+        assert_eq!(percent_decode("plain").ok().unwrap(), "plain");
+        for bad in ["%", "%2", "%zz", "%FF"] {
+            assert!(percent_decode(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn limiter_counts_each_client_separately() {
+        let mut limiter = AuthLimiter::default();
+        let (noisy, quiet) = (
+            Some("127.0.0.1".parse().unwrap()),
+            Some("::1".parse().unwrap()),
+        );
+        for _ in 0..MAX_AUTH_FAILURES - 1 {
+            limiter.fail(noisy);
+        }
+        assert_eq!(limiter.locked_for(noisy), None);
+        limiter.fail(noisy);
+        let wait = limiter.locked_for(noisy).unwrap();
+        assert!(wait > Duration::ZERO && wait <= AUTH_WINDOW);
+        assert_eq!(limiter.locked_for(quiet), None);
+        assert_eq!(limiter.locked_for(None), None);
+    }
+}
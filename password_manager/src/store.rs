@@ -0,0 +1,337 @@
+use crate::agestore;
+use crate::attachments::{self, Attachment};
+use crate::config::{self, Backend};
+use crate::crypto::{Envelope, VaultKey, VAULT_FORMAT};
+use crate::derive::Derivation;
+use crate::keyfile::KeyFile;
+use crate::policy::Policy;
+use crate::secret::{SecretBytes, SecretString};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    pub username: String,
+    /// Empty for derived entries, which store no secret.
+    #[serde(default, skip_serializing_if = "SecretString::is_empty")]
+    pub password: SecretString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// When `password` was last set. Missing on entries saved before this
+    /// was tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_changed: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_every_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    /// Set for entries whose password is recomputed from the master
+    /// secret instead of stored. See [`crate::derive`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived: Option<Derivation>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favourite: bool,
+    /// When the entry was last viewed, and how often. See [`crate::usage`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub use_count: u32,
+    /// Passwords this entry no longer uses, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<PastPassword>,
+    /// Free-form secret text, edited with `$EDITOR`. See [`crate::editor`].
+    #[serde(default, skip_serializing_if = "SecretString::is_empty")]
+    pub notes: SecretString,
+    /// Named secrets besides the password, such as a PIN or recovery code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, SecretString>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// A retired password, kept so an old login can still be recovered.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PastPassword {
+    pub password: SecretString,
+    pub retired: DateTime<Utc>,
+    /// The entry it came from, when it was merged in from another one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_from: Option<String>,
+}
+
+impl Entry {
+    pub fn new(username: String, password: SecretString) -> Self {
+        Self {
+            username,
+            password,
+            url: None,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+            password_changed: Some(Utc::now()),
+            rotate_every_days: None,
+            expires: None,
+            policy: None,
+            derived: None,
+            favourite: false,
+            last_used: None,
+            use_count: 0,
+            history: Vec::new(),
+            notes: SecretString::default(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// An entry that records only how to derive its password.
+    pub fn derived(login: String, derivation: Derivation) -> Self {
+        Self {
+            derived: Some(derivation),
+            ..Self::new(login, SecretString::default())
+        }
+    }
+
+    /// Replaces the password and restarts the rotation clock. The old
+    /// password goes to `history`; a derived entry becomes a stored one.
+    pub fn set_password(&mut self, password: SecretString) {
+        let old = std::mem::replace(&mut self.password, password);
+        self.retire(old, None);
+        self.derived = None;
+        self.password_changed = Some(Utc::now());
+    }
+
+    /// Makes this a derived entry for `login`. A stored password goes to
+    /// `history`, as with [`Entry::set_password`].
+    pub fn set_derived(&mut self, login: String, derivation: Derivation) {
+        let old = std::mem::take(&mut self.password);
+        self.retire(old, None);
+        self.username = login;
+        self.derived = Some(derivation);
+        self.password_changed = Some(Utc::now());
+    }
+
+    /// Adds `password` to `history` unless it is empty or already the
+    /// current password.
+    pub fn retire(&mut self, password: SecretString, merged_from: Option<String>) {
+        if password.is_empty() || password.expose() == self.password.expose() {
+            return;
+        }
+        self.history.push(PastPassword {
+            password,
+            retired: Utc::now(),
+            merged_from,
+        });
+    }
+}
+
+/// Entries keyed by service name. A `BTreeMap` keeps listings and the
+/// on-disk JSON in a stable, alphabetical order.
+pub type PasswordStore = BTreeMap<String, Entry>;
+
+/// Whether `service` is inside `folder`. `work/github` is in folder `work`;
+/// folders nest, so `work/ops/db` is in both `work` and `work/ops`.
+pub fn in_folder(service: &str, folder: &str) -> bool {
+    service
+        .strip_prefix(folder.trim_end_matches('/'))
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// The entries plus, for an encrypted vault, the key needed to write them
+/// back.
+pub struct Vault {
+    pub entries: PasswordStore,
+    pub key: Option<VaultKey>,
+}
+
+impl Vault {
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some() || config::current().backend == Backend::Age
+    }
+
+    /// Removes `service`, saves with `save` and then deletes its attachment
+    /// blobs. Every way of deleting an entry goes through here, so no blob
+    /// is left behind, and none is deleted while a saved vault still
+    /// refers to it. Returns false if there was no such entry.
+    pub fn delete(
+        &mut self,
+        service: &str,
+        save: impl FnOnce(&mut Vault) -> io::Result<()>,
+    ) -> io::Result<bool> {
+        let Some(entry) = self.entries.remove(service) else {
+            return Ok(false);
+        };
+        if let Err(e) = save(self) {
+            self.entries.insert(service.to_string(), entry);
+            return Err(e);
+        }
+        // A malformed id names no blob of ours, so there is nothing to delete.
+        for attachment in entry
+            .attachments
+            .iter()
+            .filter(|a| attachments::valid_id(&a.id))
+        {
+            attachments::remove_blob(attachment)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Loads the selected vault's file, which is either a plain JSON map of entries or an
+/// encrypted [`Envelope`]. `master_password` is only called for the latter,
+/// and `key_file` is only used if the envelope requires one.
+pub fn load_store(
+    key_file: Option<&KeyFile>,
+    master_password: impl FnOnce() -> io::Result<SecretString>,
+) -> io::Result<Vault> {
+    if config::current().backend == Backend::Age {
+        return Ok(Vault {
+            entries: agestore::load()?,
+            key: None,
+        });
+    }
+    let path = &config::current().path;
+    if !path.exists() {
+        return Ok(Vault {
+            entries: BTreeMap::new(),
+            key: None,
+        });
+    }
+    let raw = fs::read(path)?;
+    // Anything unreadable is an error, never an empty vault: the next save
+    // would otherwise overwrite whatever is still in the file.
+    let unreadable = |e: serde_json::Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a readable vault: {}", path.display(), e),
+        )
+    };
+    let value: serde_json::Value = serde_json::from_slice(&raw).map_err(unreadable)?;
+
+    if value.get("format").and_then(|f| f.as_str()) == Some(VAULT_FORMAT) {
+        let envelope: Envelope = serde_json::from_value(value).map_err(unreadable)?;
+        if envelope.key_file.is_none() && key_file.is_some() {
+            eprintln!("Note: this vault does not use a key file; --key-file ignored.");
+        }
+        envelope.check_key_file(key_file)?;
+        let password = master_password()?;
+        let (key, plaintext) = VaultKey::unlock(&envelope, password.expose(), key_file)?;
+        let entries = serde_json::from_slice(plaintext.expose()).map_err(unreadable)?;
+        return Ok(Vault {
+            entries,
+            key: Some(key),
+        });
+    }
+
+    let entries = serde_json::from_value(value).map_err(unreadable)?;
+    Ok(Vault { entries, key: None })
+}
+
+/// Reads the selected vault's file again with an already unlocked `key`,
+/// for picking up changes made by another process. `None` if the file is
+/// gone.
+pub fn reload_store(key: Option<&mut VaultKey>) -> io::Result<Option<PasswordStore>> {
+    if config::current().backend == Backend::Age {
+        return match config::current().path.exists() {
+            true => agestore::load().map(Some),
+            false => Ok(None),
+        };
+    }
+    let raw = match fs::read(&config::current().path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let value: serde_json::Value = serde_json::from_slice(&raw)?;
+    let encrypted = value.get("format").and_then(|f| f.as_str()) == Some(VAULT_FORMAT);
+    match key {
+        Some(key) if encrypted => {
+            let envelope: Envelope = serde_json::from_value(value)?;
+            let plaintext = key.reopen(&envelope)?;
+            Ok(Some(serde_json::from_slice(plaintext.expose())?))
+        }
+        None if !encrypted => Ok(Some(serde_json::from_value(value)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the vault was encrypted or decrypted by another process; restart to open it again",
+        )),
+    }
+}
+
+/// A digest of the selected vault as it is on disk, which changes with
+/// every write. `None` if there is nothing there yet.
+pub fn fingerprint() -> io::Result<Option<[u8; 32]>> {
+    if config::current().backend == Backend::Age {
+        return agestore::fingerprint();
+    }
+    match fs::read(&config::current().path) {
+        Ok(raw) => Ok(Some(Sha256::digest(raw).into())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_store(vault: &Vault) -> io::Result<()> {
+    if config::current().backend == Backend::Age {
+        return agestore::save(&vault.entries);
+    }
+    let bytes = match &vault.key {
+        Some(key) => {
+            let plaintext = SecretBytes::new(serde_json::to_vec(&vault.entries)?);
+            serde_json::to_vec_pretty(&key.seal(plaintext.expose())?)?
+        }
+        None => serde_json::to_vec_pretty(&vault.entries)?,
+    };
+    let path = &config::current().path;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        config::create_private_dir(dir)?;
+    }
+    write_atomic(path, &bytes)
+}
+
+/// Writes `bytes` to a sibling temp file, syncs it and renames it over
+/// `path`. A crash at any point leaves either the old or the new file,
+/// never a truncated one. The temp file's name is unique, so two
+/// processes saving at once can't write into the same one.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}-{:016x}.tmp",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let tmp = PathBuf::from(tmp);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    let written = file.write_all(bytes).and_then(|_| file.sync_all());
+    drop(file);
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
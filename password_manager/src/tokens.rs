@@ -0,0 +1,152 @@
+use crate::config;
+use crate::crypto;
+use crate::store::{self, write_atomic};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// API tokens for `serve`. Only SHA-256 hashes are stored; a token is shown
+/// once, when it's created.
+pub const TOKENS_FILE: &str = "tokens.json";
+
+fn tokens_path() -> PathBuf {
+    config::current().sidecar(TOKENS_FILE)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Token {
+    pub name: String,
+    hash: String,
+    pub access: Access,
+    /// Folders (service name prefixes before a `/`) the token may see.
+    /// Empty means the whole vault.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub folders: Vec<String>,
+}
+
+impl Token {
+    /// Whether `service` is inside one of the token's folders, see
+    /// [`store::in_folder`].
+    pub fn can_see(&self, service: &str) -> bool {
+        self.folders.is_empty()
+            || self
+                .folders
+                .iter()
+                .any(|folder| store::in_folder(service, folder))
+    }
+
+    pub fn can_write(&self) -> bool {
+        self.access == Access::ReadWrite
+    }
+}
+
+fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn load() -> io::Result<Vec<Token>> {
+    let path = tokens_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+pub fn save(tokens: &[Token]) -> io::Result<()> {
+    let path = tokens_path();
+    // Tokens can be created before the vault is first saved.
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        config::create_private_dir(dir)?;
+    }
+    write_atomic(&path, &serde_json::to_vec_pretty(tokens)?)
+}
+
+/// Creates a token and returns it together with its secret value.
+pub fn create(name: String, access: Access, folders: Vec<String>) -> (Token, String) {
+    let secret: String = format!(
+        "pm_{}",
+        crypto::random_bytes(32)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    );
+    let token = Token {
+        name,
+        hash: hash(&secret),
+        access,
+        folders,
+    };
+    (token, secret)
+}
+
+/// Looks up the token a bearer value belongs to.
+pub fn authenticate(tokens: Vec<Token>, presented: &str) -> Option<Token> {
+    let presented = hash(presented);
+    tokens.into_iter().find(|t| t.hash == presented)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_hash_is_kept_and_it_authenticates() {
+        let (token, secret) = create("ci".to_string(), Access::ReadOnly, Vec::new());
+        assert!(secret.starts_with("pm_"));
+        assert_eq!(secret.len(), 3 + 64);
+        let stored = serde_json::to_string(&token).unwrap();
+        assert!(!stored.contains(&secret[3..]));
+
+        let (other, other_secret) = create("deploy".to_string(), Access::ReadWrite, Vec::new());
+        assert_ne!(secret, other_secret);
+        let tokens = vec![token, other];
+        let found = authenticate(tokens, &other_secret).unwrap();
+        assert_eq!(found.name, "deploy");
+        assert!(found.can_write());
+    }
+
+    #[test]
+    fn unknown_and_altered_secrets_are_refused() {
+        let (token, secret) = create("ci".to_string(), Access::ReadOnly, Vec::new());
+        let tokens = || {
+            vec![Token {
+                name: token.name.clone(),
+                hash: token.hash.clone(),
+                access: token.access,
+                folders: Vec::new(),
+            }]
+        };
+        assert!(authenticate(tokens(), &secret).is_some());
+        assert!(authenticate(tokens(), "").is_none());
+        assert!(authenticate(tokens(), &secret.to_uppercase()).is_none());
+        assert!(authenticate(tokens(), &secret[..secret.len() - 1]).is_none());
+        assert!(authenticate(tokens(), &token.hash).is_none());
+        assert!(authenticate(Vec::new(), &secret).is_none());
+    }
+
+    #[test]
+    fn folders_limit_what_a_token_sees() {
+        let (all, _) = create("all".to_string(), Access::ReadOnly, Vec::new());
+        assert!(all.can_see("github") && all.can_see("work/jira"));
+        assert!(!all.can_write());
+        let folders = vec!["work".to_string(), "home/media".to_string()];
+        let (some, _) = create("some".to_string(), Access::ReadOnly, folders);
+        assert!(some.can_see("work/jira"));
+        assert!(some.can_see("home/media/netflix"));
+        assert!(!some.can_see("home/bank"));
+        assert!(!some.can_see("github"));
+        assert!(!some.can_see("workshop"));
+    }
+}
@@ -0,0 +1,679 @@
+use crate::audit::{self, Event};
+use crate::display;
+use crate::rotation;
+use crate::search;
+use crate::secret::SecretString;
+use crate::store::{Entry, Vault};
+use crate::usage;
+use crate::watch::{Change, Watch};
+use ratatui::crossterm::event::{
+    self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+use std::time::{Duration, Instant};
+
+const HELP: &str = "/ filter  a add  e edit  d delete  f favourite  r reveal  c copy  q quit";
+const FIELDS: [&str; 5] = ["Service", "Username", "Password", "URL", "Tags"];
+const PASSWORD_FIELD: usize = 2;
+
+/// Whether the full-screen UI can run here. Dumb terminals and redirected
+/// output get the `dialoguer` menu instead.
+pub fn supported() -> bool {
+    use std::io::IsTerminal;
+    io::stdout().is_terminal()
+        && io::stdin().is_terminal()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+}
+
+pub fn run(vault: &mut Vault, alphabetical: bool) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let watch = Watch::start(vault)?;
+    let result = App::new(vault, watch, alphabetical).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// Add/edit form. Fields are plain strings while editing; the password is
+/// moved into a `SecretString` as soon as it's saved.
+struct Form {
+    /// Service being edited, `None` when adding.
+    original: Option<String>,
+    fields: [String; 5],
+    focus: usize,
+}
+
+enum Mode {
+    List,
+    Filter,
+    Form(Form),
+    ConfirmDelete(String),
+    /// Asking for the derivation master password of a derived entry.
+    DeriveSecret {
+        service: String,
+        input: String,
+        copy: bool,
+    },
+}
+
+struct Revealed {
+    service: String,
+    password: SecretString,
+    at: Instant,
+}
+
+struct App<'a> {
+    vault: &'a mut Vault,
+    watch: Watch,
+    alphabetical: bool,
+    filter: String,
+    mode: Mode,
+    list: ListState,
+    revealed: Option<Revealed>,
+    clipboard_at: Option<Instant>,
+    status: String,
+}
+
+impl<'a> App<'a> {
+    fn new(vault: &'a mut Vault, watch: Watch, alphabetical: bool) -> Self {
+        let status = rotation::banner(&vault.entries, chrono::Local::now().date_naive())
+            .and_then(|banner| banner.lines().next().map(str::to_string))
+            .unwrap_or_else(|| HELP.to_string());
+        Self {
+            vault,
+            watch,
+            alphabetical,
+            filter: String::new(),
+            mode: Mode::List,
+            list: ListState::default().with_selected(Some(0)),
+            revealed: None,
+            clipboard_at: None,
+            status,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(Duration::from_millis(250))?
+                && let TermEvent::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !self.on_key(key)?
+            {
+                break;
+            }
+            self.tick()?;
+        }
+        if self.clipboard_at.is_some() {
+            display::clear_clipboard()?;
+        }
+        Ok(())
+    }
+
+    /// Services shown in the list: everything, favourites and most used
+    /// first, or the fuzzy matches for the filter, best first.
+    fn visible(&self) -> Vec<String> {
+        if self.filter.is_empty() {
+            usage::ordered(&self.vault.entries, self.alphabetical)
+                .into_iter()
+                .cloned()
+                .collect()
+        } else {
+            search::search(&self.vault.entries, &self.filter)
+                .into_iter()
+                .map(|m| m.service.to_string())
+                .collect()
+        }
+    }
+
+    fn selected(&self) -> Option<String> {
+        let visible = self.visible();
+        self.list.selected().and_then(|i| visible.get(i).cloned())
+    }
+
+    /// Keeps the cursor on `service` after the list was reordered.
+    fn select(&mut self, service: &str) {
+        let index = self.visible().iter().position(|s| s == service);
+        self.list.select(index);
+    }
+
+    /// Picks up changes made to the vault elsewhere, hides revealed
+    /// passwords and clears the clipboard once their time is up.
+    fn tick(&mut self) -> io::Result<()> {
+        match self.watch.poll(self.vault) {
+            Ok(changes) => self.on_changes(&changes),
+            Err(e) => self.status = format!("Could not reload the vault: {}", e),
+        }
+        let limit = Duration::from_secs(display::CLEAR_AFTER_SECS);
+        if self
+            .revealed
+            .as_ref()
+            .is_some_and(|r| r.at.elapsed() > limit)
+        {
+            self.revealed = None;
+        }
+        if self.clipboard_at.is_some_and(|at| at.elapsed() > limit) {
+            display::clear_clipboard()?;
+            self.clipboard_at = None;
+            self.status = "Clipboard cleared.".to_string();
+        }
+        Ok(())
+    }
+
+    /// Saves, merging in changes made elsewhere first.
+    fn save(&mut self) -> io::Result<()> {
+        let changes = self.watch.save(self.vault)?;
+        self.on_changes(&changes);
+        Ok(())
+    }
+
+    /// Reports changes from elsewhere, and drops anything on screen that
+    /// refers to an entry which changed or is gone.
+    fn on_changes(&mut self, changes: &[Change]) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut status: Vec<String> = changes.iter().map(Change::to_string).collect();
+        let touched = |service: &str| changes.iter().any(|c| c.service() == service);
+        if self.revealed.as_ref().is_some_and(|r| touched(&r.service)) {
+            self.revealed = None;
+            status.push("Press r to reveal it again.".to_string());
+        }
+        match &mut self.mode {
+            Mode::Form(form) => {
+                if let Some(original) = &form.original
+                    && !self.vault.entries.contains_key(original)
+                {
+                    form.original = None;
+                    status.push("Saving the form will add it again.".to_string());
+                }
+            }
+            Mode::ConfirmDelete(service) | Mode::DeriveSecret { service, .. }
+                if !self.vault.entries.contains_key(service) =>
+            {
+                self.mode = Mode::List;
+            }
+            _ => {}
+        }
+        self.status = status.join(" ");
+        self.clamp_selection();
+    }
+
+    /// Returns `false` to quit.
+    fn on_key(&mut self, key: KeyEvent) -> io::Result<bool> {
+        match std::mem::replace(&mut self.mode, Mode::List) {
+            Mode::List => return self.on_list_key(key),
+            Mode::Filter => match key.code {
+                KeyCode::Esc => self.filter.clear(),
+                KeyCode::Enter => {}
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.mode = Mode::Filter;
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.mode = Mode::Filter;
+                }
+                _ => self.mode = Mode::Filter,
+            },
+            Mode::Form(form) => self.on_form_key(form, key)?,
+            Mode::ConfirmDelete(service) => {
+                if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    if self.vault.entries.contains_key(&service) {
+                        audit::record(Event::Delete, Some(&service))?;
+                        let mut changes = Vec::new();
+                        self.vault.delete(&service, |vault| {
+                            changes = self.watch.save(vault)?;
+                            Ok(())
+                        })?;
+                        self.on_changes(&changes);
+                        self.status = format!("Deleted '{}'.", service);
+                    }
+                } else {
+                    self.status = "Deletion cancelled.".to_string();
+                }
+            }
+            Mode::DeriveSecret {
+                service,
+                mut input,
+                copy,
+            } => match key.code {
+                KeyCode::Esc => self.status = "Cancelled.".to_string(),
+                KeyCode::Enter => {
+                    let input = SecretString::from(input);
+                    let entry = &self.vault.entries[&service];
+                    let derivation = entry.derived.as_ref().expect("derived entry");
+                    match derivation.derive(input.expose(), &entry.username) {
+                        Ok(password) => {
+                            self.reveal(service, SecretString::from(password.to_string()), copy)?
+                        }
+                        Err(e) => self.status = e,
+                    }
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.mode = Mode::DeriveSecret {
+                        service,
+                        input,
+                        copy,
+                    };
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.mode = Mode::DeriveSecret {
+                        service,
+                        input,
+                        copy,
+                    };
+                }
+                _ => {
+                    self.mode = Mode::DeriveSecret {
+                        service,
+                        input,
+                        copy,
+                    }
+                }
+            },
+        }
+        self.clamp_selection();
+        Ok(true)
+    }
+
+    fn on_list_key(&mut self, key: KeyEvent) -> io::Result<bool> {
+        let count = self.visible().len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+            KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                let next = self.list.selected().map_or(0, |i| (i + 1).min(count - 1));
+                self.list.select(Some(next));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                let prev = self.list.selected().map_or(0, |i| i.saturating_sub(1));
+                self.list.select(Some(prev));
+            }
+            KeyCode::Char('/') => self.mode = Mode::Filter,
+            KeyCode::Char('a') => {
+                self.mode = Mode::Form(Form {
+                    original: None,
+                    fields: Default::default(),
+                    focus: 0,
+                })
+            }
+            KeyCode::Char('e') => {
+                if let Some(service) = self.selected() {
+                    let entry = &self.vault.entries[&service];
+                    self.mode = Mode::Form(Form {
+                        original: Some(service.clone()),
+                        fields: [
+                            service,
+                            entry.username.clone(),
+                            String::new(),
+                            entry.url.clone().unwrap_or_default(),
+                            entry.tags.join(", "),
+                        ],
+                        focus: 1,
+                    });
+                }
+            }
+            KeyCode::Char('f') => {
+                if let Some(service) = self.selected() {
+                    let entry = self.vault.entries.get_mut(&service).expect("listed entry");
+                    entry.favourite = !entry.favourite;
+                    self.status = if entry.favourite {
+                        format!("'{}' marked as a favourite.", service)
+                    } else {
+                        format!("'{}' is no longer a favourite.", service)
+                    };
+                    self.save()?;
+                    self.select(&service);
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(service) = self.selected() {
+                    self.mode = Mode::ConfirmDelete(service);
+                }
+            }
+            KeyCode::Char(c @ ('r' | 'c')) => {
+                let copy = c == 'c';
+                if let Some(service) = self.selected() {
+                    if !copy && self.revealed.as_ref().is_some_and(|r| r.service == service) {
+                        self.revealed = None;
+                    } else if self.vault.entries[&service].derived.is_some() {
+                        self.mode = Mode::DeriveSecret {
+                            service,
+                            input: String::new(),
+                            copy,
+                        };
+                    } else {
+                        let stored = self.vault.entries[&service].password.expose().to_string();
+                        self.reveal(service, SecretString::from(stored), copy)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn reveal(&mut self, service: String, password: SecretString, copy: bool) -> io::Result<()> {
+        audit::record(Event::View, Some(&service))?;
+        if let Some(entry) = self.vault.entries.get_mut(&service) {
+            usage::record(entry);
+            self.save()?;
+            self.select(&service);
+        }
+        if copy {
+            display::copy_to_clipboard(password.expose())?;
+            self.clipboard_at = Some(Instant::now());
+            self.status = format!(
+                "Copied password for '{}'; clipboard clears in {}s.",
+                service,
+                display::CLEAR_AFTER_SECS
+            );
+        } else {
+            self.status = format!(
+                "Revealed for {}s; press r again to hide.",
+                display::CLEAR_AFTER_SECS
+            );
+            self.revealed = Some(Revealed {
+                service,
+                password,
+                at: Instant::now(),
+            });
+        }
+        Ok(())
+    }
+
+    fn on_form_key(&mut self, mut form: Form, key: KeyEvent) -> io::Result<()> {
+        match key.code {
+            KeyCode::Esc => {
+                self.status = "Cancelled.".to_string();
+                return Ok(());
+            }
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % FIELDS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.focus = (form.focus + FIELDS.len() - 1) % FIELDS.len()
+            }
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let policy = form
+                    .original
+                    .as_ref()
+                    .and_then(|s| self.vault.entries.get(s))
+                    .and_then(|e| e.policy.as_ref());
+                match crate::policy::or_default(policy).generate(None) {
+                    Ok(password) => {
+                        form.fields[PASSWORD_FIELD] = password;
+                        self.status = "Generated a password.".to_string();
+                    }
+                    Err(e) => self.status = e,
+                }
+            }
+            KeyCode::Enter => match self.save_form(&form) {
+                Ok(service) => {
+                    self.status = format!("Saved '{}'.", service);
+                    self.filter.clear();
+                    self.select(&service);
+                    return Ok(());
+                }
+                Err(message) => self.status = message,
+            },
+            KeyCode::Backspace => {
+                form.fields[form.focus].pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].push(c),
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+        Ok(())
+    }
+
+    /// Applies the form with the same rules as the Add Entry menu. Errors
+    /// are messages for the status line.
+    fn save_form(&mut self, form: &Form) -> Result<String, String> {
+        let [service, username, password, url, tags] = &form.fields;
+        let service = service.trim().to_string();
+        if service.is_empty() {
+            return Err("Service name cannot be empty.".to_string());
+        }
+        if form.original.as_ref() != Some(&service) && self.vault.entries.contains_key(&service) {
+            return Err(format!("'{}' already exists.", service));
+        }
+        let existing = form
+            .original
+            .as_ref()
+            .and_then(|s| self.vault.entries.get(s));
+        if existing.is_none() && password.is_empty() {
+            return Err("Password cannot be empty.".to_string());
+        }
+        if !password.is_empty()
+            && let Some(policy) = existing.and_then(|e| e.policy.as_ref())
+        {
+            let problems = policy.violations(password);
+            if !problems.is_empty() {
+                return Err(format!("Rejected by policy: {}", problems.join("; ")));
+            }
+        }
+
+        let password = SecretString::from(password.clone());
+        let mut entry = match &form.original {
+            Some(original) => {
+                let mut entry = self
+                    .vault
+                    .entries
+                    .remove(original)
+                    .expect("entry being edited");
+                if !password.is_empty() {
+                    entry.set_password(password);
+                }
+                entry
+            }
+            None => Entry::new(String::new(), password),
+        };
+        entry.username = username.clone();
+        entry.url = Some(url.trim().to_string()).filter(|u| !u.is_empty());
+        entry.tags = tags
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        self.vault.entries.insert(service.clone(), entry);
+        audit::record(Event::Add, Some(&service)).map_err(|e| e.to_string())?;
+        self.save().map_err(|e| e.to_string())?;
+        Ok(service)
+    }
+
+    fn clamp_selection(&mut self) {
+        let count = self.visible().len();
+        match self.list.selected() {
+            _ if count == 0 => self.list.select(None),
+            Some(i) if i >= count => self.list.select(Some(count - 1)),
+            None => self.list.select(Some(0)),
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(1),
+            ])
+            .split(frame.area());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(rows[1]);
+
+        let filter_style = if matches!(self.mode, Mode::Filter) {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        frame.render_widget(
+            Paragraph::new(self.filter.as_str())
+                .style(filter_style)
+                .block(Block::default().borders(Borders::ALL).title("Filter (/)")),
+            rows[0],
+        );
+
+        let items: Vec<ListItem> = self
+            .visible()
+            .into_iter()
+            .map(|service| {
+                let mark = if self.vault.entries[&service].favourite {
+                    "★"
+                } else {
+                    " "
+                };
+                ListItem::new(format!("{} {}", mark, service))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Services"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, columns[0], &mut self.list);
+
+        frame.render_widget(
+            Paragraph::new(self.detail_lines())
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Details")),
+            columns[1],
+        );
+        frame.render_widget(Paragraph::new(self.status.as_str()), rows[2]);
+
+        match &self.mode {
+            Mode::Form(form) => draw_form(frame, form),
+            Mode::ConfirmDelete(service) => draw_popup(
+                frame,
+                "Delete",
+                vec![Line::from(format!("Delete entry for '{}'? (y/n)", service))],
+            ),
+            Mode::DeriveSecret { input, .. } => draw_popup(
+                frame,
+                "Derived entry",
+                vec![
+                    Line::from("Derivation master password:"),
+                    Line::from("*".repeat(input.chars().count())),
+                ],
+            ),
+            Mode::List | Mode::Filter => {}
+        }
+    }
+
+    fn detail_lines(&self) -> Vec<Line<'static>> {
+        let Some(service) = self.selected() else {
+            return vec![Line::from("No entries.")];
+        };
+        let entry = &self.vault.entries[&service];
+        let password = match &self.revealed {
+            Some(r) if r.service == service => r.password.expose().to_string(),
+            _ => display::MASK.to_string(),
+        };
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<10}", name),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ])
+        };
+        let mut lines = vec![
+            field("Service", service.clone()),
+            field("Username", entry.username.clone()),
+            field("Password", password),
+        ];
+        if let Some(url) = &entry.url {
+            lines.push(field("URL", url.clone()));
+        }
+        if !entry.tags.is_empty() {
+            lines.push(field("Tags", entry.tags.join(", ")));
+        }
+        if entry.derived.is_some() {
+            lines.push(field("Kind", "derived (nothing stored)".to_string()));
+        }
+        if let Some(policy) = &entry.policy {
+            lines.push(field("Policy", policy.summary()));
+        }
+        let today = chrono::Local::now().date_naive();
+        if let Some((due, _)) = rotation::due_date(entry, today) {
+            lines.push(field("Rotate by", due.to_string()));
+        }
+        if !entry.attachments.is_empty() {
+            let names: Vec<&str> = entry.attachments.iter().map(|a| a.name.as_str()).collect();
+            lines.push(field("Files", names.join(", ")));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(HELP));
+        lines
+    }
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let title = if form.original.is_some() {
+        "Edit entry"
+    } else {
+        "Add entry"
+    };
+    let mut lines: Vec<Line> = FIELDS
+        .iter()
+        .zip(&form.fields)
+        .enumerate()
+        .map(|(i, (name, value))| {
+            let shown = if i == PASSWORD_FIELD {
+                "*".repeat(value.chars().count())
+            } else {
+                value.clone()
+            };
+            let style = if i == form.focus {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Line::from(vec![
+                Span::raw(format!("{:<10}", name)),
+                Span::styled(format!("{} ", shown), style),
+            ])
+        })
+        .collect();
+    lines.push(Line::from(""));
+    if form.original.is_some() {
+        lines.push(Line::from("Leave Password empty to keep the current one."));
+    }
+    lines.push(Line::from(
+        "Tab next field  Ctrl-G generate  Enter save  Esc cancel",
+    ));
+    draw_popup(frame, title, lines);
+}
+
+fn draw_popup(frame: &mut Frame, title: &str, lines: Vec<Line>) {
+    let area = centered(frame.area(), 70, lines.len() as u16 + 2);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title.to_string()),
+        ),
+        area,
+    );
+}
+
+fn centered(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height: height.min(area.height),
+    }
+}
@@ -0,0 +1,120 @@
+use crate::store::{Entry, PasswordStore};
+use chrono::{DateTime, Utc};
+
+/// Notes that `entry` was just viewed.
+pub fn record(entry: &mut Entry) {
+    entry.use_count = entry.use_count.saturating_add(1);
+    entry.last_used = Some(Utc::now());
+}
+
+/// Use count weighted by how recently the entry was last used, in the
+/// spirit of Firefox's frecency buckets: a login used daily this week
+/// beats one used often last year.
+pub fn frecency(entry: &Entry, now: DateTime<Utc>) -> u64 {
+    let Some(last_used) = entry.last_used else {
+        return 0;
+    };
+    let weight = match (now - last_used).num_days() {
+        ..=4 => 100,
+        5..=14 => 70,
+        15..=31 => 50,
+        32..=90 => 30,
+        _ => 10,
+    };
+    u64::from(entry.use_count) * weight
+}
+
+/// Service names in display order: favourites first, then by frecency,
+/// ties broken alphabetically. `alphabetical` ignores usage altogether.
+pub fn ordered(store: &PasswordStore, alphabetical: bool) -> Vec<&String> {
+    let mut services: Vec<&String> = store.keys().collect();
+    if !alphabetical {
+        let now = Utc::now();
+        // Stable sort over the already alphabetical keys keeps ties in order.
+        services.sort_by_key(|service| {
+            let entry = &store[*service];
+            (!entry.favourite, std::cmp::Reverse(frecency(entry, now)))
+        });
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretString;
+    use chrono::Duration;
+
+    fn entry(uses: u32, days_ago: Option<i64>, favourite: bool, now: DateTime<Utc>) -> Entry {
+        let mut entry = Entry::new("me".to_string(), SecretString::from("pw".to_string()));
+        entry.use_count = uses;
+        entry.last_used = days_ago.map(|days| now - Duration::days(days));
+        entry.favourite = favourite;
+        entry
+    }
+
+    #[test]
+    fn frecency_weights_uses_by_recency() {
+        let now = Utc::now();
+        assert_eq!(frecency(&entry(0, None, false, now), now), 0);
+        // A count without a date (hand-edited vault) doesn't rank.
+        assert_eq!(frecency(&entry(9, None, false, now), now), 0);
+        for (days, weight) in [
+            (0, 100),
+            (4, 100),
+            (5, 70),
+            (14, 70),
+            (15, 50),
+            (31, 50),
+            (32, 30),
+            (90, 30),
+            (91, 10),
+            (3650, 10),
+        ] {
+            assert_eq!(
+                frecency(&entry(3, Some(days), false, now), now),
+                3 * weight,
+                "{} days ago",
+                days
+            );
+        }
+        // Daily use this week beats heavy use last year.
+        assert!(
+            frecency(&entry(7, Some(1), false, now), now)
+                > frecency(&entry(60, Some(400), false, now), now)
+        );
+        let max = entry(u32::MAX, Some(0), false, now);
+        assert_eq!(frecency(&max, now), u64::from(u32::MAX) * 100);
+    }
+
+    #[test]
+    fn ordered_puts_favourites_first_then_frecency_then_names() {
+        let now = Utc::now();
+        let store: PasswordStore = [
+            ("alpha", entry(0, None, false, now)),
+            ("bank", entry(2, Some(1), false, now)),
+            ("mail", entry(50, Some(1), false, now)),
+            ("old", entry(50, Some(400), false, now)),
+            ("pinned", entry(0, None, true, now)),
+            ("zeta", entry(0, None, false, now)),
+            ("busy-pin", entry(1, Some(0), true, now)),
+        ]
+        .into_iter()
+        .map(|(name, entry)| (name.to_string(), entry))
+        .collect();
+        let names = |alphabetical| -> Vec<&str> {
+            ordered(&store, alphabetical)
+                .into_iter()
+                .map(String::as_str)
+                .collect()
+        };
+        assert_eq!(
+            names(false),
+            ["busy-pin", "pinned", "mail", "old", "bank", "alpha", "zeta"]
+        );
+        assert_eq!(
+            names(true),
+            ["alpha", "bank", "busy-pin", "mail", "old", "pinned", "zeta"]
+        );
+    }
+}
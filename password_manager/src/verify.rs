@@ -0,0 +1,628 @@
+use crate::agestore;
+use crate::attachments::{self, Attachment};
+use crate::config::{self, Backend};
+use crate::crypto::{Envelope, VaultKey, VAULT_FORMAT, VAULT_VERSION};
+use crate::derive::Derivation;
+use crate::keyfile::KeyFile;
+use crate::policy::Policy;
+use crate::secret::SecretString;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chrono::{DateTime, NaiveDate};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io;
+
+const ENVELOPE_FIELDS: &[&str] = &[
+    "format",
+    "version",
+    "kdf",
+    "key_file",
+    "wrapped_key",
+    "payload",
+];
+const KDF_FIELDS: &[&str] = &["algorithm", "salt", "m_cost", "t_cost", "p_cost"];
+const SEALED_FIELDS: &[&str] = &["nonce", "ciphertext"];
+const ENTRY_FIELDS: &[&str] = &[
+    "username",
+    "password",
+    "url",
+    "tags",
+    "attachments",
+    "password_changed",
+    "rotate_every_days",
+    "expires",
+    "policy",
+    "derived",
+    "favourite",
+    "last_used",
+    "use_count",
+    "history",
+    "notes",
+    "fields",
+];
+const ATTACHMENT_FIELDS: &[&str] = &["id", "name", "size", "key"];
+const HISTORY_FIELDS: &[&str] = &["password", "retired", "merged_from"];
+/// OTP secrets are kept as custom fields with one of these names, or as an
+/// `otpauth://` URI in any field.
+const OTP_FIELDS: &[&str] = &["otp", "totp", "hotp"];
+const OTPAUTH: &str = "otpauth://";
+
+/// What `verify` found: each problem prefixed with where it is, e.g.
+/// `github.tags[1]: expected a string`.
+pub struct Report {
+    pub encrypted: bool,
+    pub entries: usize,
+    pub problems: Vec<String>,
+}
+
+/// Checks the selected vault: the envelope and its authentication tags if
+/// it is encrypted, then every entry against the schema. Only a missing
+/// file or a failure to ask for the password is an `Err`; anything wrong
+/// with the vault itself ends up in the report.
+pub fn verify(
+    key_file: Option<&KeyFile>,
+    master_password: impl FnOnce() -> io::Result<SecretString>,
+) -> io::Result<Report> {
+    let mut report = Report {
+        encrypted: false,
+        entries: 0,
+        problems: Vec::new(),
+    };
+    if config::current().backend == Backend::Age {
+        verify_age(&mut report)?;
+        return Ok(report);
+    }
+    let path = &config::current().path;
+    let raw = fs::read(path)?;
+    let value: Value = match serde_json::from_slice(&raw) {
+        Ok(value) => value,
+        Err(e) => {
+            report.problems.push(format!(
+                "{}:{}:{}: not valid JSON: {}",
+                path.display(),
+                e.line(),
+                e.column(),
+                e
+            ));
+            return Ok(report);
+        }
+    };
+
+    if value.get("format").and_then(Value::as_str) == Some(VAULT_FORMAT) {
+        report.encrypted = true;
+        let before = report.problems.len();
+        check_envelope(&value, &mut report.problems);
+        if report.problems.len() > before {
+            return Ok(report);
+        }
+        let envelope: Envelope = match serde_json::from_value(value) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                report.problems.push(format!("envelope: {}", e));
+                return Ok(report);
+            }
+        };
+        if let Err(e) = envelope.check_key_file(key_file) {
+            report.problems.push(format!("key_file: {}", e));
+            return Ok(report);
+        }
+        let password = master_password()?;
+        let plaintext = match VaultKey::unlock(&envelope, password.expose(), key_file) {
+            Ok((_, plaintext)) => plaintext,
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                report.problems.push(
+                    "wrapped_key: authentication failed (wrong master password, or the key was modified)"
+                        .to_string(),
+                );
+                return Ok(report);
+            }
+            Err(e) => {
+                report.problems.push(format!("payload: {}", e));
+                return Ok(report);
+            }
+        };
+        match serde_json::from_slice(plaintext.expose()) {
+            Ok(entries) => check_entries(&entries, &mut report),
+            Err(e) => report.problems.push(format!(
+                "payload: decrypted entries are not valid JSON: {}",
+                e
+            )),
+        }
+    } else {
+        check_entries(&value, &mut report);
+    }
+    Ok(report)
+}
+
+/// An age store has no envelope; each entry file is authenticated and
+/// checked on its own.
+fn verify_age(report: &mut Report) -> io::Result<()> {
+    report.encrypted = true;
+    if let Err(e) = agestore::check_recipients() {
+        report.problems.push(e.to_string());
+    }
+    for (service, plaintext) in agestore::read_each()? {
+        report.entries += 1;
+        let value = plaintext.and_then(|p| Ok(serde_json::from_slice(p.expose())?));
+        match value {
+            Ok(value) => check_entry(&service, &value, &mut report.problems),
+            Err(e) => report.problems.push(format!("{}: {}", service, e)),
+        }
+    }
+    Ok(())
+}
+
+fn check_envelope(value: &Value, problems: &mut Vec<String>) {
+    let Some(envelope) = object(value, "envelope", problems) else {
+        return;
+    };
+    unknown_fields(envelope, ENVELOPE_FIELDS, "envelope", problems);
+    match envelope.get("version").and_then(Value::as_u64) {
+        Some(v) if v == u64::from(VAULT_VERSION) => {}
+        Some(v) => problems.push(format!("version: unsupported vault version {}", v)),
+        None => problems.push("version: missing or not a number".to_string()),
+    }
+
+    match envelope.get("kdf") {
+        Some(kdf) => {
+            if let Some(kdf) = object(kdf, "kdf", problems) {
+                unknown_fields(kdf, KDF_FIELDS, "kdf", problems);
+                if kdf.get("algorithm").and_then(Value::as_str) != Some("argon2id") {
+                    problems.push("kdf.algorithm: expected \"argon2id\"".to_string());
+                }
+                base64_field(kdf, "salt", "kdf.salt", None, problems);
+                for cost in ["m_cost", "t_cost", "p_cost"] {
+                    if !kdf
+                        .get(cost)
+                        .and_then(Value::as_u64)
+                        .is_some_and(|n| n > 0 && n <= u64::from(u32::MAX))
+                    {
+                        problems.push(format!("kdf.{}: expected a positive integer", cost));
+                    }
+                }
+            }
+        }
+        None => problems.push("kdf: missing".to_string()),
+    }
+
+    if let Some(check) = envelope.get("key_file")
+        && !check.is_string()
+    {
+        problems.push("key_file: expected a string".to_string());
+    }
+
+    for field in ["wrapped_key", "payload"] {
+        let Some(sealed) = envelope.get(field) else {
+            problems.push(format!("{}: missing", field));
+            continue;
+        };
+        if let Some(sealed) = object(sealed, field, problems) {
+            unknown_fields(sealed, SEALED_FIELDS, field, problems);
+            base64_field(
+                sealed,
+                "nonce",
+                &format!("{}.nonce", field),
+                Some(24),
+                problems,
+            );
+            base64_field(
+                sealed,
+                "ciphertext",
+                &format!("{}.ciphertext", field),
+                None,
+                problems,
+            );
+        }
+    }
+}
+
+fn check_entries(value: &Value, report: &mut Report) {
+    let Some(entries) = object(value, "vault", &mut report.problems) else {
+        return;
+    };
+    report.entries = entries.len();
+    for (service, entry) in entries {
+        check_entry(service, entry, &mut report.problems);
+    }
+}
+
+fn check_entry(service: &str, value: &Value, problems: &mut Vec<String>) {
+    let Some(entry) = object(value, service, problems) else {
+        return;
+    };
+    let at = |field: &str| format!("{}.{}", service, field);
+    unknown_fields(entry, ENTRY_FIELDS, service, problems);
+
+    match entry.get("username") {
+        Some(Value::String(_)) => {}
+        Some(_) => problems.push(format!("{}: expected a string", at("username"))),
+        None => problems.push(format!("{}: missing", at("username"))),
+    }
+
+    // Empty passwords are not written out, so a missing one is empty too.
+    let derived = entry.get("derived").filter(|d| !d.is_null());
+    match entry.get("password") {
+        Some(Value::String(p)) if p.is_empty() && derived.is_none() => {
+            problems.push(format!("{}: empty password", at("password")))
+        }
+        Some(Value::String(p)) if !p.is_empty() && derived.is_some() => problems.push(format!(
+            "{}: derived entries must not store a password",
+            at("password")
+        )),
+        Some(Value::String(_)) => {}
+        Some(_) => problems.push(format!("{}: expected a string", at("password"))),
+        None if derived.is_none() => problems.push(format!("{}: empty password", at("password"))),
+        None => {}
+    }
+
+    optional(entry, "url", &at("url"), problems, |v| {
+        v.is_string()
+            .then_some(())
+            .ok_or("expected a string".into())
+    });
+    optional(entry, "tags", &at("tags"), problems, string_array);
+    optional(entry, "notes", &at("notes"), problems, |v| {
+        v.is_string()
+            .then_some(())
+            .ok_or("expected a string".into())
+    });
+    optional(entry, "fields", &at("fields"), problems, |v| {
+        match v.as_object() {
+            Some(fields) => match fields.iter().find(|(_, value)| !value.is_string()) {
+                Some((name, _)) => Err(format!("'{}' is not a string", name)),
+                None => Ok(()),
+            },
+            None => Err("expected a map of names to strings".into()),
+        }
+    });
+    if let Some(Value::Object(fields)) = entry.get("fields") {
+        for (name, value) in fields {
+            if let Some(value) = value.as_str()
+                && (is_otp_field(name) || value.starts_with(OTPAUTH))
+                && let Err(e) = otp_secret(value)
+            {
+                problems.push(format!("{}.{}: {}", at("fields"), name, e));
+            }
+        }
+    }
+    optional(entry, "favourite", &at("favourite"), problems, |v| {
+        v.is_boolean()
+            .then_some(())
+            .ok_or("expected true or false".into())
+    });
+    optional(entry, "use_count", &at("use_count"), problems, |v| {
+        u32_value(v, 0)
+    });
+    optional(
+        entry,
+        "rotate_every_days",
+        &at("rotate_every_days"),
+        problems,
+        |v| u32_value(v, 1),
+    );
+    optional(
+        entry,
+        "password_changed",
+        &at("password_changed"),
+        problems,
+        timestamp,
+    );
+    optional(entry, "last_used", &at("last_used"), problems, timestamp);
+    optional(entry, "expires", &at("expires"), problems, |v| {
+        v.as_str()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            .map(|_| ())
+            .ok_or("expected a date like 2025-12-31".into())
+    });
+    optional(entry, "policy", &at("policy"), problems, |v| {
+        serde_json::from_value::<Policy>(v.clone())
+            .map_err(|e| e.to_string())?
+            .check()
+    });
+    optional(entry, "derived", &at("derived"), problems, |v| {
+        serde_json::from_value::<Derivation>(v.clone())
+            .map_err(|e| e.to_string())?
+            .check()
+    });
+
+    if let Some(attachments) = entry.get("attachments") {
+        match attachments.as_array() {
+            Some(attachments) => {
+                for (i, attachment) in attachments.iter().enumerate() {
+                    check_attachment(
+                        &format!("{}[{}]", at("attachments"), i),
+                        attachment,
+                        problems,
+                    );
+                }
+            }
+            None => problems.push(format!("{}: expected a list", at("attachments"))),
+        }
+    }
+    if let Some(history) = entry.get("history") {
+        match history.as_array() {
+            Some(history) => {
+                for (i, past) in history.iter().enumerate() {
+                    let at = format!("{}[{}]", at("history"), i);
+                    let Some(past) = object(past, &at, problems) else {
+                        continue;
+                    };
+                    unknown_fields(past, HISTORY_FIELDS, &at, problems);
+                    match past.get("password") {
+                        Some(Value::String(p)) if !p.is_empty() => {}
+                        _ => problems.push(format!("{}.password: expected a non-empty string", at)),
+                    }
+                    if let Err(e) = past.get("retired").map_or(Err("missing".into()), timestamp) {
+                        problems.push(format!("{}.retired: {}", at, e));
+                    }
+                }
+            }
+            None => problems.push(format!("{}: expected a list", at("history"))),
+        }
+    }
+}
+
+fn check_attachment(at: &str, value: &Value, problems: &mut Vec<String>) {
+    let Some(fields) = object(value, at, problems) else {
+        return;
+    };
+    unknown_fields(fields, ATTACHMENT_FIELDS, at, problems);
+    base64_field(fields, "key", &format!("{}.key", at), Some(32), problems);
+    match serde_json::from_value::<Attachment>(value.clone()) {
+        Ok(attachment) if !attachments::valid_id(&attachment.id) => {
+            problems.push(format!("{}.id: expected 32 lowercase hex digits", at))
+        }
+        Ok(attachment) => {
+            if !attachment.blob_exists() {
+                problems.push(format!(
+                    "{}: encrypted blob for '{}' is missing",
+                    at, attachment.name
+                ));
+            }
+        }
+        Err(e) => problems.push(format!("{}: {}", at, e)),
+    }
+}
+
+fn object<'a>(
+    value: &'a Value,
+    at: &str,
+    problems: &mut Vec<String>,
+) -> Option<&'a Map<String, Value>> {
+    let object = value.as_object();
+    if object.is_none() {
+        problems.push(format!("{}: expected an object", at));
+    }
+    object
+}
+
+fn unknown_fields(
+    object: &Map<String, Value>,
+    known: &[&str],
+    at: &str,
+    problems: &mut Vec<String>,
+) {
+    for key in object.keys().filter(|k| !known.contains(&k.as_str())) {
+        problems.push(format!("{}.{}: unknown field", at, key));
+    }
+}
+
+/// Runs `check` on `field` if it is present and not null.
+fn optional(
+    object: &Map<String, Value>,
+    field: &str,
+    at: &str,
+    problems: &mut Vec<String>,
+    check: impl FnOnce(&Value) -> Result<(), String>,
+) {
+    if let Some(value) = object.get(field).filter(|v| !v.is_null())
+        && let Err(e) = check(value)
+    {
+        problems.push(format!("{}: {}", at, e));
+    }
+}
+
+fn base64_field(
+    object: &Map<String, Value>,
+    field: &str,
+    at: &str,
+    len: Option<usize>,
+    problems: &mut Vec<String>,
+) {
+    let decoded = object
+        .get(field)
+        .and_then(Value::as_str)
+        .map(|s| B64.decode(s));
+    match (decoded, len) {
+        (None, _) => problems.push(format!("{}: missing or not a string", at)),
+        (Some(Err(_)), _) => problems.push(format!("{}: not valid base64", at)),
+        (Some(Ok(bytes)), Some(len)) if bytes.len() != len => problems.push(format!(
+            "{}: expected {} bytes, got {}",
+            at,
+            len,
+            bytes.len()
+        )),
+        _ => {}
+    }
+}
+
+fn is_otp_field(name: &str) -> bool {
+    OTP_FIELDS
+        .iter()
+        .any(|otp| name.trim().eq_ignore_ascii_case(otp))
+}
+
+/// Checks an OTP secret: base32 (RFC 4648, spaces and padding allowed, any
+/// case) or an `otpauth://` URI whose `secret` parameter is.
+fn otp_secret(value: &str) -> Result<(), String> {
+    let secret = match value.strip_prefix(OTPAUTH) {
+        Some(rest) => rest
+            .split_once('?')
+            .into_iter()
+            .flat_map(|(_, query)| query.split('&'))
+            .find_map(|param| param.strip_prefix("secret="))
+            .ok_or("otpauth URI has no secret parameter")?,
+        None => value,
+    };
+    let digits: Vec<u8> = secret
+        .bytes()
+        .filter(|b| *b != b' ')
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+    let end = digits.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    let unpadded = &digits[..end];
+    if unpadded.is_empty() {
+        return Err("empty OTP secret".into());
+    }
+    if !unpadded
+        .iter()
+        .all(|b| matches!(b, b'A'..=b'Z' | b'2'..=b'7'))
+    {
+        return Err("OTP secret is not valid base32".into());
+    }
+    // Lengths that no whole number of bytes encodes to.
+    if matches!(unpadded.len() % 8, 1 | 3 | 6) {
+        return Err("OTP secret has an impossible base32 length".into());
+    }
+    Ok(())
+}
+
+fn timestamp(value: &Value) -> Result<(), String> {
+    value
+        .as_str()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|_| ())
+        .ok_or("expected an RFC 3339 timestamp".into())
+}
+
+fn string_array(value: &Value) -> Result<(), String> {
+    match value.as_array() {
+        Some(items) => match items.iter().position(|i| !i.is_string()) {
+            Some(i) => Err(format!("item {} is not a string", i)),
+            None => Ok(()),
+        },
+        None => Err("expected a list of strings".into()),
+    }
+}
+
+fn u32_value(value: &Value, min: u64) -> Result<(), String> {
+    value
+        .as_u64()
+        .filter(|&n| n >= min && n <= u64::from(u32::MAX))
+        .map(|_| ())
+        .ok_or(format!("expected a whole number of at least {}", min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derive::Derivation;
+    use crate::store::{Entry, PastPassword};
+    use chrono::Utc;
+    use std::collections::BTreeSet;
+
+    fn keys(value: &Value) -> BTreeSet<&str> {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// A struct literal, so a new `Entry` field fails to compile here until
+    /// it is added to the test and to `ENTRY_FIELDS`.
+    #[test]
+    fn field_lists_match_what_entries_serialize() {
+        let entry = Entry {
+            username: "me".into(),
+            password: SecretString::from("hunter2".to_string()),
+            url: Some("https://example.com".into()),
+            tags: vec!["work".into()],
+            attachments: vec![serde_json::from_value(serde_json::json!({
+                "id": "0123456789abcdef0123456789abcdef", "name": "codes.txt",
+                "size": 7, "key": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+            }))
+            .unwrap()],
+            password_changed: Some(Utc::now()),
+            rotate_every_days: Some(90),
+            expires: Some(NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()),
+            policy: Some(Policy::default()),
+            derived: Some(Derivation {
+                site: "example.com".into(),
+                counter: 1,
+                length: 20,
+                lowercase: true,
+                uppercase: true,
+                digits: true,
+                symbols: true,
+            }),
+            favourite: true,
+            last_used: Some(Utc::now()),
+            use_count: 3,
+            history: vec![PastPassword {
+                password: SecretString::from("old".to_string()),
+                retired: Utc::now(),
+                merged_from: Some("example".into()),
+            }],
+            notes: SecretString::from("notes".to_string()),
+            fields: [("pin".to_string(), SecretString::from("1234".to_string()))].into(),
+        };
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(keys(&value), ENTRY_FIELDS.iter().copied().collect());
+        assert_eq!(
+            keys(&value["attachments"][0]),
+            ATTACHMENT_FIELDS.iter().copied().collect()
+        );
+        assert_eq!(
+            keys(&value["history"][0]),
+            HISTORY_FIELDS.iter().copied().collect()
+        );
+    }
+
+    #[test]
+    fn otp_secrets_are_base32_or_otpauth_uris() {
+        for good in [
+            "JBSWY3DPEHPK3PXP",
+            "jbsw y3dp ehpk 3pxp",
+            "JBSWY3DPEE======",
+            "otpauth://totp/Example:me?secret=JBSWY3DPEHPK3PXP&issuer=Example",
+        ] {
+            assert!(otp_secret(good).is_ok(), "{}", good);
+        }
+        for bad in [
+            "",
+            "JBSWY3DP1",
+            "hunter2!",
+            "JBSWY3",
+            "otpauth://totp/Example:me?issuer=Example",
+            "otpauth://totp/Example:me?secret=0000",
+        ] {
+            assert!(otp_secret(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn otp_fields_are_checked_where_they_are_stored() {
+        let entry = serde_json::json!({
+            "username": "me",
+            "password": "hunter2",
+            "fields": {
+                "TOTP": "JBSWY3DPEHPK3PXP",
+                "otp": "not base32!",
+                "backup": "otpauth://totp/x?secret=JBSWY3DP",
+                "login": "otpauth://totp/x?secret=88",
+                "pin": "1234",
+            },
+        });
+        let mut problems = Vec::new();
+        check_entry("mail", &entry, &mut problems);
+        assert_eq!(
+            problems,
+            [
+                "mail.fields.login: OTP secret is not valid base32",
+                "mail.fields.otp: OTP secret is not valid base32",
+            ]
+        );
+    }
+}
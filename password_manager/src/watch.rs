@@ -0,0 +1,369 @@
+use crate::config::{self, Backend};
+use crate::store::{fingerprint, reload_store, save_store, Entry, PasswordStore, Vault};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::{Map, Value};
+use sha2::{Digest as _, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use zeroize::Zeroizing;
+
+type Digest = [u8; 32];
+/// Digest of each field of an entry, so a merge can tell which fields each
+/// side touched without keeping a plaintext copy of the entry around.
+type Fields = BTreeMap<String, Digest>;
+
+/// Keeps an interactive session in step with the vault file, so edits made
+/// by another process or a sync tool are picked up instead of being
+/// overwritten by the next save.
+///
+/// Merges are three-way against the vault as last read from or written to
+/// disk: whatever only one side changed is taken from that side, field by
+/// field. When both changed the same field this session's value wins; a
+/// password from the other side is kept in the entry's history.
+pub struct Watch {
+    /// `None` if inotify couldn't be set up; every poll then checks the file.
+    watcher: Option<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)>,
+    base: BTreeMap<String, Fields>,
+    /// The file as last seen, so our own writes don't count as changes.
+    file: Option<Digest>,
+}
+
+/// One entry that changed on disk, as reported to the user.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Changed(String),
+    Deleted(String),
+    /// Both sides edited the same fields; this session's values were kept.
+    Conflict(String),
+    /// Deleted elsewhere while edited here; the edit was kept.
+    Kept(String),
+    /// Edited elsewhere while deleted here; the other side's entry is back.
+    Restored(String),
+}
+
+impl Change {
+    pub fn service(&self) -> &str {
+        match self {
+            Change::Added(s)
+            | Change::Changed(s)
+            | Change::Deleted(s)
+            | Change::Conflict(s)
+            | Change::Kept(s)
+            | Change::Restored(s) => s,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(s) => write!(f, "'{}' was added elsewhere.", s),
+            Change::Changed(s) => write!(f, "'{}' was changed elsewhere.", s),
+            Change::Deleted(s) => write!(f, "'{}' was deleted elsewhere.", s),
+            Change::Conflict(s) => write!(
+                f,
+                "'{}' was changed here and elsewhere; kept your changes where they overlap.",
+                s
+            ),
+            Change::Kept(s) => write!(f, "'{}' was deleted elsewhere; kept your changes.", s),
+            Change::Restored(s) => write!(
+                f,
+                "'{}' was changed elsewhere after you deleted it; restored their version.",
+                s
+            ),
+        }
+    }
+}
+
+impl Watch {
+    /// Starts watching the selected vault, taking `vault` as just loaded.
+    pub fn start(vault: &Vault) -> io::Result<Self> {
+        Ok(Self {
+            watcher: watcher().ok(),
+            base: fields_of(&vault.entries)?,
+            file: fingerprint()?,
+        })
+    }
+
+    /// Merges in whatever changed on disk since the last poll or save.
+    pub fn poll(&mut self, vault: &mut Vault) -> io::Result<Vec<Change>> {
+        if let Some((_, events)) = &self.watcher {
+            let location = config::current();
+            let ours = |path: &Path| match location.backend {
+                Backend::Json => path.file_name() == location.path.file_name(),
+                Backend::Age => path.starts_with(&location.path),
+            };
+            let touched = events
+                .try_iter()
+                .any(|event| event.is_ok_and(|event| event.paths.iter().any(|p| ours(p))));
+            if !touched {
+                return Ok(Vec::new());
+            }
+        }
+        self.sync(vault)
+    }
+
+    /// Saves `vault`, first merging in changes made elsewhere.
+    pub fn save(&mut self, vault: &mut Vault) -> io::Result<Vec<Change>> {
+        let changes = self.sync(vault)?;
+        save_store(vault)?;
+        self.file = fingerprint()?;
+        self.base = fields_of(&vault.entries)?;
+        // Our own write; the next poll needn't look at it.
+        if let Some((_, events)) = &self.watcher {
+            events.try_iter().for_each(drop);
+        }
+        Ok(changes)
+    }
+
+    fn sync(&mut self, vault: &mut Vault) -> io::Result<Vec<Change>> {
+        // Gone, or being replaced: the next save writes it again.
+        let Some(file) = fingerprint()? else {
+            return Ok(Vec::new());
+        };
+        if self.file == Some(file) {
+            return Ok(Vec::new());
+        }
+        let Some(theirs) = reload_store(vault.key.as_mut())? else {
+            return Ok(Vec::new());
+        };
+        let theirs_fields = fields_of(&theirs)?;
+        let changes = merge(&self.base, &mut vault.entries, theirs)?;
+        self.base = theirs_fields;
+        self.file = Some(file);
+        Ok(changes)
+    }
+}
+
+fn watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+    let location = config::current();
+    match location.backend {
+        // The directory rather than the file: saves replace the file by
+        // renaming a new one over it, which a watch on the old inode
+        // would miss.
+        Backend::Json => {
+            let dir = location
+                .path
+                .parent()
+                .filter(|d| !d.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            config::create_private_dir(dir)?;
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        Backend::Age => {
+            config::create_private_dir(&location.path)?;
+            watcher.watch(&location.path, RecursiveMode::Recursive)?;
+        }
+    }
+    Ok((watcher, rx))
+}
+
+/// Three-way merge of `theirs` into `ours`, `base` being what both started
+/// from. Returns what changed in `ours`.
+fn merge(
+    base: &BTreeMap<String, Fields>,
+    ours: &mut PasswordStore,
+    mut theirs: PasswordStore,
+) -> io::Result<Vec<Change>> {
+    let services: BTreeSet<String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .cloned()
+        .collect();
+    let mut changes = Vec::new();
+    for service in services {
+        let base = base.get(&service);
+        let our_fields = ours.get(&service).map(fields).transpose()?;
+        let their_fields = theirs.get(&service).map(fields).transpose()?;
+        if our_fields == their_fields || their_fields.as_ref() == base {
+            continue;
+        }
+        let theirs = theirs.remove(&service);
+        let change = if our_fields.as_ref() == base {
+            match theirs {
+                Some(entry) => {
+                    let change = if ours.contains_key(&service) {
+                        Change::Changed(service.clone())
+                    } else {
+                        Change::Added(service.clone())
+                    };
+                    ours.insert(service, entry);
+                    change
+                }
+                None => {
+                    ours.remove(&service);
+                    Change::Deleted(service)
+                }
+            }
+        } else {
+            match (ours.remove(&service), theirs) {
+                (Some(mine), Some(theirs)) => {
+                    let (entry, conflict) = merge_entry(base, mine, theirs)?;
+                    ours.insert(service.clone(), entry);
+                    if conflict {
+                        Change::Conflict(service)
+                    } else {
+                        Change::Changed(service)
+                    }
+                }
+                (Some(mine), None) => {
+                    ours.insert(service.clone(), mine);
+                    Change::Kept(service)
+                }
+                (None, Some(theirs)) => {
+                    ours.insert(service.clone(), theirs);
+                    Change::Restored(service)
+                }
+                (None, None) => continue,
+            }
+        };
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+/// Field-by-field merge of one entry both sides changed. Returns the
+/// merged entry and whether any field was changed on both sides.
+fn merge_entry(base: Option<&Fields>, ours: Entry, theirs: Entry) -> io::Result<(Entry, bool)> {
+    let mut merged = object(&ours)?;
+    let their_values = object(&theirs)?;
+    let keys: BTreeSet<String> = merged.keys().chain(their_values.keys()).cloned().collect();
+    let mut conflict = false;
+    let mut lost_password = false;
+    for key in keys {
+        let base = base.and_then(|b| b.get(&key));
+        let mine = merged.get(&key).map(value_digest).transpose()?;
+        let other = their_values.get(&key).map(value_digest).transpose()?;
+        if mine == other || other.as_ref() == base {
+            continue;
+        }
+        if mine.as_ref() == base {
+            match their_values.get(&key) {
+                Some(value) => merged.insert(key, value.clone()),
+                None => merged.remove(&key),
+            };
+        } else {
+            conflict = true;
+            lost_password |= key == "password";
+        }
+    }
+    let mut entry: Entry = serde_json::from_value(Value::Object(merged))?;
+    if lost_password {
+        entry.retire(theirs.password, None);
+    }
+    Ok((entry, conflict))
+}
+
+fn object(entry: &Entry) -> io::Result<Map<String, Value>> {
+    match serde_json::to_value(entry)? {
+        Value::Object(map) => Ok(map),
+        _ => unreachable!("entries serialize as objects"),
+    }
+}
+
+fn fields(entry: &Entry) -> io::Result<Fields> {
+    object(entry)?
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), value_digest(value)?)))
+        .collect()
+}
+
+fn fields_of(entries: &PasswordStore) -> io::Result<BTreeMap<String, Fields>> {
+    entries
+        .iter()
+        .map(|(service, entry)| Ok((service.clone(), fields(entry)?)))
+        .collect()
+}
+
+fn value_digest(value: &Value) -> io::Result<Digest> {
+    Ok(digest(&Zeroizing::new(serde_json::to_vec(value)?)))
+}
+
+fn digest(bytes: &[u8]) -> Digest {
+    Sha256::digest(bytes).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::SecretString;
+
+    fn entry(username: &str, password: &str) -> Entry {
+        Entry::new(
+            username.to_string(),
+            SecretString::from(password.to_string()),
+        )
+    }
+
+    fn store(entries: Vec<(&str, Entry)>) -> PasswordStore {
+        entries
+            .into_iter()
+            .map(|(service, entry)| (service.to_string(), entry))
+            .collect()
+    }
+
+    #[test]
+    fn takes_whatever_only_one_side_changed() {
+        let base = store(vec![("a", entry("me", "one")), ("b", entry("me", "two"))]);
+        let base_fields = fields_of(&base).unwrap();
+        let mut ours = fields_clone(&base, &["a", "b"]);
+        ours.insert("mine".to_string(), entry("me", "new"));
+        let mut theirs = fields_clone(&base, &["a", "b"]);
+        theirs.get_mut("a").unwrap().username = "them".to_string();
+        theirs.remove("b");
+        theirs.insert("theirs".to_string(), entry("x", "y"));
+
+        let changes = merge(&base_fields, &mut ours, theirs).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Changed("a".to_string()),
+                Change::Deleted("b".to_string()),
+                Change::Added("theirs".to_string()),
+            ]
+        );
+        assert_eq!(ours["a"].username, "them");
+        assert!(ours.contains_key("mine"));
+        assert!(!ours.contains_key("b"));
+    }
+
+    #[test]
+    fn overlapping_edits_keep_ours_and_their_password_in_history() {
+        let base = store(vec![("a", entry("me", "one"))]);
+        let base_fields = fields_of(&base).unwrap();
+        let mut ours = fields_clone(&base, &["a"]);
+        let mut theirs = fields_clone(&base, &["a"]);
+        ours.get_mut("a").unwrap().password = SecretString::from("mine".to_string());
+        ours.get_mut("a").unwrap().favourite = true;
+        theirs.get_mut("a").unwrap().password = SecretString::from("theirs".to_string());
+        theirs.get_mut("a").unwrap().url = Some("https://example.com".to_string());
+
+        let changes = merge(&base_fields, &mut ours, theirs).unwrap();
+        assert_eq!(changes, vec![Change::Conflict("a".to_string())]);
+        let a = &ours["a"];
+        assert_eq!(a.password.expose(), "mine");
+        assert!(a.favourite);
+        assert_eq!(a.url.as_deref(), Some("https://example.com"));
+        assert_eq!(a.history.last().unwrap().password.expose(), "theirs");
+    }
+
+    /// Round-trips the named entries through JSON, so they compare equal.
+    fn fields_clone(entries: &PasswordStore, services: &[&str]) -> PasswordStore {
+        services
+            .iter()
+            .map(|s| {
+                let value = serde_json::to_value(&entries[*s]).unwrap();
+                (s.to_string(), serde_json::from_value(value).unwrap())
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,1073 @@
+//! End-to-end sessions driven through `--batch`, each against its own
+//! temporary vault.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Runs the binary in `dir` with `answers` on stdin. Config and data go
+/// under `dir` too.
+fn run(dir: &Path, args: &[&str], answers: &str) -> Output {
+    run_env(dir, args, &[], answers)
+}
+
+/// Like `run`, with extra environment variables.
+fn run_env(dir: &Path, args: &[&str], env: &[(&str, &str)], answers: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_password_manager"))
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("start password_manager");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(answers.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Where the default vault lives for a `run` in `dir`.
+fn vault_file(dir: &Path) -> PathBuf {
+    dir.join("data/password_manager/passwords.json")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+const ADD_GITHUB: &str =
+    "Add Entry\ngithub\nme\nNo policy\nn\nhunter2\nhttps://github.com\nwork\n\n";
+
+#[test]
+fn add_view_delete_session() {
+    let dir = TempDir::new().unwrap();
+    let answers = format!(
+        "{}View Password\ngithub\nShow in full\nDelete Entry\ngithub\ny\nExit\n",
+        ADD_GITHUB
+    );
+    let output = run(dir.path(), &["--batch"], &answers);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let out = stdout(&output);
+    assert!(out.contains("Entry for 'github' saved."));
+    assert!(out.contains("Username: me"));
+    assert!(out.contains("Password: hunter2"));
+    assert!(out.contains("Entry deleted."));
+    let vault = fs::read_to_string(vault_file(dir.path())).unwrap();
+    assert!(!vault.contains("github"));
+}
+
+#[test]
+fn entries_persist_between_sessions() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = run(dir.path(), &["--batch"], "List Services\nExit\n");
+    assert!(stdout(&output).contains("- github"));
+
+    let output = run(
+        dir.path(),
+        &["show", "github", "--full", "--clear-after", "0"],
+        "",
+    );
+    assert!(stdout(&output).contains("Password: hunter2"));
+}
+
+#[test]
+fn encrypted_vault_session() {
+    let dir = TempDir::new().unwrap();
+    let output = run(
+        dir.path(),
+        &["--batch", "encrypt", "--unlock-ms", "10"],
+        "master\nmaster\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let answers = format!(
+        "master\n{}View Password\ngithub\nShow in full\nExit\n",
+        ADD_GITHUB
+    );
+    let output = run(dir.path(), &["--batch"], &answers);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Password: hunter2"));
+    assert!(!stderr(&output).contains("master"), "password echoed");
+
+    let vault = fs::read_to_string(vault_file(dir.path())).unwrap();
+    assert!(!vault.contains("hunter2"));
+    assert!(!vault.contains("github"));
+
+    let output = run(dir.path(), &["--batch", "search", "git"], "wrong\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("wrong master password"));
+}
+
+#[test]
+fn an_unreadable_vault_is_an_error_not_an_empty_vault() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let path = vault_file(dir.path());
+    let full = fs::read_to_string(&path).unwrap();
+    let truncated = &full[..full.len() / 2];
+    fs::write(&path, truncated).unwrap();
+
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("is not a readable vault"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), truncated);
+}
+
+#[test]
+fn deleting_an_entry_deletes_its_attachments() {
+    let dir = TempDir::new().unwrap();
+    let output = run(
+        dir.path(),
+        &["--batch", "encrypt", "--unlock-ms", "10"],
+        "master\nmaster\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run(
+        dir.path(),
+        &["--batch"],
+        &format!("master\n{}Exit\n", ADD_GITHUB),
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let file = dir.path().join("recovery-codes.txt");
+    fs::write(&file, "1234-5678").unwrap();
+    let output = run(
+        dir.path(),
+        &["--batch", "attach", "github", file.to_str().unwrap()],
+        "master\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let blobs = dir
+        .path()
+        .join("data/password_manager/passwords.attachments");
+    assert_eq!(fs::read_dir(&blobs).unwrap().count(), 1);
+
+    let output = run(
+        dir.path(),
+        &["--batch"],
+        "master\nDelete Entry\ngithub\ny\nExit\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Entry deleted."));
+    assert_eq!(fs::read_dir(&blobs).unwrap().count(), 0);
+}
+
+#[test]
+fn malformed_attachment_ids_are_reported_and_never_used_as_paths() {
+    let dir = TempDir::new().unwrap();
+    let victim = dir.path().join("victim");
+    fs::write(&victim, "keep me").unwrap();
+    fs::create_dir_all(vault_file(dir.path()).parent().unwrap()).unwrap();
+    fs::write(
+        vault_file(dir.path()),
+        r#"{"github": {"username": "me", "password": "hunter2", "attachments": [
+  {"id": "../../../victim", "name": "codes.txt", "size": 7,
+   "key": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="}
+]}}"#,
+    )
+    .unwrap();
+
+    let output = run(dir.path(), &["verify"], "");
+    assert!(!output.status.success());
+    assert!(
+        stdout(&output).contains("github.attachments[0].id: expected 32 lowercase hex digits"),
+        "{}",
+        stdout(&output)
+    );
+    let out = dir.path().join("extracted");
+    let output = run(
+        dir.path(),
+        &[
+            "--batch",
+            "extract",
+            "github",
+            "codes.txt",
+            "--out",
+            out.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("malformed id"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = run(dir.path(), &["--batch"], "Delete Entry\ngithub\ny\nExit\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Entry deleted."));
+    assert_eq!(fs::read_to_string(&victim).unwrap(), "keep me");
+}
+
+#[test]
+fn derive_save_only_replaces_an_entry_when_asked() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let before = fs::read_to_string(vault_file(dir.path())).unwrap();
+
+    let derive = ["derive", "github.com", "me", "--save", "github"];
+    let output = run(dir.path(), &derive, "");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("use --replace"));
+    assert_eq!(fs::read_to_string(vault_file(dir.path())).unwrap(), before);
+
+    let output = run(dir.path(), &[&derive[..], &["--replace"]].concat(), "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let vault: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(vault_file(dir.path())).unwrap()).unwrap();
+    let github = &vault["github"];
+    assert_eq!(github["derived"]["site"], "github.com");
+    assert!(github.get("password").is_none());
+    assert_eq!(github["history"][0]["password"], "hunter2");
+    assert_eq!(github["url"], "https://github.com");
+}
+
+#[test]
+fn answers_can_come_from_a_file() {
+    let dir = TempDir::new().unwrap();
+    let script = dir.path().join("answers.txt");
+    fs::write(&script, format!("{}Exit\n", ADD_GITHUB)).unwrap();
+    let output = run(dir.path(), &["--answers", "answers.txt"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Entry for 'github' saved."));
+}
+
+#[test]
+fn running_out_of_answers_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], "Add Entry\ngithub\n");
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert!(
+        err.contains("ran out of answers at 'Enter username'"),
+        "{}",
+        err
+    );
+    assert!(!err.contains("panicked"));
+}
+
+#[test]
+fn invalid_answers_are_refused() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], "Teleport\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("'Teleport' is not one unique choice"));
+
+    let answers = "Add Entry\ngithub\nme\nNo policy\nn\nhunter2\n\n\nsoon\n";
+    let output = run(dir.path(), &["--batch"], answers);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("enter a number of days"));
+}
+
+#[test]
+fn no_terminal_without_batch_fails_cleanly() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &[], "");
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert!(err.contains("--batch"), "{}", err);
+    assert!(!err.contains("panicked"));
+}
+
+#[test]
+fn named_vaults_are_separate() {
+    let dir = TempDir::new().unwrap();
+    let config = dir.path().join("config/password_manager");
+    fs::create_dir_all(&config).unwrap();
+    let team = dir.path().join("shared/team.json");
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "default = \"personal\"\n\n[vaults.personal]\npath = \"personal.json\"\n\n[vaults.team]\npath = \"{}\"\n",
+            team.display()
+        ),
+    )
+    .unwrap();
+
+    let output = run(
+        dir.path(),
+        &["--batch", "--vault", "team"],
+        &format!("{}Exit\n", ADD_GITHUB),
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(&team).unwrap().contains("github"));
+    assert!(dir.path().join("shared/team.audit.jsonl").exists());
+
+    let output = run(dir.path(), &["search", "github"], "");
+    assert!(stdout(&output).contains("No entries match"));
+    assert!(!dir
+        .path()
+        .join("data/password_manager/personal.json")
+        .exists());
+
+    let output = run(dir.path(), &["--vault", "prod", "search", "x"], "");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("configured: personal, team"));
+}
+
+#[test]
+fn legacy_vault_in_current_directory_is_moved_once() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("passwords.json"),
+        r#"{"github":{"username":"me","password":"hunter2"}}"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join("passwords.audit.jsonl"), "").unwrap();
+
+    let output = run(dir.path(), &["search", "github"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("one-time migration"));
+    assert!(stdout(&output).contains("github"));
+    assert!(!dir.path().join("passwords.json").exists());
+    assert!(vault_file(dir.path()).exists());
+    assert!(dir
+        .path()
+        .join("data/password_manager/passwords.audit.jsonl")
+        .exists());
+
+    let output = run(dir.path(), &["search", "github"], "");
+    assert!(!stderr(&output).contains("migration"));
+}
+
+#[test]
+fn legacy_vault_under_version_control_is_left_alone() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    let legacy = r#"{"github":{"username":"me","password":"hunter2"}}"#;
+    fs::write(dir.path().join("passwords.json"), legacy).unwrap();
+
+    // Scripted answers never agree to the move.
+    let output = run(dir.path(), &["--batch", "search", "github"], "y\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("not moving passwords.json (under version control)"));
+    assert!(stdout(&output).contains("No entries match"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("passwords.json")).unwrap(),
+        legacy
+    );
+    assert!(!vault_file(dir.path()).exists());
+}
+
+#[test]
+fn verify_reports_every_problem_with_its_location() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run(dir.path(), &["verify"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Vault OK: 1 entries"));
+
+    fs::write(
+        vault_file(dir.path()),
+        r#"{
+  "github": {"username": "me", "password": "", "tags": ["work", 3]},
+  "mail": {"password": "x", "last_used": "yesterday", "otp": "JBSWY3DP",
+           "fields": {"totp": "JBSWY3DP1", "backup": "otpauth://totp/mail?secret=JBSWY3DP"}}
+}"#,
+    )
+    .unwrap();
+    let output = run(dir.path(), &["verify"], "");
+    assert!(!output.status.success());
+    let out = stdout(&output);
+    for problem in [
+        "github.password: empty password",
+        "github.tags: item 1 is not a string",
+        "mail.username: missing",
+        "mail.last_used: expected an RFC 3339 timestamp",
+        "mail.otp: unknown field",
+        "mail.fields.totp: OTP secret is not valid base32",
+        "6 problem(s)",
+    ] {
+        assert!(out.contains(problem), "{} not in\n{}", problem, out);
+    }
+}
+
+#[test]
+fn verify_log_detects_edited_deleted_and_reordered_records() {
+    let dir = TempDir::new().unwrap();
+    let answers = format!(
+        "{}View Password\ngithub\nShow in full\nDelete Entry\ngithub\ny\nExit\n",
+        ADD_GITHUB
+    );
+    let output = run(dir.path(), &["--batch"], &answers);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run(dir.path(), &["verify-log"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Audit log OK: 3 records"));
+
+    let log = dir
+        .path()
+        .join("data/password_manager/passwords.audit.jsonl");
+    let original = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = original.lines().collect();
+    assert_eq!(lines.len(), 3);
+    let tampered = [
+        (
+            original.replacen("\"view\"", "\"add\"", 1),
+            "line 2: hash mismatch",
+        ),
+        (
+            format!("{}\n{}\n", lines[0], lines[2]),
+            "line 2: sequence 3 where 2 was expected",
+        ),
+        (
+            format!("{}\n{}\n{}\n", lines[0], lines[2], lines[1]),
+            "line 2: does not chain from the previous record",
+        ),
+        (
+            format!("{}\n{}\n", lines[0], lines[1]),
+            "log ends at record 2 but the head file expects record 3",
+        ),
+    ];
+    for (text, problem) in tampered {
+        fs::write(&log, text).unwrap();
+        let output = run(dir.path(), &["verify-log"], "");
+        assert!(!output.status.success());
+        let out = stdout(&output);
+        assert!(out.contains(problem), "{} not in\n{}", problem, out);
+        assert!(out.contains("Audit log FAILED verification."));
+    }
+}
+
+#[test]
+fn processes_recording_at_once_keep_one_chain() {
+    let dir = TempDir::new().unwrap();
+    let output = run(
+        dir.path(),
+        &["--batch"],
+        &format!(
+            "{}Exit
+",
+            ADD_GITHUB
+        ),
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    let output = run(
+                        dir.path(),
+                        &["show", "github", "--full", "--clear-after", "0"],
+                        "",
+                    );
+                    assert!(output.status.success(), "{}", stderr(&output));
+                }
+            });
+        }
+    });
+    let output = run(dir.path(), &["verify-log"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Audit log OK: 21 records"));
+}
+
+#[test]
+fn verify_checks_the_encrypted_payload() {
+    let dir = TempDir::new().unwrap();
+    let answers = format!("{}Exit\n", ADD_GITHUB);
+    run(dir.path(), &["--batch"], &answers);
+    let output = run(
+        dir.path(),
+        &["--batch", "encrypt", "--unlock-ms", "10"],
+        "master\nmaster\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = run(dir.path(), &["--batch", "verify"], "master\n");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("(encrypted)"));
+
+    let path = vault_file(dir.path());
+    let mut envelope: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let ciphertext = envelope["payload"]["ciphertext"].as_str().unwrap();
+    let flipped = if ciphertext.starts_with('A') {
+        "B"
+    } else {
+        "A"
+    };
+    let tampered = format!("{}{}", flipped, &ciphertext[1..]);
+    envelope["payload"]["ciphertext"] = tampered.into();
+    fs::write(&path, envelope.to_string()).unwrap();
+
+    let output = run(dir.path(), &["--batch", "verify"], "master\n");
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("payload: vault payload failed authentication"));
+}
+
+#[test]
+fn changes_from_another_process_are_merged_not_overwritten() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_password_manager"))
+        .arg("--batch")
+        .current_dir(dir.path())
+        .env("XDG_CONFIG_HOME", dir.path().join("config"))
+        .env("XDG_DATA_HOME", dir.path().join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"List Services\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // Another process adds an entry and changes github's username.
+    let path = vault_file(dir.path());
+    let mut vault: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    vault["github"]["username"] = "someone-else".into();
+    vault["mail"] = serde_json::json!({"username": "me", "password": "letmein"});
+    fs::write(&path, vault.to_string()).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    stdin
+        .write_all(b"Toggle Favourite\ngithub\nList Services\nExit\n")
+        .unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let out = stdout(&output);
+    assert!(
+        out.contains("Note: 'github' was changed elsewhere."),
+        "{}",
+        out
+    );
+    assert!(out.contains("Note: 'mail' was added elsewhere."), "{}", out);
+    assert!(out.contains("- mail"), "{}", out);
+
+    let vault: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(vault["github"]["username"], "someone-else");
+    assert_eq!(vault["github"]["favourite"], true);
+    assert_eq!(vault["mail"]["password"], "letmein");
+}
+
+/// Writes a config with an `age` vault named `team` at `dir/team`,
+/// opened with a fresh identity. Returns the identity's public key.
+fn age_vault_config(dir: &Path) -> String {
+    let output = run(dir, &["bundle-keygen", "team.key"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let public = stdout(&output).trim().to_string();
+    let config = dir.join("config/password_manager");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!(
+            "[vaults.team]\npath = \"{}\"\nbackend = \"age\"\nidentity = \"{}\"\n",
+            dir.join("team").display(),
+            dir.join("team.key").display()
+        ),
+    )
+    .unwrap();
+    fs::create_dir_all(dir.join("team")).unwrap();
+    fs::write(dir.join("team/.age-recipients"), format!("{}\n", public)).unwrap();
+    public
+}
+
+#[test]
+fn age_vault_keeps_one_file_per_entry() {
+    let dir = TempDir::new().unwrap();
+    age_vault_config(dir.path());
+    let answers = "Add Entry\nwork/github\nme\nNo policy\nn\nhunter2\n\n\n\n\
+                   Add Entry\nmail\nme\nNo policy\nn\nletmein\n\n\n\nExit\n";
+    let output = run(dir.path(), &["--batch", "--vault", "team"], answers);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let github = dir.path().join("team/work/github.age");
+    let mail = dir.path().join("team/mail.age");
+    let bytes = fs::read(&github).unwrap();
+    assert!(bytes.starts_with(b"age-encryption.org/v1"));
+    assert!(!String::from_utf8_lossy(&bytes).contains("hunter2"));
+    let mail_before = fs::read(&mail).unwrap();
+
+    // Viewing records usage on github only; mail's file is left alone.
+    let output = run(
+        dir.path(),
+        &[
+            "--vault",
+            "team",
+            "show",
+            "work/github",
+            "--full",
+            "--clear-after",
+            "0",
+        ],
+        "",
+    );
+    assert!(
+        stdout(&output).contains("Password: hunter2"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(fs::read(&mail).unwrap(), mail_before);
+
+    let output = run(dir.path(), &["--vault", "team", "verify"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Vault OK: 2 entries"));
+
+    let output = run(
+        dir.path(),
+        &["--batch", "--vault", "team"],
+        "Delete Entry\nwork/github\ny\nExit\n",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!github.exists());
+    assert!(!dir.path().join("team/work").exists());
+}
+
+#[test]
+fn imported_bundles_never_bring_attachments() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["bundle-keygen", "me.key"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let public: age::x25519::Recipient = stdout(&output).trim().parse().unwrap();
+
+    // A bundle built by hand, not by `export`, which would strip these.
+    let attachment = |id: &str| {
+        serde_json::json!({"id": id, "name": "codes.txt", "size": 7,
+            "key": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="})
+    };
+    let bundle = serde_json::json!({
+        "format": "password_manager/bundle",
+        "version": 1,
+        "created": "2026-01-01T00:00:00Z",
+        "entries": {
+            "github": {"username": "me", "password": "hunter2",
+                "attachments": [attachment("../../../victim")]},
+            "mail": {"username": "me", "password": "letmein",
+                "attachments": [attachment("0123456789abcdef0123456789abcdef")]},
+        },
+    });
+    let mut bytes = Vec::new();
+    let encryptor =
+        age::Encryptor::with_recipients(std::iter::once(&public as &dyn age::Recipient)).unwrap();
+    let mut writer = encryptor.wrap_output(&mut bytes).unwrap();
+    writer.write_all(bundle.to_string().as_bytes()).unwrap();
+    writer.finish().unwrap();
+    fs::write(dir.path().join("crafted.age"), bytes).unwrap();
+    fs::create_dir_all(vault_file(dir.path()).parent().unwrap()).unwrap();
+    fs::write(vault_file(dir.path()), "{}").unwrap();
+
+    let output = run(
+        dir.path(),
+        &["import-bundle", "crafted.age", "--identity", "me.key"],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Imported 2 entries."));
+    let vault: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(vault_file(dir.path())).unwrap()).unwrap();
+    for service in ["github", "mail"] {
+        assert!(vault[service]["password"].is_string());
+        assert!(
+            vault[service]["attachments"]
+                .as_array()
+                .is_none_or(|a| a.is_empty()),
+            "{}",
+            vault
+        );
+    }
+}
+
+#[test]
+fn entries_move_between_json_and_age_stores() {
+    let dir = TempDir::new().unwrap();
+    let public = age_vault_config(dir.path());
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = run(
+        dir.path(),
+        &["export-age", "exported", "--recipient", &public],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.path().join("exported/github.age").exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("exported/.age-recipients")).unwrap(),
+        format!("{}\n", public)
+    );
+
+    let output = run(
+        dir.path(),
+        &[
+            "--vault",
+            "team",
+            "import-age",
+            "exported",
+            "--identity",
+            "team.key",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Imported 1 entries."));
+    assert!(dir.path().join("team/github.age").exists());
+
+    fs::remove_file(vault_file(dir.path())).unwrap();
+    let output = run(
+        dir.path(),
+        &["import-age", "team", "--identity", "team.key"],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = run(
+        dir.path(),
+        &["show", "github", "--full", "--clear-after", "0"],
+        "",
+    );
+    assert!(stdout(&output).contains("Password: hunter2"));
+}
+
+#[test]
+fn notes_round_trip_through_the_editor() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // First attempt has an empty field; the second fixes it after seeing
+    // the problem listed at the top of the reopened file.
+    let editor = dir.path().join("editor.sh");
+    fs::write(
+        &editor,
+        r#"#!/bin/sh
+ls -l "$1" >> seen
+if grep -q '^#!' "$1"; then
+  cat "$1" >> seen
+  printf 'notes = """\nrecovery phrase\n"""\n[fields]\npin = "1234"\n' > "$1"
+else
+  printf 'notes = "x"\n[fields]\npin = ""\n' > "$1"
+fi
+"#,
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let output = run_env(
+        dir.path(),
+        &["edit", "github"],
+        &[("EDITOR", editor.to_str().unwrap()), ("VISUAL", "")],
+        "",
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Notes and fields of 'github' saved."));
+
+    let seen = fs::read_to_string(dir.path().join("seen")).unwrap();
+    assert!(seen.contains("-rw-------"), "{}", seen);
+    assert!(
+        seen.contains("line 6: field 'pin' is empty; delete its line to remove it"),
+        "{}",
+        seen
+    );
+    let temp = seen.split_whitespace().nth(8).unwrap();
+    assert!(!Path::new(temp).exists(), "{} left behind", temp);
+
+    let vault: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(vault_file(dir.path())).unwrap()).unwrap();
+    assert_eq!(vault["github"]["notes"], "recovery phrase\n");
+    assert_eq!(vault["github"]["fields"]["pin"], "1234");
+
+    let output = run(dir.path(), &["show", "github"], "");
+    assert!(stdout(&output).contains("Fields:   pin"));
+}
+
+#[test]
+fn emptying_the_editor_cancels_but_removing_the_contents_clears() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let edit = |script: &str| {
+        let editor = dir.path().join("editor.sh");
+        fs::write(&editor, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let output = run_env(
+            dir.path(),
+            &["edit", "github"],
+            &[("EDITOR", editor.to_str().unwrap()), ("VISUAL", "")],
+            "",
+        );
+        assert!(output.status.success(), "{}", stderr(&output));
+        stdout(&output)
+    };
+    let notes = || {
+        let vault: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(vault_file(dir.path())).unwrap()).unwrap();
+        vault["github"]["notes"].clone()
+    };
+
+    assert!(edit(r#"printf 'notes = "in the safe"\n' > "$1""#).contains("saved"));
+    assert_eq!(notes(), "in the safe");
+    assert!(edit(r#": > "$1""#).contains("Edit cancelled."));
+    assert_eq!(notes(), "in the safe");
+    // The header is left, the notes line removed.
+    assert!(edit(r#"sed -i '/^notes/d' "$1""#).contains("saved"));
+    assert_eq!(notes(), serde_json::Value::Null);
+
+    let log = fs::read_to_string(
+        dir.path()
+            .join("data/password_manager/passwords.audit.jsonl"),
+    )
+    .unwrap();
+    assert_eq!(log.matches("\"event\":\"edit\"").count(), 2, "{}", log);
+}
+
+/// `serve` on a free loopback port, killed when dropped.
+struct Server {
+    child: Child,
+    port: u16,
+}
+
+impl Server {
+    fn start(dir: &Path) -> Server {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = Server {
+            child: Command::new(env!("CARGO_BIN_EXE_password_manager"))
+                .args(["serve", "--listen", &format!("127.0.0.1:{}", port)])
+                .current_dir(dir)
+                .env("XDG_CONFIG_HOME", dir.join("config"))
+                .env("XDG_DATA_HOME", dir.join("data"))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("start password_manager serve"),
+            port,
+        };
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return server;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("serve did not start listening");
+    }
+
+    /// Sends one request and returns the status, headers and body.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, String, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        let auth = token.map_or(String::new(), |t| {
+            format!("Authorization: Bearer {}\r\n", t)
+        });
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, head.to_string(), body.to_string())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Creates an API token and returns its secret.
+fn token_add(dir: &Path, args: &[&str]) -> String {
+    let output = run(dir, &[&["token-add"], args].concat(), "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output).trim().to_string()
+}
+
+#[test]
+fn api_requires_a_token_with_the_right_access() {
+    let dir = TempDir::new().unwrap();
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let reader = token_add(dir.path(), &["reader"]);
+    let writer = token_add(dir.path(), &["writer", "--write"]);
+    let work = token_add(dir.path(), &["work", "--write", "--folder", "work"]);
+    let server = Server::start(dir.path());
+
+    assert_eq!(server.request("GET", "/entries", None, "").0, 401);
+    assert_eq!(
+        server.request("GET", "/entries", Some("pm_wrong"), "").0,
+        401
+    );
+
+    let (status, _, body) = server.request("GET", "/entries", Some(&reader), "");
+    assert_eq!(status, 200);
+    assert!(
+        body.contains("\"github\"") && !body.contains("hunter2"),
+        "{}",
+        body
+    );
+    let (status, _, body) = server.request("GET", "/entries/github", Some(&reader), "");
+    assert_eq!(status, 200);
+    assert!(body.contains("hunter2"), "{}", body);
+    // Reading a password counts as a use, as it does in the menu.
+    let vault = fs::read_to_string(vault_file(dir.path())).unwrap();
+    assert!(vault.contains("\"use_count\": 1"), "{}", vault);
+    let put = r#"{"username": "me", "password": "s3cret-Passw0rd!"}"#;
+    assert_eq!(
+        server.request("PUT", "/entries/mail", Some(&reader), put).0,
+        403
+    );
+    assert_eq!(
+        server
+            .request("DELETE", "/entries/github", Some(&reader), "")
+            .0,
+        403
+    );
+
+    // Folder tokens see nothing outside their folder.
+    assert_eq!(
+        server.request("GET", "/entries/github", Some(&work), "").0,
+        403
+    );
+    let (status, _, body) = server.request("GET", "/entries", Some(&work), "");
+    assert_eq!((status, body.as_str()), (200, "[]"));
+
+    // Create, read, update and delete.
+    assert_eq!(
+        server
+            .request("PUT", "/entries/work/mail", Some(&work), put)
+            .0,
+        200
+    );
+    let (status, _, body) = server.request("GET", "/entries/work/mail", Some(&writer), "");
+    assert_eq!(status, 200);
+    assert!(body.contains("s3cret-Passw0rd!"), "{}", body);
+    let update = r#"{"username": "me", "password": "n3w-Passw0rd!"}"#;
+    assert_eq!(
+        server
+            .request("PUT", "/entries/work/mail", Some(&work), update)
+            .0,
+        200
+    );
+    let (_, _, body) = server.request("GET", "/entries/work/mail", Some(&work), "");
+    assert!(body.contains("n3w-Passw0rd!"), "{}", body);
+    assert_eq!(
+        server
+            .request("PUT", "/entries/work/mail", Some(&work), "{")
+            .0,
+        400
+    );
+    assert_eq!(
+        server
+            .request("DELETE", "/entries/work/mail", Some(&work), "")
+            .0,
+        200
+    );
+    assert_eq!(
+        server
+            .request("GET", "/entries/work/mail", Some(&work), "")
+            .0,
+        404
+    );
+    assert_eq!(
+        server
+            .request("DELETE", "/entries/work/mail", Some(&work), "")
+            .0,
+        404
+    );
+
+    let vault = fs::read_to_string(vault_file(dir.path())).unwrap();
+    assert!(vault.contains("github") && !vault.contains("work/mail"));
+}
+
+#[test]
+fn api_rate_limits_bad_tokens_but_not_good_ones() {
+    let dir = TempDir::new().unwrap();
+    let reader = token_add(dir.path(), &["reader"]);
+    let server = Server::start(dir.path());
+
+    for _ in 0..5 {
+        assert_eq!(
+            server.request("GET", "/entries", Some("pm_guess"), "").0,
+            401
+        );
+    }
+    let (status, head, _) = server.request("GET", "/entries", Some("pm_guess"), "");
+    assert_eq!(status, 429);
+    assert!(head.to_lowercase().contains("retry-after:"), "{}", head);
+    assert_eq!(server.request("GET", "/entries", None, "").0, 429);
+    // Someone else's bad guesses don't lock out a valid token.
+    assert_eq!(server.request("GET", "/entries", Some(&reader), "").0, 200);
+}
+
+#[test]
+fn api_keeps_changes_made_by_other_processes() {
+    let dir = TempDir::new().unwrap();
+    let writer = token_add(dir.path(), &["writer", "--write"]);
+    let server = Server::start(dir.path());
+    let put = r#"{"username": "me", "password": "s3cret-Passw0rd!"}"#;
+    assert_eq!(
+        server.request("PUT", "/entries/mail", Some(&writer), put).0,
+        200
+    );
+
+    // Added while the server is running.
+    let output = run(dir.path(), &["--batch"], &format!("{}Exit\n", ADD_GITHUB));
+    assert!(output.status.success(), "{}", stderr(&output));
+    let (status, _, body) = server.request("GET", "/entries/github", Some(&writer), "");
+    assert_eq!(status, 200, "{}", body);
+
+    assert_eq!(
+        server.request("PUT", "/entries/bank", Some(&writer), put).0,
+        200
+    );
+    let vault = fs::read_to_string(vault_file(dir.path())).unwrap();
+    for service in ["mail", "github", "bank"] {
+        assert!(vault.contains(service), "{} lost:\n{}", service, vault);
+    }
+}
@@ -0,0 +1,593 @@
+use chrono::{
+    DateTime, Datelike, Days, Duration, LocalResult, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Timelike, Weekday,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// When a task is due: a day, and optionally a time on that day. Both are
+/// wall-clock values, so a task due at 09:00 stays due at 09:00 when the
+/// clocks change. Stored as `2026-10-23` or `2026-10-23T17:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Due {
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+impl Due {
+    pub fn status(&self, now: NaiveDateTime) -> Status {
+        let overdue = match self.time {
+            Some(time) => self.date.and_time(time) < now,
+            None => self.date < now.date(),
+        };
+        if overdue {
+            Status::Overdue
+        } else if self.date == now.date() {
+            Status::Today
+        } else {
+            Status::Upcoming
+        }
+    }
+
+    /// `today`, `tomorrow 17:00`, `in 12 days`, `3 days ago` and so on,
+    /// counted in calendar days from `today`.
+    pub fn label(&self, today: NaiveDate) -> String {
+        let days = (self.date - today).num_days();
+        let day = match days {
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            -1 => "yesterday".to_string(),
+            n if n > 0 => format!("in {} days", n),
+            n => format!("{} days ago", -n),
+        };
+        match self.time {
+            Some(time) => format!("{} {}", day, time.format("%H:%M")),
+            None => day,
+        }
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{}T{}", self.date, time.format("%H:%M")),
+            None => write!(f, "{}", self.date),
+        }
+    }
+}
+
+impl FromStr for Due {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (date, time) = match s.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (s, None),
+        };
+        let date = iso_date(date).ok_or_else(|| format!("'{}' is not a YYYY-MM-DD date", s))?;
+        let time = match time {
+            Some(time) => Some(
+                NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|_| format!("'{}' is not an HH:MM time", time))?,
+            ),
+            None => None,
+        };
+        Ok(Due { date, time })
+    }
+}
+
+impl Serialize for Due {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Due {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reads a due date as typed by a person, relative to `now`:
+///
+/// - ISO dates and times: `2026-10-23`, `2026-10-23T17:00`, `2026-10-23 17:00`
+/// - `today`, `tomorrow`, `yesterday`
+/// - a weekday, optionally after `next`: `fri`, `next friday`. Always the
+///   first one after today, never today itself.
+/// - `next week` (its Monday), `next month` (its 1st), `next year` (Jan 1)
+/// - `in 3 days`, `in 2 weeks`, `in a month`, `in 1 year`: calendar
+///   arithmetic, so `in 1 month` from Jan 31 is the last day of February
+/// - `in 2 hours`, `in 90 minutes`: elapsed time, which gives a time too
+/// - `eow` (Sunday), `eom` (last day of the month), `eoy` (Dec 31)
+/// - a month and day: `oct 23`, `23 october`. Next year's once this
+///   year's has passed.
+///
+/// Any of the day forms can end with a time, `17:00`, `5pm` or `5:30pm`,
+/// optionally after `at`; a time on its own means today. A time the
+/// clocks skip on that day is refused.
+pub fn parse<Tz: TimeZone>(input: &str, now: &DateTime<Tz>) -> Result<Due, String> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return Err("no date given".to_string());
+    }
+    if let Ok(due) = input.to_uppercase().parse::<Due>() {
+        return check_exists(due, now);
+    }
+
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let mut time = None;
+    if let Some(last) = words.last()
+        && let Some(t) = parse_time(last)
+    {
+        time = Some(t);
+        words.pop();
+        if words.last() == Some(&"at") {
+            words.pop();
+        }
+    }
+
+    let today = now.date_naive();
+    let date = match words.as_slice() {
+        [] if time.is_some() => today,
+        ["in", amount, unit] if is_clock_unit(unit) => {
+            if time.is_some() {
+                return Err(format!("'in {} {}' already gives a time", amount, unit));
+            }
+            let amount = parse_amount(amount)?;
+            let then = if unit.starts_with('h') {
+                Duration::try_hours(amount)
+            } else {
+                Duration::try_minutes(amount)
+            }
+            .and_then(|elapsed| now.clone().checked_add_signed(elapsed))
+            .and_then(|then| then.naive_local().with_second(0))
+            .and_then(|then| then.with_nanosecond(0))
+            .ok_or_else(|| format!("'{}' is too far away", input))?;
+            return Ok(Due {
+                date: then.date(),
+                time: Some(then.time()),
+            });
+        }
+        ["in", amount, unit] => {
+            let amount = parse_amount(amount)?;
+            let amount =
+                u32::try_from(amount).map_err(|_| format!("'{}' is too far away", input))?;
+            shift(today, amount, unit).ok_or_else(|| {
+                format!(
+                    "'{}' is not a unit; use days, weeks, months, years, hours or minutes",
+                    unit
+                )
+            })?
+        }
+        [word] => day_word(word, today).ok_or_else(|| unknown(&input))?,
+        ["next", "week"] => {
+            today + Days::new(7 - u64::from(today.weekday().num_days_from_monday()))
+        }
+        ["next", "month"] => first_of_month(today + Months::new(1)),
+        ["next", "year"] => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1).unwrap(),
+        ["next", day] => next_weekday(today, weekday(day).ok_or_else(|| unknown(&input))?),
+        [a, b] => month_day(a, b, today).ok_or_else(|| unknown(&input))?,
+        _ => return Err(unknown(&input)),
+    };
+    check_exists(Due { date, time }, now)
+}
+
+fn unknown(input: &str) -> String {
+    format!(
+        "can't read '{}' as a date; try 2026-10-23, tomorrow, next fri, in 3 days or eom",
+        input
+    )
+}
+
+/// Refuses a time that doesn't happen on that day, such as 02:30 on the
+/// night the clocks go forward. Times that happen twice are kept.
+fn check_exists<Tz: TimeZone>(due: Due, now: &DateTime<Tz>) -> Result<Due, String> {
+    if let Some(time) = due.time
+        && let LocalResult::None = now.timezone().from_local_datetime(&due.date.and_time(time))
+    {
+        return Err(format!(
+            "{} doesn't happen on {}: the clocks skip it",
+            time.format("%H:%M"),
+            due.date
+        ));
+    }
+    Ok(due)
+}
+
+fn day_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" | "tod" => Some(today),
+        "tomorrow" | "tmr" | "tmrw" | "tom" => Some(today + Days::new(1)),
+        "yesterday" => Some(today - Days::new(1)),
+        "eow" => Some(today + Days::new(6 - u64::from(today.weekday().num_days_from_monday()))),
+        "eom" => Some(first_of_month(today + Months::new(1)) - Days::new(1)),
+        "eoy" => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        _ => iso_date(word).or_else(|| weekday(word).map(|day| next_weekday(today, day))),
+    }
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// The first `day` after `today`.
+fn next_weekday(today: NaiveDate, day: Weekday) -> NaiveDate {
+    let ahead = (7 + day.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Days::new(if ahead == 0 { 7 } else { u64::from(ahead) })
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let days = [
+        ("monday", "mon", Weekday::Mon),
+        ("tuesday", "tue", Weekday::Tue),
+        ("wednesday", "wed", Weekday::Wed),
+        ("thursday", "thu", Weekday::Thu),
+        ("friday", "fri", Weekday::Fri),
+        ("saturday", "sat", Weekday::Sat),
+        ("sunday", "sun", Weekday::Sun),
+    ];
+    days.into_iter()
+        .find(|(name, short, _)| word.len() >= short.len() && name.starts_with(word))
+        .map(|(_, _, day)| day)
+}
+
+fn month(word: &str) -> Option<u32> {
+    let months = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    months
+        .iter()
+        .position(|name| word.len() >= 3 && name.starts_with(word))
+        .map(|i| i as u32 + 1)
+}
+
+/// `oct 23` or `23 oct`, this year unless that day has passed.
+fn month_day(a: &str, b: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (month, day) = match (month(a), month(b)) {
+        (Some(m), None) => (m, b.parse().ok()?),
+        (None, Some(m)) => (m, a.parse().ok()?),
+        _ => return None,
+    };
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+    match this_year {
+        Some(date) if date >= today => Some(date),
+        // Feb 29 in a year without one, or a day already gone: the next
+        // year that has it.
+        _ => (1..=8).find_map(|n| NaiveDate::from_ymd_opt(today.year() + n, month, day)),
+    }
+}
+
+fn is_clock_unit(unit: &str) -> bool {
+    matches!(
+        unit,
+        "h" | "hr" | "hrs" | "hour" | "hours" | "min" | "mins" | "minute" | "minutes"
+    )
+}
+
+fn shift(today: NaiveDate, amount: u32, unit: &str) -> Option<NaiveDate> {
+    match unit {
+        "d" | "day" | "days" => today.checked_add_days(Days::new(amount.into())),
+        "w" | "wk" | "wks" | "week" | "weeks" => {
+            today.checked_add_days(Days::new(u64::from(amount) * 7))
+        }
+        "mo" | "month" | "months" => today.checked_add_months(Months::new(amount)),
+        "y" | "yr" | "yrs" | "year" | "years" => {
+            today.checked_add_months(Months::new(amount.checked_mul(12)?))
+        }
+        _ => None,
+    }
+}
+
+fn parse_amount(amount: &str) -> Result<i64, String> {
+    match amount {
+        "a" | "an" => Ok(1),
+        _ => amount
+            .parse()
+            .ok()
+            .filter(|n: &i64| *n >= 0)
+            .ok_or_else(|| format!("'{}' is not a number", amount)),
+    }
+}
+
+fn iso_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// `17:00`, `5pm`, `5:30pm` or `noon`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    if word == "noon" {
+        return NaiveTime::from_hms_opt(12, 0, 0);
+    }
+    let (clock, offset) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (word, None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) if m.len() == 2 => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        _ => return None,
+    };
+    let hour = match offset {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(offset) => hour % 12 + offset,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::{America::New_York, Europe::London};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn day_of(input: &str, now: DateTime<Utc>) -> NaiveDate {
+        let due = parse(input, &now).unwrap();
+        assert_eq!(due.time, None, "{}", input);
+        due.date
+    }
+
+    fn time(h: u32, m: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, 0)
+    }
+
+    #[test]
+    fn iso_dates_and_times() {
+        let now = at(2026, 10, 19, 12, 0);
+        assert_eq!(day_of("2026-10-23", now), date(2026, 10, 23));
+        for input in ["2026-10-23T17:00", "2026-10-23 17:00", "2026-10-23t17:00"] {
+            let due = parse(input, &now).unwrap();
+            assert_eq!((due.date, due.time), (date(2026, 10, 23), time(17, 0)));
+        }
+        assert!(parse("2026-02-30", &now).is_err());
+        assert!(parse("2026-10-23T25:00", &now).is_err());
+    }
+
+    #[test]
+    fn stored_form_round_trips() {
+        for text in ["2026-10-23", "2026-10-23T07:05"] {
+            let due: Due = text.parse().unwrap();
+            assert_eq!(due.to_string(), text);
+            let json = serde_json::to_string(&due).unwrap();
+            assert_eq!(serde_json::from_str::<Due>(&json).unwrap(), due);
+        }
+    }
+
+    #[test]
+    fn day_words() {
+        // A Monday.
+        let now = at(2026, 10, 19, 12, 0);
+        assert_eq!(day_of("today", now), date(2026, 10, 19));
+        assert_eq!(day_of("Tomorrow", now), date(2026, 10, 20));
+        assert_eq!(day_of("tmr", now), date(2026, 10, 20));
+        assert_eq!(day_of("yesterday", now), date(2026, 10, 18));
+        assert_eq!(day_of("eow", now), date(2026, 10, 25));
+        assert_eq!(day_of("eoy", now), date(2026, 12, 31));
+        assert_eq!(day_of("next week", now), date(2026, 10, 26));
+        assert_eq!(day_of("next month", now), date(2026, 11, 1));
+        assert_eq!(day_of("next year", now), date(2027, 1, 1));
+        // eow on a Sunday is that Sunday.
+        assert_eq!(day_of("eow", at(2026, 10, 25, 12, 0)), date(2026, 10, 25));
+    }
+
+    #[test]
+    fn weekdays_are_always_ahead() {
+        // A Friday.
+        let now = at(2026, 10, 23, 12, 0);
+        assert_eq!(day_of("fri", now), date(2026, 10, 30));
+        assert_eq!(day_of("next fri", now), date(2026, 10, 30));
+        assert_eq!(day_of("friday", now), date(2026, 10, 30));
+        assert_eq!(day_of("sat", now), date(2026, 10, 24));
+        assert_eq!(day_of("thurs", now), date(2026, 10, 29));
+        assert_eq!(day_of("next mon", now), date(2026, 10, 26));
+        // Across a month and a year end.
+        assert_eq!(day_of("mon", at(2026, 10, 30, 12, 0)), date(2026, 11, 2));
+        assert_eq!(day_of("fri", at(2026, 12, 30, 12, 0)), date(2027, 1, 1));
+        assert!(parse("fr", &now).is_err());
+    }
+
+    #[test]
+    fn eom_at_month_ends() {
+        assert_eq!(day_of("eom", at(2026, 1, 31, 12, 0)), date(2026, 1, 31));
+        assert_eq!(day_of("eom", at(2026, 2, 1, 12, 0)), date(2026, 2, 28));
+        assert_eq!(day_of("eom", at(2028, 2, 10, 12, 0)), date(2028, 2, 29));
+        assert_eq!(day_of("eom", at(2026, 4, 30, 12, 0)), date(2026, 4, 30));
+        assert_eq!(day_of("eom", at(2026, 12, 31, 12, 0)), date(2026, 12, 31));
+    }
+
+    #[test]
+    fn relative_amounts_at_month_ends() {
+        let jan31 = at(2026, 1, 31, 12, 0);
+        assert_eq!(day_of("in 1 month", jan31), date(2026, 2, 28));
+        assert_eq!(day_of("in a month", jan31), date(2026, 2, 28));
+        assert_eq!(
+            day_of("in 1 month", at(2028, 1, 31, 12, 0)),
+            date(2028, 2, 29)
+        );
+        assert_eq!(day_of("in 2 months", jan31), date(2026, 3, 31));
+        assert_eq!(day_of("in 3 months", jan31), date(2026, 4, 30));
+        assert_eq!(day_of("in 1 day", jan31), date(2026, 2, 1));
+        assert_eq!(
+            day_of("in 3 days", at(2026, 2, 27, 12, 0)),
+            date(2026, 3, 2)
+        );
+        assert_eq!(
+            day_of("in 3 days", at(2028, 2, 27, 12, 0)),
+            date(2028, 3, 1)
+        );
+        assert_eq!(
+            day_of("in 2 weeks", at(2026, 12, 25, 12, 0)),
+            date(2027, 1, 8)
+        );
+        assert_eq!(
+            day_of("in 1 year", at(2028, 2, 29, 12, 0)),
+            date(2029, 2, 28)
+        );
+        assert_eq!(day_of("in 0 days", jan31), date(2026, 1, 31));
+        assert!(parse("in 3 fortnights", &jan31).is_err());
+        assert!(parse("in -3 days", &jan31).is_err());
+        assert!(parse("in 99999999999 years", &jan31).is_err());
+    }
+
+    #[test]
+    fn month_and_day() {
+        let now = at(2026, 10, 19, 12, 0);
+        assert_eq!(day_of("oct 23", now), date(2026, 10, 23));
+        assert_eq!(day_of("23 october", now), date(2026, 10, 23));
+        assert_eq!(day_of("oct 19", now), date(2026, 10, 19));
+        assert_eq!(day_of("jan 5", now), date(2027, 1, 5));
+        assert_eq!(day_of("feb 29", now), date(2028, 2, 29));
+        assert!(parse("feb 30", &now).is_err());
+        assert!(parse("ma 3", &now).is_err());
+    }
+
+    #[test]
+    fn times() {
+        let now = at(2026, 10, 19, 12, 0);
+        let cases = [
+            ("tomorrow 17:00", date(2026, 10, 20), time(17, 0)),
+            ("tomorrow at 5pm", date(2026, 10, 20), time(17, 0)),
+            ("fri 9:30am", date(2026, 10, 23), time(9, 30)),
+            ("2026-11-02 at noon", date(2026, 11, 2), time(12, 0)),
+            ("12am", date(2026, 10, 19), time(0, 0)),
+            ("12pm", date(2026, 10, 19), time(12, 0)),
+            ("at 23:59", date(2026, 10, 19), time(23, 59)),
+        ];
+        for (input, date, time) in cases {
+            let due = parse(input, &now).unwrap();
+            assert_eq!((due.date, due.time), (date, time), "{}", input);
+        }
+        for input in ["13pm", "0am", "24:00", "17:5", "tomorrow 5"] {
+            assert!(parse(input, &now).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn elapsed_time_crosses_midnight_and_month_end() {
+        let now = at(2026, 10, 31, 23, 15);
+        let due = parse("in 90 minutes", &now).unwrap();
+        assert_eq!((due.date, due.time), (date(2026, 11, 1), time(0, 45)));
+        let due = parse("in 2 hours", &at(2026, 10, 19, 9, 30)).unwrap();
+        assert_eq!((due.date, due.time), (date(2026, 10, 19), time(11, 30)));
+        assert!(parse("in 2 hours 5pm", &now).is_err());
+    }
+
+    #[test]
+    fn today_is_the_local_day() {
+        // 03:00 UTC is still the evening before in New York.
+        let now = at(2026, 10, 20, 3, 0).with_timezone(&New_York);
+        assert_eq!(parse("today", &now).unwrap().date, date(2026, 10, 19));
+        assert_eq!(parse("tomorrow", &now).unwrap().date, date(2026, 10, 20));
+    }
+
+    #[test]
+    fn days_are_calendar_days_across_dst() {
+        // London goes back an hour on 2026-10-25 and forward on 2027-03-28.
+        let before_fall = London.with_ymd_and_hms(2026, 10, 24, 0, 30, 0).unwrap();
+        assert_eq!(
+            parse("in 2 days", &before_fall).unwrap().date,
+            date(2026, 10, 26)
+        );
+        let before_spring = London.with_ymd_and_hms(2027, 3, 27, 23, 30, 0).unwrap();
+        assert_eq!(
+            parse("tomorrow", &before_spring).unwrap().date,
+            date(2027, 3, 28)
+        );
+        assert_eq!(
+            parse("in 1 week", &before_spring).unwrap().date,
+            date(2027, 4, 3)
+        );
+    }
+
+    #[test]
+    fn hours_are_elapsed_time_across_dst() {
+        // New York springs forward at 02:00 on 2027-03-14: 01:30 plus one
+        // hour is 03:30 on the wall clock.
+        let now = New_York.with_ymd_and_hms(2027, 3, 14, 1, 30, 0).unwrap();
+        let due = parse("in 1 hour", &now).unwrap();
+        assert_eq!((due.date, due.time), (date(2027, 3, 14), time(3, 30)));
+        // And falls back at 02:00 on 2026-11-01: 01:30 EDT plus one hour is
+        // 01:30 again, on the wall clock.
+        let now = New_York.with_ymd_and_hms(2026, 11, 1, 0, 30, 0).unwrap();
+        let due = parse("in 2 hours", &now).unwrap();
+        assert_eq!((due.date, due.time), (date(2026, 11, 1), time(1, 30)));
+    }
+
+    #[test]
+    fn skipped_times_are_refused_and_repeated_ones_kept() {
+        let now = New_York.with_ymd_and_hms(2027, 3, 13, 12, 0, 0).unwrap();
+        let err = parse("tomorrow 2:30am", &now).unwrap_err();
+        assert!(err.contains("clocks skip"), "{}", err);
+        assert!(parse("2027-03-14T02:30", &now).is_err());
+        assert!(parse("tomorrow 3:30am", &now).is_ok());
+        // 01:30 happens twice on 2026-11-01; either way it is a due time.
+        let now = New_York.with_ymd_and_hms(2026, 10, 31, 12, 0, 0).unwrap();
+        let due = parse("tomorrow 1:30am", &now).unwrap();
+        assert_eq!((due.date, due.time), (date(2026, 11, 1), time(1, 30)));
+    }
+
+    #[test]
+    fn status_and_labels() {
+        let now = date(2026, 10, 19).and_hms_opt(12, 0, 0).unwrap();
+        let today = now.date();
+        let due = |text: &str| text.parse::<Due>().unwrap();
+        assert_eq!(due("2026-10-18").status(now), Status::Overdue);
+        assert_eq!(due("2026-10-19").status(now), Status::Today);
+        assert_eq!(due("2026-10-19T11:59").status(now), Status::Overdue);
+        assert_eq!(due("2026-10-19T12:00").status(now), Status::Today);
+        assert_eq!(due("2026-10-20T00:00").status(now), Status::Upcoming);
+        assert_eq!(due("2026-10-19").label(today), "today");
+        assert_eq!(due("2026-10-20T17:00").label(today), "tomorrow 17:00");
+        assert_eq!(due("2026-10-18").label(today), "yesterday");
+        assert_eq!(due("2026-10-16").label(today), "3 days ago");
+        assert_eq!(due("2026-11-01").label(today), "in 13 days");
+    }
+
+    #[test]
+    fn nonsense_is_refused() {
+        let now = at(2026, 10, 19, 12, 0);
+        for input in [
+            "",
+            "   ",
+            "someday",
+            "next",
+            "next decade",
+            "in days",
+            "fri fri fri",
+        ] {
+            assert!(parse(input, &now).is_err(), "{:?}", input);
+        }
+    }
+}
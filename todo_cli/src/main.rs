@@ -1,49 +1,552 @@
+mod due;
+mod todotxt;
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use clap::{Parser, Subcommand};
+use console::style;
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use due::{Due, Status};
 use serde::{Deserialize, Serialize};
-use std::fs::{File, OpenOptions};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufReader, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 const TODO_FILE: &str = "todo.json";
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct Task {
+    /// Given when the task is added and never reused, so a script can keep
+    /// using it after other tasks are deleted. Missing in files written
+    /// before tasks had IDs.
+    #[serde(default)]
+    id: u32,
     description: String,
     done: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<Due>,
+    /// `A` to `Z`, as todo.txt writes it: `(A)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<char>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed: Option<NaiveDate>,
+    /// The todo.txt line the task was read from.
+    #[serde(skip)]
+    line: Option<usize>,
 }
 
 impl Task {
     fn new(description: String) -> Self {
         Self {
+            id: 0,
             description,
             done: false,
+            due: None,
+            priority: None,
+            created: None,
+            completed: None,
+            line: None,
+        }
+    }
+
+    fn set_done(&mut self, done: bool) {
+        self.done = done;
+        self.completed = done.then(|| Local::now().date_naive());
+    }
+}
+
+/// Everything `todo.json` holds, and where the list lives.
+#[derive(Serialize, Deserialize, Debug)]
+struct TodoList {
+    next_id: u32,
+    tasks: Vec<Task>,
+    #[serde(skip)]
+    file: PathBuf,
+    /// The lines of a todo.txt file, kept to write it back.
+    #[serde(skip)]
+    layout: Option<todotxt::Layout>,
+}
+
+/// `todo.json` as written now, or as a bare list of tasks from before
+/// tasks had IDs.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TodoFile {
+    List(TodoList),
+    Tasks(Vec<Task>),
+}
+
+impl TodoList {
+    fn add(&mut self, description: String, due: Option<Due>) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.push(Task {
+            id,
+            due,
+            created: Some(Local::now().date_naive()),
+            ..Task::new(description)
+        });
+        id
+    }
+
+    /// Gives tasks without an ID the next free ones, in file order, so
+    /// tasks keep the numbers they were listed under before. Returns
+    /// whether anything changed.
+    fn assign_ids(&mut self) -> bool {
+        let highest = self.tasks.iter().map(|t| t.id).max().unwrap_or(0);
+        let mut changed = false;
+        if self.next_id <= highest {
+            self.next_id = highest + 1;
+            changed = true;
+        }
+        for task in self.tasks.iter_mut().filter(|t| t.id == 0) {
+            task.id = self.next_id;
+            self.next_id += 1;
+            changed = true;
         }
+        changed
+    }
+
+    /// Index of the task with ID `id`. IDs are matched exactly; one below
+    /// `next_id` that no task has was deleted, and says so.
+    fn find(&self, id: &str) -> io::Result<usize> {
+        let id = id.trim().trim_start_matches('#');
+        let parsed: u32 = id.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not a task ID", id),
+            )
+        })?;
+        if let Some(i) = self.tasks.iter().position(|t| t.id == parsed) {
+            return Ok(i);
+        }
+        let message = if parsed > 0 && parsed < self.next_id {
+            format!("no task {} (it was deleted)", parsed)
+        } else {
+            format!("no task {}", parsed)
+        };
+        Err(io::Error::new(io::ErrorKind::NotFound, message))
     }
 }
 
-fn load_tasks() -> io::Result<Vec<Task>> {
-    if !Path::new(TODO_FILE).exists() {
-        return Ok(Vec::new());
+/// Reads the list in `file`, as todo.txt if it ends in `.txt` and as
+/// JSON otherwise.
+fn load_tasks(file: &Path) -> io::Result<TodoList> {
+    let mut list = if !file.exists() {
+        TodoList {
+            next_id: 1,
+            tasks: Vec::new(),
+            file: PathBuf::new(),
+            layout: None,
+        }
+    } else if todotxt::is_todotxt(file) {
+        todotxt::read(&fs::read_to_string(file)?)
+    } else {
+        let reader = BufReader::new(File::open(file)?);
+        // Anything unreadable is an error, never an empty list: the next
+        // save would otherwise overwrite whatever is still in the file.
+        let parsed = serde_json::from_reader(reader).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a readable task list: {}", file.display(), e),
+            )
+        })?;
+        match parsed {
+            TodoFile::List(list) => list,
+            TodoFile::Tasks(tasks) => TodoList {
+                next_id: 1,
+                tasks,
+                file: PathBuf::new(),
+                layout: None,
+            },
+        }
+    };
+    list.file = file.to_path_buf();
+    if todotxt::is_todotxt(file) {
+        list.next_id = todotxt::read_next_id(file)?;
     }
-    let file = File::open(TODO_FILE)?;
-    let reader = BufReader::new(file);
-    let tasks = serde_json::from_reader(reader).unwrap_or_else(|_| Vec::new());
-    Ok(tasks)
+    // Reading never writes the file. Tasks without an ID get one in memory,
+    // the same one on every read, and it is saved with the next change.
+    list.assign_ids();
+    Ok(list)
 }
 
-fn save_tasks(tasks: &[Task]) -> io::Result<()> {
-    let file = OpenOptions::new()
+fn save_tasks(list: &TodoList) -> io::Result<()> {
+    let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(TODO_FILE)?;
-    serde_json::to_writer_pretty(file, tasks)?;
+        .open(&list.file)?;
+    if todotxt::is_todotxt(&list.file) {
+        file.write_all(todotxt::write(list).as_bytes())?;
+        todotxt::write_next_id(&list.file, list.next_id)?;
+    } else {
+        serde_json::to_writer_pretty(file, list)?;
+    }
+    Ok(())
+}
+
+/// How a task is shown in listings and menus. Pending tasks that are
+/// overdue or due today have their due label highlighted.
+fn task_line(task: &Task, now: NaiveDateTime) -> String {
+    let status = if task.done { "[x]" } else { "[ ]" };
+    let priority = match task.priority {
+        Some(priority) => format!("({}) ", priority),
+        None => String::new(),
+    };
+    let mut line = format!("{} {}: {}{}", status, task.id, priority, task.description);
+    if let Some(due) = task.due {
+        let label = format!("(due {})", due.label(now.date()));
+        let label = match (task.done, due.status(now)) {
+            (false, Status::Overdue) => style(label).red().bold().to_string(),
+            (false, Status::Today) => style(label).yellow().bold().to_string(),
+            _ => label,
+        };
+        line = format!("{} {}", line, label);
+    }
+    line
+}
+
+fn parse_priority(text: &str) -> io::Result<char> {
+    match text.as_bytes() {
+        [letter] if letter.is_ascii_alphabetic() => Ok(letter.to_ascii_uppercase() as char),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a priority; use a letter from A to Z", text),
+        )),
+    }
+}
+
+fn parse_due(text: &str) -> io::Result<Due> {
+    due::parse(text, &Local::now()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+#[derive(Parser)]
+#[command(about = "A small to-do list")]
+struct Cli {
+    /// The list to use. A file ending in .txt is kept in todo.txt format.
+    #[arg(long, global = true, default_value = TODO_FILE)]
+    file: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add a task
+    Add {
+        text: String,
+        /// When it is due: 2026-10-23, tomorrow 5pm, next fri, in 3 days, eom...
+        #[arg(long)]
+        due: Option<String>,
+        /// Priority, A (highest) to Z
+        #[arg(long)]
+        priority: Option<String>,
+    },
+    /// List tasks; pending ones unless asked otherwise
+    List {
+        /// Show done and pending tasks
+        #[arg(long, conflicts_with_all = ["done", "pending"])]
+        all: bool,
+        /// Show only done tasks
+        #[arg(long, conflicts_with = "pending")]
+        done: bool,
+        /// Show only pending tasks (the default)
+        #[arg(long)]
+        pending: bool,
+        /// Only tasks tagged +PROJECT
+        #[arg(long)]
+        project: Option<String>,
+        /// Only tasks tagged @CONTEXT
+        #[arg(long)]
+        context: Option<String>,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Mark a task as done
+    Done { id: String },
+    /// Mark a task as not done
+    Undo { id: String },
+    /// Change a task's text or due date
+    Edit {
+        id: String,
+        text: Option<String>,
+        /// New due date, in any form `add --due` takes
+        #[arg(long, conflicts_with = "no_due")]
+        due: Option<String>,
+        /// Remove the due date
+        #[arg(long)]
+        no_due: bool,
+        /// New priority, A (highest) to Z
+        #[arg(long, conflicts_with = "no_priority")]
+        priority: Option<String>,
+        /// Remove the priority
+        #[arg(long)]
+        no_priority: bool,
+    },
+    /// Delete a task
+    Rm { id: String },
+    /// Pending tasks with a due date, grouped by day
+    Agenda {
+        /// How many days ahead to show, from today
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+    /// Add the tasks of a todo.txt file, with new IDs
+    Import { path: PathBuf },
+    /// Write every task to a todo.txt file
+    Export { path: PathBuf },
+}
+
+/// A task as `list --json` prints it.
+#[derive(Serialize)]
+struct Listed<'a> {
+    id: u32,
+    description: &'a str,
+    done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due: Option<Due>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<char>,
+    projects: Vec<&'a str>,
+    contexts: Vec<&'a str>,
+}
+
+fn check_text(text: &str) -> io::Result<()> {
+    if text.trim().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "task description cannot be empty",
+        ));
+    }
+    Ok(())
+}
+
+fn set_done(list: &mut TodoList, id: &str, done: bool) -> io::Result<()> {
+    let i = list.find(id)?;
+    list.tasks[i].set_done(done);
+    save_tasks(list)?;
+    println!(
+        "Task {} marked as {}.",
+        list.tasks[i].id,
+        if done { "done" } else { "not done" }
+    );
     Ok(())
 }
 
+/// Overdue tasks first, then a heading per day up to `days` ahead, each
+/// day's tasks in order of time, untimed ones first.
+fn print_agenda(list: &TodoList, days: u32, now: NaiveDateTime) {
+    let today = now.date();
+    let last = today + chrono::Days::new(days.into());
+    let mut overdue = Vec::new();
+    let mut by_day: BTreeMap<_, Vec<&Task>> = BTreeMap::new();
+    let mut later = 0;
+    for task in list.tasks.iter().filter(|t| !t.done) {
+        let Some(due) = task.due else { continue };
+        if due.status(now) == Status::Overdue {
+            overdue.push(task);
+        } else if due.date <= last {
+            by_day.entry(due.date).or_default().push(task);
+        } else {
+            later += 1;
+        }
+    }
+    if overdue.is_empty() && by_day.is_empty() {
+        println!("Nothing due in the next {} days.", days);
+    }
+    if !overdue.is_empty() {
+        overdue.sort_by_key(|t| t.due);
+        println!("{}", style("Overdue").red().bold());
+        for task in overdue {
+            println!("  {}", task_line(task, now));
+        }
+    }
+    for (date, mut tasks) in by_day {
+        tasks.sort_by_key(|t| t.due);
+        let heading = format!("{}", date.format("%a %d %b"));
+        match (date - today).num_days() {
+            0 => println!("{}", style(format!("{} (today)", heading)).yellow().bold()),
+            1 => println!("{} (tomorrow)", heading),
+            _ => println!("{}", heading),
+        }
+        for task in tasks {
+            let time = match task.due.and_then(|d| d.time) {
+                Some(time) => time.format("%H:%M").to_string(),
+                None => String::new(),
+            };
+            println!("  {:>5}  {}: {}", time, task.id, task.description);
+        }
+    }
+    if later > 0 {
+        println!("({} more due later; see them with --days)", later);
+    }
+}
+
 fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    let mut list = load_tasks(&cli.file)?;
+
+    match cli.command {
+        None => run_menu(list),
+        Some(Command::Add {
+            text,
+            due,
+            priority,
+        }) => {
+            check_text(&text)?;
+            let due = due.as_deref().map(parse_due).transpose()?;
+            let priority = priority.as_deref().map(parse_priority).transpose()?;
+            let id = list.add(text, due);
+            if let Some(task) = list.tasks.last_mut() {
+                task.priority = priority;
+            }
+            save_tasks(&list)?;
+            println!("Added task {}.", id);
+            Ok(())
+        }
+        Some(Command::List {
+            all,
+            done,
+            pending: _,
+            project,
+            context,
+            json,
+        }) => {
+            let tagged = |task: &Task, marker, tag: &Option<String>| match tag {
+                Some(tag) => todotxt::tags(&task.description, marker).contains(&tag.as_str()),
+                None => true,
+            };
+            let shown: Vec<&Task> = list
+                .tasks
+                .iter()
+                .filter(|t| all || t.done == done)
+                .filter(|t| tagged(t, '+', &project) && tagged(t, '@', &context))
+                .collect();
+            if json {
+                let listed: Vec<Listed> = shown
+                    .iter()
+                    .map(|t| Listed {
+                        id: t.id,
+                        description: &t.description,
+                        done: t.done,
+                        due: t.due,
+                        priority: t.priority,
+                        projects: todotxt::tags(&t.description, '+'),
+                        contexts: todotxt::tags(&t.description, '@'),
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&listed)?);
+            } else if shown.is_empty() {
+                println!("No tasks found.");
+            } else {
+                let now = Local::now().naive_local();
+                for task in shown {
+                    println!("{}", task_line(task, now));
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Done { id }) => set_done(&mut list, &id, true),
+        Some(Command::Undo { id }) => set_done(&mut list, &id, false),
+        Some(Command::Edit {
+            id,
+            text,
+            due,
+            no_due,
+            priority,
+            no_priority,
+        }) => {
+            if text.is_none() && due.is_none() && !no_due && priority.is_none() && !no_priority {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "give new text, --due, --no-due, --priority or --no-priority",
+                ));
+            }
+            let i = list.find(&id)?;
+            if let Some(text) = text {
+                check_text(&text)?;
+                list.tasks[i].description = text;
+            }
+            if let Some(due) = due {
+                list.tasks[i].due = Some(parse_due(&due)?);
+            } else if no_due {
+                list.tasks[i].due = None;
+            }
+            if let Some(priority) = priority {
+                list.tasks[i].priority = Some(parse_priority(&priority)?);
+            } else if no_priority {
+                list.tasks[i].priority = None;
+            }
+            save_tasks(&list)?;
+            println!("Task {} updated.", list.tasks[i].id);
+            Ok(())
+        }
+        Some(Command::Rm { id }) => {
+            let i = list.find(&id)?;
+            let task = list.tasks.remove(i);
+            save_tasks(&list)?;
+            println!("Deleted task {}: {}", task.id, task.description);
+            Ok(())
+        }
+        Some(Command::Agenda { days }) => {
+            print_agenda(&list, days, Local::now().naive_local());
+            Ok(())
+        }
+        Some(Command::Import { path }) => {
+            let imported = todotxt::read(&fs::read_to_string(&path)?);
+            let count = imported.tasks.len();
+            for task in imported.tasks {
+                list.tasks.push(Task {
+                    id: list.next_id,
+                    line: None,
+                    ..task
+                });
+                list.next_id += 1;
+            }
+            save_tasks(&list)?;
+            println!("Imported {} task(s) from {}.", count, path.display());
+            Ok(())
+        }
+        Some(Command::Export { path }) => {
+            fs::write(&path, todotxt::write(&list))?;
+            println!(
+                "Exported {} task(s) to {}.",
+                list.tasks.len(),
+                path.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Asks for a due date until one reads; an empty answer means none.
+fn ask_due(theme: &ColorfulTheme, current: Option<Due>) -> Option<Due> {
+    loop {
+        let mut input = Input::<String>::with_theme(theme);
+        input
+            .with_prompt("Due date (e.g. tomorrow, next fri 5pm; empty for none)")
+            .allow_empty(true);
+        if let Some(due) = current {
+            input.with_initial_text(due.to_string());
+        }
+        let text = input.interact_text().unwrap();
+        if text.trim().is_empty() {
+            return None;
+        }
+        match parse_due(&text) {
+            Ok(due) => return Some(due),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn run_menu(mut list: TodoList) -> io::Result<()> {
     let theme = ColorfulTheme::default();
-    let mut tasks = load_tasks()?;
 
     loop {
         println!("\n--- To-Do List ---\n");
@@ -72,40 +575,35 @@ fn main() -> io::Result<()> {
                     .interact_text()
                     .unwrap();
                 if !input.trim().is_empty() {
-                    tasks.push(Task::new(input));
-                    save_tasks(&tasks)?;
-                    println!("Task added.");
+                    let due = ask_due(&theme, None);
+                    let id = list.add(input, due);
+                    save_tasks(&list)?;
+                    println!("Task {} added.", id);
                 } else {
                     println!("Task description cannot be empty.");
                 }
             }
             1 => {
                 // List Tasks
-                if tasks.is_empty() {
+                if list.tasks.is_empty() {
                     println!("No tasks found.");
                 } else {
-                    for (i, task) in tasks.iter().enumerate() {
-                        let status = if task.done { "[x]" } else { "[ ]" };
-                        println!("{} {}: {}", status, i + 1, task.description);
+                    let now = Local::now().naive_local();
+                    for task in &list.tasks {
+                        println!("{}", task_line(task, now));
                     }
                 }
             }
             2 => {
                 // Edit Task
-                if tasks.is_empty() {
+                if list.tasks.is_empty() {
                     println!("No tasks to edit.");
                     continue;
                 }
-                let task_strings: Vec<String> = tasks
-                    .iter()
-                    .map(|t| {
-                        format!(
-                            "{}{}",
-                            if t.done { "[x] " } else { "[ ] " },
-                            t.description.clone()
-                        )
-                    })
-                    .collect();
+                let task_strings: Vec<String> = {
+                    let now = Local::now().naive_local();
+                    list.tasks.iter().map(|t| task_line(t, now)).collect()
+                };
 
                 let selected = Select::with_theme(&theme)
                     .with_prompt("Select task to edit")
@@ -116,13 +614,14 @@ fn main() -> io::Result<()> {
 
                 let new_desc: String = Input::with_theme(&theme)
                     .with_prompt("Enter new description")
-                    .with_initial_text(&tasks[selected].description)
+                    .with_initial_text(&list.tasks[selected].description)
                     .interact_text()
                     .unwrap();
 
                 if !new_desc.trim().is_empty() {
-                    tasks[selected].description = new_desc;
-                    save_tasks(&tasks)?;
+                    list.tasks[selected].description = new_desc;
+                    list.tasks[selected].due = ask_due(&theme, list.tasks[selected].due);
+                    save_tasks(&list)?;
                     println!("Task updated.");
                 } else {
                     println!("Description cannot be empty.");
@@ -130,20 +629,14 @@ fn main() -> io::Result<()> {
             }
             3 => {
                 // Delete Task
-                if tasks.is_empty() {
+                if list.tasks.is_empty() {
                     println!("No tasks to delete.");
                     continue;
                 }
-                let task_strings: Vec<String> = tasks
-                    .iter()
-                    .map(|t| {
-                        format!(
-                            "{}{}",
-                            if t.done { "[x] " } else { "[ ] " },
-                            t.description.clone()
-                        )
-                    })
-                    .collect();
+                let task_strings: Vec<String> = {
+                    let now = Local::now().naive_local();
+                    list.tasks.iter().map(|t| task_line(t, now)).collect()
+                };
 
                 let selected = Select::with_theme(&theme)
                     .with_prompt("Select task to delete")
@@ -155,14 +648,14 @@ fn main() -> io::Result<()> {
                 if Confirm::with_theme(&theme)
                     .with_prompt(format!(
                         "Are you sure you want to delete task: '{}'? ",
-                        tasks[selected].description
+                        list.tasks[selected].description
                     ))
                     .default(false)
                     .interact()
                     .unwrap()
                 {
-                    tasks.remove(selected);
-                    save_tasks(&tasks)?;
+                    list.tasks.remove(selected);
+                    save_tasks(&list)?;
                     println!("Task deleted.");
                 } else {
                     println!("Deletion cancelled.");
@@ -170,20 +663,14 @@ fn main() -> io::Result<()> {
             }
             4 => {
                 // Toggle Done/Undone
-                if tasks.is_empty() {
+                if list.tasks.is_empty() {
                     println!("No tasks to toggle.");
                     continue;
                 }
-                let task_strings: Vec<String> = tasks
-                    .iter()
-                    .map(|t| {
-                        format!(
-                            "{}{}",
-                            if t.done { "[x] " } else { "[ ] " },
-                            t.description.clone()
-                        )
-                    })
-                    .collect();
+                let task_strings: Vec<String> = {
+                    let now = Local::now().naive_local();
+                    list.tasks.iter().map(|t| task_line(t, now)).collect()
+                };
 
                 let selected = Select::with_theme(&theme)
                     .with_prompt("Select task to toggle done/undone")
@@ -192,12 +679,13 @@ fn main() -> io::Result<()> {
                     .interact()
                     .unwrap();
 
-                tasks[selected].done = !tasks[selected].done;
-                save_tasks(&tasks)?;
+                let done = !list.tasks[selected].done;
+                list.tasks[selected].set_done(done);
+                save_tasks(&list)?;
                 println!(
                     "Task '{}' marked as {}.",
-                    tasks[selected].description,
-                    if tasks[selected].done {
+                    list.tasks[selected].description,
+                    if list.tasks[selected].done {
                         "done"
                     } else {
                         "not done"
@@ -215,3 +703,169 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path in a fresh directory under the system temp dir.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "todo_cli-{}-{}",
+            std::process::id(),
+            name.replace('.', "-")
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    fn list(ids: &[u32], next_id: u32) -> TodoList {
+        TodoList {
+            next_id,
+            tasks: ids
+                .iter()
+                .map(|&id| Task {
+                    id,
+                    ..Task::new(format!("task {}", id))
+                })
+                .collect(),
+            file: PathBuf::new(),
+            layout: None,
+        }
+    }
+
+    fn ids(list: &TodoList) -> Vec<u32> {
+        list.tasks.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn legacy_task_lists_are_numbered_in_file_order() {
+        let file = scratch("legacy.json");
+        fs::write(
+            &file,
+            r#"[{"description": "a", "done": false},
+                {"description": "b", "done": true},
+                {"description": "c", "done": false}]"#,
+        )
+        .unwrap();
+        let before = fs::read_to_string(&file).unwrap();
+        let list = load_tasks(&file).unwrap();
+        assert_eq!(ids(&list), [1, 2, 3]);
+        assert_eq!(list.next_id, 4);
+        assert_eq!(list.tasks[1].description, "b");
+        assert!(list.tasks[1].done);
+        // Written with IDs on the next change, not when read.
+        assert_eq!(fs::read_to_string(&file).unwrap(), before);
+        save_tasks(&list).unwrap();
+        let list = load_tasks(&file).unwrap();
+        assert_eq!(ids(&list), [1, 2, 3]);
+        assert!(fs::read_to_string(&file)
+            .unwrap()
+            .contains("\"next_id\": 4"));
+    }
+
+    #[test]
+    fn an_unreadable_file_is_an_error_and_left_alone() {
+        let file = scratch("broken.json");
+        let broken = r#"{"next_id": 3, "tasks": [{"id": 1, "descr"#;
+        fs::write(&file, broken).unwrap();
+        let err = load_tasks(&file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("is not a readable task list"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), broken);
+    }
+
+    #[test]
+    fn assign_ids_numbers_new_tasks_after_the_highest() {
+        let mut fresh = list(&[1, 2], 3);
+        assert!(!fresh.assign_ids());
+
+        let mut mixed = list(&[0, 7, 0, 3], 1);
+        assert!(mixed.assign_ids());
+        assert_eq!(ids(&mixed), [8, 7, 9, 3]);
+        assert_eq!(mixed.next_id, 10);
+
+        // A high-water mark above every task is kept, so deleted IDs
+        // aren't handed out again.
+        let mut gaps = list(&[0, 2], 6);
+        assert!(gaps.assign_ids());
+        assert_eq!(ids(&gaps), [6, 2]);
+        assert_eq!(gaps.next_id, 7);
+    }
+
+    #[test]
+    fn find_matches_exact_ids_and_refuses_deleted_ones() {
+        let list = list(&[1, 3, 21], 22);
+        assert_eq!(list.find("3").unwrap(), 1);
+        assert_eq!(list.find(" #21 ").unwrap(), 2);
+        let deleted = list.find("2").unwrap_err();
+        assert_eq!(deleted.kind(), io::ErrorKind::NotFound);
+        assert_eq!(deleted.to_string(), "no task 2 (it was deleted)");
+        assert_eq!(list.find("22").unwrap_err().to_string(), "no task 22");
+        for bad in ["", "x", "-1", "1.5"] {
+            let err = list.find(bad).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn loaded_lists_address_tasks_by_exact_id_only() {
+        for name in ["exact.json", "exact.txt"] {
+            let file = scratch(name);
+            let mut list = load_tasks(&file).unwrap();
+            for i in 1..=21 {
+                list.add(format!("task {}", i), None);
+            }
+            list.tasks.retain(|t| [1, 3, 21].contains(&t.id));
+            save_tasks(&list).unwrap();
+
+            let list = load_tasks(&file).unwrap();
+            assert_eq!(list.find("21").unwrap(), 2, "{}", name);
+            // Not a prefix of 21: task 2 is gone.
+            let deleted = list.find("2").unwrap_err();
+            assert_eq!(deleted.to_string(), "no task 2 (it was deleted)");
+            assert_eq!(list.find("22").unwrap_err().to_string(), "no task 22");
+        }
+    }
+
+    #[test]
+    fn reading_a_todotxt_file_does_not_rewrite_it() {
+        let file = scratch("untouched.txt");
+        let text = "(A) call the plumber\nb id:5\n\nx done\n";
+        fs::write(&file, text).unwrap();
+        for _ in 0..2 {
+            let list = load_tasks(&file).unwrap();
+            assert_eq!(ids(&list), [6, 5, 7]);
+            assert_eq!(fs::read_to_string(&file).unwrap(), text);
+        }
+        assert!(!todotxt::next_id_path(&file).exists());
+    }
+
+    #[test]
+    fn ids_of_removed_tasks_are_not_reused() {
+        for name in ["reuse.txt", "reuse.json"] {
+            let file = scratch(name);
+            let mut list = load_tasks(&file).unwrap();
+            list.add("a".to_string(), None);
+            list.add("b".to_string(), None);
+            save_tasks(&list).unwrap();
+
+            let mut list = load_tasks(&file).unwrap();
+            let i = list.find("2").unwrap();
+            list.tasks.remove(i);
+            save_tasks(&list).unwrap();
+
+            let mut list = load_tasks(&file).unwrap();
+            assert_eq!(list.add("c".to_string(), None), 3, "{}", name);
+            save_tasks(&list).unwrap();
+            let list = load_tasks(&file).unwrap();
+            assert_eq!(ids(&list), [1, 3], "{}", name);
+            assert!(list.find("2").is_err());
+        }
+        let file = scratch("ids.txt");
+        fs::write(todotxt::next_id_path(&file), "lots\n").unwrap();
+        let err = load_tasks(&file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
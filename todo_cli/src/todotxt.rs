@@ -0,0 +1,380 @@
+//! The todo.txt format (<https://github.com/todotxt/todo.txt>): one task per
+//! line, such as
+//!
+//! ```text
+//! (A) 2026-10-19 Call the plumber +house @phone due:2026-10-23 id:4
+//! x 2026-10-20 2026-10-19 Buy milk @shop id:5
+//! ```
+//!
+//! `+project`, `@context` and other `key:value` extensions stay in the
+//! description. `due:` and `id:` are read into the task, and so is `pri:`
+//! on completed ones, where the priority can't be written in front.
+//!
+//! Lines are written back exactly as they were read unless their task
+//! changed, so blank lines, spacing and anything this tool doesn't
+//! understand survive a round trip byte for byte.
+//!
+//! The next ID to give out is kept in a file of its own next to the list
+//! (`todo.txt.next-id`), so the IDs of deleted tasks aren't reused.
+
+use crate::due::Due;
+use crate::{Task, TodoList};
+use chrono::NaiveDate;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The lines of a todo.txt file as read, so it can be written back with
+/// only the changed tasks rewritten.
+#[derive(Debug, Default)]
+pub struct Layout {
+    lines: Vec<Line>,
+    crlf: bool,
+    final_newline: bool,
+}
+
+#[derive(Debug)]
+enum Line {
+    /// A task line, with the task as it was read. Tasks point back at
+    /// their line through `Task::line`.
+    Task { raw: String, read: Task },
+    /// A blank line or anything else that isn't a task.
+    Other(String),
+}
+
+/// Whether `path` is read and written as todo.txt rather than JSON.
+pub fn is_todotxt(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "txt")
+}
+
+/// Where the next ID for the list in `path` is kept.
+pub fn next_id_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".next-id");
+    path.with_file_name(name)
+}
+
+/// The next ID saved for the list in `path`; 1 if none was saved yet.
+pub fn read_next_id(path: &Path) -> io::Result<u32> {
+    let sidecar = next_id_path(path);
+    match fs::read_to_string(&sidecar) {
+        Ok(text) => text.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} does not hold a task ID", sidecar.display()),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(1),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_next_id(path: &Path, next_id: u32) -> io::Result<()> {
+    fs::write(next_id_path(path), format!("{}\n", next_id))
+}
+
+/// Reads a whole file. Tasks without an `id:` are left with ID 0, as are
+/// repeats of an ID already used by an earlier line, for
+/// `TodoList::assign_ids` to number.
+pub fn read(text: &str) -> TodoList {
+    let final_newline = text.ends_with('\n');
+    let body = text.strip_suffix('\n').unwrap_or(text);
+    let crlf = body.split('\n').next().is_some_and(|l| l.ends_with('\r'));
+    let mut layout = Layout {
+        lines: Vec::new(),
+        crlf,
+        final_newline,
+    };
+    let mut tasks = Vec::new();
+    let mut seen = BTreeSet::new();
+    if !text.is_empty() {
+        for (i, raw) in body.split('\n').enumerate() {
+            let line = raw.strip_suffix('\r').unwrap_or(raw);
+            match parse_line(line) {
+                Some(mut task) => {
+                    task.line = Some(i);
+                    let read = task.clone();
+                    if !seen.insert(task.id) {
+                        task.id = 0;
+                    }
+                    layout.lines.push(Line::Task {
+                        raw: raw.to_string(),
+                        read,
+                    });
+                    tasks.push(task);
+                }
+                None => layout.lines.push(Line::Other(raw.to_string())),
+            }
+        }
+    }
+    TodoList {
+        next_id: 1,
+        tasks,
+        file: Default::default(),
+        layout: Some(layout),
+    }
+}
+
+/// The file for `list`: its lines as read, with changed tasks rewritten,
+/// deleted ones dropped and new ones added at the end.
+pub fn write(list: &TodoList) -> String {
+    let empty = Layout::default();
+    let layout = list.layout.as_ref().unwrap_or(&empty);
+    let by_line: HashMap<usize, &Task> = list
+        .tasks
+        .iter()
+        .filter_map(|t| t.line.map(|line| (line, t)))
+        .collect();
+    let mut lines = Vec::new();
+    for (i, line) in layout.lines.iter().enumerate() {
+        match line {
+            Line::Other(raw) => lines.push(raw.clone()),
+            Line::Task { raw, read } => match by_line.get(&i) {
+                Some(task) if *task == read => lines.push(raw.clone()),
+                // Only given an ID: the rest of the line is kept as it was.
+                Some(task)
+                    if read.id == 0
+                        && Task {
+                            id: 0,
+                            ..(*task).clone()
+                        } == *read =>
+                {
+                    let (line, cr) = match raw.strip_suffix('\r') {
+                        Some(line) => (line, "\r"),
+                        None => (raw.as_str(), ""),
+                    };
+                    lines.push(format!("{} id:{}{}", line.trim_end(), task.id, cr));
+                }
+                Some(task) => lines.push(end_line(format_task(task), layout.crlf)),
+                None => {}
+            },
+        }
+    }
+    let mut added = false;
+    for task in list.tasks.iter().filter(|t| t.line.is_none()) {
+        lines.push(end_line(format_task(task), layout.crlf));
+        added = true;
+    }
+    let mut text = lines.join("\n");
+    if !lines.is_empty() && (layout.final_newline || added) {
+        text.push('\n');
+    }
+    text
+}
+
+fn end_line(line: String, crlf: bool) -> String {
+    if crlf {
+        line + "\r"
+    } else {
+        line
+    }
+}
+
+/// One line as a task, or `None` for a line that has none, such as a
+/// blank one.
+pub fn parse_line(line: &str) -> Option<Task> {
+    let mut rest = line;
+    let mut task = Task::new(String::new());
+    if let Some(after) = rest.strip_prefix("x ") {
+        task.done = true;
+        rest = after;
+        if let Some((date, after)) = leading_date(rest) {
+            task.completed = Some(date);
+            rest = after;
+            if let Some((date, after)) = leading_date(rest) {
+                task.created = Some(date);
+                rest = after;
+            }
+        }
+    } else {
+        let bytes = rest.as_bytes();
+        if bytes.len() >= 4
+            && bytes[0] == b'('
+            && bytes[1].is_ascii_uppercase()
+            && bytes[2] == b')'
+            && bytes[3] == b' '
+        {
+            task.priority = Some(bytes[1] as char);
+            rest = &rest[4..];
+        }
+        if let Some((date, after)) = leading_date(rest) {
+            task.created = Some(date);
+            rest = after;
+        }
+    }
+
+    let mut words = Vec::new();
+    for word in rest.split_whitespace() {
+        match word.split_once(':') {
+            Some(("due", value)) if task.due.is_none() => match value.parse::<Due>() {
+                Ok(due) => task.due = Some(due),
+                Err(_) => words.push(word),
+            },
+            Some(("id", value)) if task.id == 0 => match value.parse::<u32>() {
+                Ok(id) if id > 0 => task.id = id,
+                _ => words.push(word),
+            },
+            Some(("pri", value))
+                if task.done
+                    && task.priority.is_none()
+                    && value.len() == 1
+                    && value.as_bytes()[0].is_ascii_uppercase() =>
+            {
+                task.priority = value.chars().next();
+            }
+            _ => words.push(word),
+        }
+    }
+    if words.is_empty() {
+        return None;
+    }
+    task.description = words.join(" ");
+    Some(task)
+}
+
+/// `task` as a todo.txt line.
+pub fn format_task(task: &Task) -> String {
+    let mut parts = Vec::new();
+    if task.done {
+        parts.push("x".to_string());
+        // A creation date alone would be read back as the completion date.
+        if let Some(completed) = task.completed {
+            parts.push(completed.to_string());
+            parts.extend(task.created.map(|d| d.to_string()));
+        }
+    } else {
+        parts.extend(task.priority.map(|p| format!("({})", p)));
+        parts.extend(task.created.map(|d| d.to_string()));
+    }
+    parts.push(task.description.clone());
+    if task.done
+        && let Some(priority) = task.priority
+    {
+        parts.push(format!("pri:{}", priority));
+    }
+    parts.extend(task.due.map(|due| format!("due:{}", due)));
+    if task.id > 0 {
+        parts.push(format!("id:{}", task.id));
+    }
+    parts.join(" ")
+}
+
+/// A `YYYY-MM-DD ` at the start of `text`, and what follows it.
+fn leading_date(text: &str) -> Option<(NaiveDate, &str)> {
+    let (word, rest) = text.split_once(' ')?;
+    let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    (word.len() == 10).then_some((date, rest))
+}
+
+/// The `+project` or `@context` tags in `description`, by `marker`.
+pub fn tags(description: &str, marker: char) -> Vec<&str> {
+    description
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix(marker))
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn reads_the_parts_of_a_line() {
+        let task = parse_line("(A) 2026-10-19 Call the plumber +house @phone due:2026-10-23 id:4")
+            .unwrap();
+        assert_eq!(task.priority, Some('A'));
+        assert_eq!(task.created, date(2026, 10, 19));
+        assert_eq!(task.description, "Call the plumber +house @phone");
+        assert_eq!(task.due, Some("2026-10-23".parse().unwrap()));
+        assert_eq!(task.id, 4);
+        assert!(!task.done);
+        assert_eq!(tags(&task.description, '+'), ["house"]);
+        assert_eq!(tags(&task.description, '@'), ["phone"]);
+
+        let task = parse_line("x 2026-10-20 2026-10-19 Buy milk pri:B rec:1w").unwrap();
+        assert!(task.done);
+        assert_eq!(task.completed, date(2026, 10, 20));
+        assert_eq!(task.created, date(2026, 10, 19));
+        assert_eq!(task.priority, Some('B'));
+        assert_eq!(task.description, "Buy milk rec:1w");
+    }
+
+    #[test]
+    fn things_that_only_look_like_parts_stay_in_the_description() {
+        let task = parse_line("xylophone lessons").unwrap();
+        assert!(!task.done);
+        assert_eq!(task.description, "xylophone lessons");
+        let task = parse_line("(a) lower case is not a priority").unwrap();
+        assert_eq!(task.priority, None);
+        let task = parse_line("Meet at 2026-10-19 due:someday id:x").unwrap();
+        assert_eq!(task.created, None);
+        assert_eq!((task.due, task.id), (None, 0));
+        assert_eq!(task.description, "Meet at 2026-10-19 due:someday id:x");
+        // Priority after the completion mark isn't one.
+        let task = parse_line("x (A) done").unwrap();
+        assert_eq!(task.priority, None);
+        assert_eq!(task.description, "(A) done");
+    }
+
+    #[test]
+    fn unchanged_files_round_trip_byte_for_byte() {
+        let texts = [
+            "(A) 2026-10-19 first  +p   @c id:1\n\n   \nx 2026-10-20 done id:2\nx\n",
+            "no newline at the end id:1",
+            "crlf id:1\r\n\r\n(B) second id:2\r\n",
+            "",
+            "\n\n",
+        ];
+        for text in texts {
+            let list = read(text);
+            assert_eq!(write(&list), text, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn only_changed_tasks_are_rewritten() {
+        let text = "keep   this  spacing id:1\r\n\r\nchange me id:2\r\ndelete me id:3\r\n";
+        let mut list = read(text);
+        list.tasks[1].done = true;
+        list.tasks[1].completed = date(2026, 10, 21);
+        list.tasks.remove(2);
+        list.add("new one +p".to_string(), None);
+        list.tasks[2].id = 9;
+        list.tasks[2].created = None;
+        assert_eq!(
+            write(&list),
+            "keep   this  spacing id:1\r\n\r\nx 2026-10-21 change me id:2\r\nnew one +p id:9\r\n"
+        );
+    }
+
+    #[test]
+    fn missing_and_repeated_ids_are_numbered() {
+        let mut list = read("a id:3\nb\nc id:3\n");
+        assert!(list.assign_ids());
+        let ids: Vec<u32> = list.tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [3, 4, 5]);
+        assert_eq!(write(&list), "a id:3\nb id:4\nc id:5\n");
+        let mut list = read("(A)  spaced   out  \r\n");
+        list.assign_ids();
+        assert_eq!(write(&list), "(A)  spaced   out id:1\r\n");
+    }
+
+    #[test]
+    fn formatted_tasks_read_back_the_same() {
+        let lines = [
+            "(C) 2026-10-19 plain +p @c key:value due:2026-10-23T17:00 id:7",
+            "x 2026-10-20 2026-10-19 finished pri:A id:8",
+            "x 2026-10-20 finished without creation date id:9",
+        ];
+        for line in lines {
+            let task = parse_line(line).unwrap();
+            assert_eq!(format_task(&task), line);
+        }
+    }
+}
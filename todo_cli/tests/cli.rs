@@ -0,0 +1,163 @@
+//! The subcommands, run through the binary against a list in a fresh
+//! directory under the system temp dir.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A path in a fresh directory under the system temp dir.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "todo-cli-test-{}-{}",
+        std::process::id(),
+        name.replace('.', "-")
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn todo(file: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_todo"))
+        .arg("--file")
+        .arg(file)
+        .args(args)
+        .output()
+        .expect("start todo")
+}
+
+/// Runs `args` and returns stdout, failing the test if the command did.
+fn ok(file: &PathBuf, args: &[&str]) -> String {
+    let output = todo(file, args);
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs `args`, which must fail, and returns stderr.
+fn fails(file: &PathBuf, args: &[&str]) -> String {
+    let output = todo(file, args);
+    assert!(!output.status.success(), "{:?} succeeded", args);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+/// The IDs `list --json` prints with `filter`.
+fn listed(file: &PathBuf, filter: &[&str]) -> Vec<u64> {
+    let args = [&["list", "--json"], filter].concat();
+    let tasks: serde_json::Value = serde_json::from_str(&ok(file, &args)).unwrap();
+    tasks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn add_done_undo_edit_rm() {
+    for name in ["session.json", "session.txt"] {
+        let file = scratch(name);
+        assert_eq!(ok(&file, &["add", "buy milk"]), "Added task 1.\n");
+        assert_eq!(
+            ok(&file, &["add", "call +home @phone", "--priority", "b"]),
+            "Added task 2.\n"
+        );
+        assert_eq!(ok(&file, &["done", "1"]), "Task 1 marked as done.\n");
+        assert_eq!(ok(&file, &["list"]), "[ ] 2: (B) call +home @phone\n");
+        assert_eq!(ok(&file, &["list", "--done"]), "[x] 1: buy milk\n");
+
+        assert_eq!(ok(&file, &["undo", "#1"]), "Task 1 marked as not done.\n");
+        assert_eq!(
+            ok(&file, &["edit", "1", "buy oat milk", "--priority", "A"]),
+            "Task 1 updated.\n"
+        );
+        assert_eq!(
+            ok(&file, &["list", "--pending"]),
+            "[ ] 1: (A) buy oat milk\n[ ] 2: (B) call +home @phone\n"
+        );
+
+        assert_eq!(
+            ok(&file, &["rm", "2"]),
+            "Deleted task 2: call +home @phone\n"
+        );
+        assert_eq!(ok(&file, &["add", "water plants"]), "Added task 3.\n");
+        assert_eq!(listed(&file, &["--all"]), [1, 3], "{}", name);
+        assert!(fails(&file, &["done", "2"]).contains("no task 2 (it was deleted)"));
+    }
+}
+
+#[test]
+fn list_filters() {
+    let file = scratch("filters.json");
+    for text in ["a +work", "b @home", "c +work @home"] {
+        ok(&file, &["add", text]);
+    }
+    ok(&file, &["done", "2"]);
+    assert_eq!(listed(&file, &[]), [1, 3]);
+    assert_eq!(listed(&file, &["--pending"]), [1, 3]);
+    assert_eq!(listed(&file, &["--done"]), [2]);
+    assert_eq!(listed(&file, &["--all"]), [1, 2, 3]);
+    assert_eq!(listed(&file, &["--all", "--context", "home"]), [2, 3]);
+    assert_eq!(listed(&file, &["--project", "work"]), [1, 3]);
+    assert_eq!(
+        ok(&file, &["list", "--project", "none"]),
+        "No tasks found.\n"
+    );
+}
+
+#[test]
+fn list_json_describes_each_task() {
+    let file = scratch("json.json");
+    ok(&file, &["add", "ship it +release @work", "--priority", "c"]);
+    ok(&file, &["add", "plain", "--due", "2030-01-02"]);
+    let mut tasks: serde_json::Value =
+        serde_json::from_str(&ok(&file, &["list", "--json"])).unwrap();
+    let due = tasks[1].as_object_mut().unwrap().remove("due").unwrap();
+    assert!(due.to_string().contains("2030-01-02"), "{}", due);
+    assert_eq!(
+        tasks,
+        serde_json::json!([
+            {
+                "id": 1,
+                "description": "ship it +release @work",
+                "done": false,
+                "priority": "C",
+                "projects": ["release"],
+                "contexts": ["work"],
+            },
+            {
+                "id": 2,
+                "description": "plain",
+                "done": false,
+                "projects": [],
+                "contexts": [],
+            },
+        ])
+    );
+    assert_eq!(ok(&scratch("empty.json"), &["list", "--json"]), "[]\n");
+}
+
+#[test]
+fn bad_arguments_are_refused() {
+    let file = scratch("bad.json");
+    ok(&file, &["add", "a"]);
+    for args in [
+        &["done"][..],
+        &["list", "--all", "--done"],
+        &["list", "--done", "--pending"],
+        &["edit", "1", "--due", "tomorrow", "--no-due"],
+        &["frobnicate"],
+    ] {
+        assert!(fails(&file, args).contains("Usage"), "{:?}", args);
+    }
+    assert!(fails(&file, &["add", "  "]).contains("cannot be empty"));
+    assert!(fails(&file, &["done", "x"]).contains("'x' is not a task ID"));
+    assert!(fails(&file, &["done", "7"]).contains("no task 7"));
+    assert!(fails(&file, &["edit", "1"]).contains("give new text"));
+    assert!(fails(&file, &["add", "b", "--priority", "high"]).contains("not a priority"));
+    assert_eq!(listed(&file, &["--all"]), [1]);
+}